@@ -0,0 +1,86 @@
+use card_engine::germanwhist::PlayerState;
+use card_engine::learning::neural_net::{
+    mat_t_vec_mul, mat_vec_mul, outer_product, ActivationFunction, LayerDesc, NeuralNet,
+};
+use criterion::{criterion_group, criterion_main, Criterion};
+use ndarray::linalg::general_mat_vec_mul;
+use ndarray::Array;
+
+// The hidden layer used by `basic_player`.
+const HIDDEN: usize = 100;
+
+fn inputs() -> usize {
+    PlayerState::state_action_size()
+}
+
+fn bench_mat_vec_mul(c: &mut Criterion) {
+    let n = inputs();
+    let a = Array::from_shape_fn((HIDDEN, n), |(i, j)| ((i * n + j) as f32).sin());
+    let x = Array::from_shape_fn(n, |j| (j as f32).cos());
+    let mut y = Array::zeros(HIDDEN);
+
+    c.bench_function("mat_vec_mul", |b| b.iter(|| mat_vec_mul(&mut y, &a, &x)));
+    c.bench_function("general_mat_vec_mul", |b| {
+        b.iter(|| general_mat_vec_mul(1.0, &a, &x, 0.0, &mut y))
+    });
+}
+
+fn bench_mat_t_vec_mul(c: &mut Criterion) {
+    let n = inputs();
+    let a = Array::from_shape_fn((HIDDEN, n), |(i, j)| ((i * n + j) as f32).sin());
+    let x = Array::from_shape_fn(HIDDEN, |i| (i as f32).cos());
+    let mut y = Array::zeros(n);
+
+    c.bench_function("mat_t_vec_mul", |b| {
+        b.iter(|| mat_t_vec_mul(&mut y, &a, &x))
+    });
+    c.bench_function("general_mat_vec_mul (transposed)", |b| {
+        b.iter(|| general_mat_vec_mul(1.0, &a.t(), &x, 0.0, &mut y))
+    });
+}
+
+fn bench_outer_product(c: &mut Criterion) {
+    let n = inputs();
+    let a = Array::from_shape_fn(HIDDEN, |i| (i as f32).sin());
+    let x = Array::from_shape_fn(n, |j| (j as f32).cos());
+    let mut m = Array::zeros((HIDDEN, n));
+
+    c.bench_function("outer_product", |b| {
+        b.iter(|| outer_product(&mut m, &a, &x))
+    });
+}
+
+fn bench_evaluate_with_gradient(c: &mut Criterion) {
+    let n = inputs();
+    let net = NeuralNet::new(
+        &[
+            LayerDesc::new(n, HIDDEN, ActivationFunction::SymmetricSigmoid),
+            LayerDesc::new(HIDDEN, 1, ActivationFunction::Sigmoid),
+        ],
+        0.05,
+    )
+    .unwrap();
+    let x = Array::from_shape_fn(n, |j| (j as f32).cos());
+    let mut g = Array::zeros(net.num_parameters());
+
+    c.bench_function("evaluate_with_gradient", |b| {
+        b.iter(|| net.evaluate_with_gradient(&x, g.view_mut()))
+    });
+}
+
+fn bench_state_vector(c: &mut Criterion) {
+    let ps = PlayerState::new(0);
+    let mut v = Array::zeros(PlayerState::state_size());
+
+    c.bench_function("state_vector", |b| b.iter(|| ps.state_vector(v.view_mut())));
+}
+
+criterion_group!(
+    benches,
+    bench_mat_vec_mul,
+    bench_mat_t_vec_mul,
+    bench_outer_product,
+    bench_evaluate_with_gradient,
+    bench_state_vector
+);
+criterion_main!(benches);
@@ -0,0 +1,44 @@
+use card_engine::germanwhist::{GameEvent, Round, STANDARD_SCORING};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+
+// Deal used for every iteration, so both variants play the same cards.
+// Dealing happens outside the timed section.
+const SEED: u64 = 17;
+
+fn new_round() -> Round {
+    let mut round = Round::new(STANDARD_SCORING);
+    round.start_round_with_seed(0, SEED);
+    round
+}
+
+fn bench_play_round(c: &mut Criterion) {
+    c.bench_function("play_round (play_action)", |b| {
+        b.iter_batched(
+            new_round,
+            |mut round| {
+                while let Some(&action) = round.possible_actions().first() {
+                    round.play_action(action).unwrap();
+                }
+                round.score()
+            },
+            BatchSize::SmallInput,
+        )
+    });
+
+    let mut events: Vec<Vec<GameEvent>> = Vec::new();
+    c.bench_function("play_round (play_action_into)", |b| {
+        b.iter_batched(
+            new_round,
+            |mut round| {
+                while let Some(&action) = round.possible_actions().first() {
+                    round.play_action_into(action, &mut events).unwrap();
+                }
+                round.score()
+            },
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+criterion_group!(benches, bench_play_round);
+criterion_main!(benches);
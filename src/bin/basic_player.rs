@@ -1,107 +1,15 @@
 extern crate card_engine;
-extern crate ndarray;
-extern crate rand;
-extern crate time;
 
-use card_engine::cards::{self, BasicCard, Rank, Suit};
+use card_engine::cards::{self, Rank};
 use card_engine::germanwhist::util::*;
-use card_engine::germanwhist::{PlayerState, PlayerView};
+use card_engine::germanwhist::{BuiltinPlayer, Player, PlayerState};
+use card_engine::learning::harness::TrainingHarness;
 use card_engine::learning::neural_net::{ActivationFunction, LayerDesc, NeuralNet};
-use card_engine::learning::training::{SarsaLambda, SarsaLambdaParameters, SarsaPlayer};
-use card_engine::{Action, ActionError, GameEvent, Round};
-use ndarray::Array;
+use card_engine::learning::training::{SarsaLambda, SarsaLambdaParameters};
+use card_engine::{Action, ActionError, Round};
+use std::env;
+use std::io;
 // use card_engine::{NeuralNet, LayerDesc, OutputFunction};
-use rand::distributions::{IndependentSample, Range};
-use rand::thread_rng;
-
-trait Player {
-    fn on_game_action(&mut self, _ev: &GameEvent) {}
-
-    /// Return a card to play, based on the current view of the world.
-    fn play_card(&self, view: &PlayerView) -> BasicCard;
-}
-
-pub struct RandomPlayer;
-
-impl RandomPlayer {
-    fn new() -> RandomPlayer {
-        RandomPlayer {}
-    }
-}
-
-impl Player for RandomPlayer {
-    fn play_card(&self, view: &PlayerView) -> BasicCard {
-        let mut rng = thread_rng();
-        let cards = view.playable_cards();
-        let card_range = Range::new(0, cards.len());
-        let ri = card_range.ind_sample(&mut rng) as usize;
-        cards[ri]
-    }
-}
-
-pub struct BasicPlayer {
-    // try to win any non-trump with rank above this
-    min_nontrump_rank_to_win: Option<u8>,
-}
-
-impl BasicPlayer {
-    fn new(mntr: Option<Rank>) -> BasicPlayer {
-        BasicPlayer {
-            min_nontrump_rank_to_win: mntr.map(|c| c.ord_ace_high()),
-        }
-    }
-
-    fn try_to_win(&self, card: &BasicCard, trump: Suit) -> bool {
-        card.suit == trump
-            || self
-                .min_nontrump_rank_to_win
-                .map(|c| card.rank.ord_ace_high() >= c)
-                .unwrap_or(false)
-    }
-}
-
-impl Player for BasicPlayer {
-    fn play_card(&self, view: &PlayerView) -> BasicCard {
-        let mut cards = view.playable_cards();
-
-        cards.sort_by_key(|c| (c.rank.ord_ace_high(), view.ord_suit(c.suit)));
-
-        match &view.revealed {
-            // playing for cards
-            Some(ref c) => {
-                // go all-out for trumps, kings, or better
-                if self.try_to_win(c, view.trump) {
-                    // Play the highest non-trump, otherwise play the lowest trump
-                    *cards
-                        .iter()
-                        .rev()
-                        .find(|p| p.suit != view.trump)
-                        .unwrap_or(&cards[0])
-                } else {
-                    // try to ditch
-                    *cards
-                        .iter()
-                        .find(|p| p.suit != view.trump)
-                        .unwrap_or(&cards[0])
-                }
-            }
-
-            // playing for points
-            None => {
-                match &view.leading_card {
-                    Some(ref lc) => {
-                        // play the lowest card to beat it, otherwise ditch
-                        *cards
-                            .iter()
-                            .find(|p| view.wins_against(lc, p))
-                            .unwrap_or(&cards[0])
-                    }
-                    None => cards[0],
-                }
-            }
-        }
-    }
-}
 
 /// Randomly choose actions at each play
 #[allow(unused)]
@@ -112,15 +20,10 @@ fn play_random_game(
 ) -> Result<[usize; 2], ActionError> {
     let mut round = Round::new((0, 1));
 
-    let mut ps = PlayerState::new(0);
-
-    let players: [Box<dyn Player>; 2] =
-        [Box::new(BasicPlayer::new(r)), Box::new(RandomPlayer::new())];
+    let mut players = [BuiltinPlayer::Basic { min_rank: r }, BuiltinPlayer::Random];
 
     let events = round.start_round(start);
-    for ev in &events[0] {
-        ps.on_event(ev);
-    }
+    let mut ps = PlayerState::from_events(0, &events[0]);
     let mut actions = round.possible_actions();
 
     let mut iter = 1;
@@ -161,48 +64,6 @@ fn play_random_game(
     Ok(round.get_state().score)
 }
 
-fn basic_random_game() -> f32 {
-    let mut round = Round::new((0, 1));
-    let mut ps = PlayerState::new(0);
-
-    let players: [Box<dyn Player>; 2] = [
-        Box::new(BasicPlayer::new(None)),
-        Box::new(RandomPlayer::new()),
-    ];
-
-    let events = round.start_round(None);
-    for ev in &events[0] {
-        ps.on_event(ev);
-    }
-    let mut actions = round.possible_actions();
-
-    while !actions.is_empty() {
-        let action = {
-            let player_view = round.active_player_view();
-            let card = players[player_view.player].play_card(&player_view);
-
-            Action {
-                card,
-                player: player_view.player,
-            }
-        };
-
-        let events = round.play_action(action).unwrap();
-        for ev in &events[0] {
-            ps.on_event(ev);
-        }
-
-        actions = round.possible_actions();
-    }
-
-    let s = round.get_state().score;
-    if (s[0] as f32 - s[1] as f32) > 0.0 {
-        1.0
-    } else {
-        0.0
-    }
-}
-
 #[allow(unused)]
 fn test_basic_player(r: Option<Rank>) -> [usize; 2] {
     let mut games_won = [0, 0];
@@ -218,46 +79,8 @@ fn test_basic_player(r: Option<Rank>) -> [usize; 2] {
     games_won
 }
 
-fn test_against<P: Player>(nn: &NeuralNet, oppo: P) -> f32 {
-    let mut eng = Round::new((0, 1));
-
-    let mut ps = SarsaPlayer::new(PlayerState::new(1), nn.num_parameters());
-
-    let evs = eng.start_round(None);
-    for ev in &evs[1] {
-        ps.state.on_event(ev);
-    }
-
-    // start the game
-    let mut actions = eng.possible_actions();
-    let mut sa = Array::zeros(ps.state.state_vector_size() + ps.state.action_vector_size());
-
-    while !actions.is_empty() {
-        let action = if eng.active_player() == 0 {
-            let c = oppo.play_card(&eng.active_player_view());
-            Action { player: 0, card: c }
-        } else {
-            ps.greedy_action(nn, &actions, sa.view_mut())
-        };
-
-        let evs = eng.play_action(action).expect("must play valid action");
-
-        for ev in &evs[1] {
-            ps.state.on_event(ev);
-        }
-
-        actions = eng.possible_actions();
-    }
-
-    if eng.get_state().score[1] as f32 - eng.get_state().score[0] as f32 > 0.0 {
-        1.0
-    } else {
-        0.0
-    }
-}
-
 fn main() {
-    cards::auto_suit_colors();
+    cards::configure_colors_from_env();
     let sa = PlayerState::action_size() + PlayerState::state_size();
     let nn = NeuralNet::new(
         &[
@@ -269,29 +92,16 @@ fn main() {
     .unwrap();
 
     let mut sl = SarsaLambda::new((0, 1), nn, SarsaLambdaParameters::default())
-        .ok()
         .expect("sarsa lambda creation");
 
-    let mut basic_sd: f32 = 0.0;
-    let mut random_sd: f32 = 0.0;
-    let mut br_sd: f32 = 0.0;
-    let decay: f32 = 0.95;
-    for i in 0..1000000 {
-        if i % 10 == 0 {
-            basic_sd = basic_sd * decay
-                + test_against(sl.current_model(), BasicPlayer::new(None)) * (1.0 - decay);
-            random_sd =
-                random_sd * decay + test_against(sl.current_model(), RandomPlayer) * (1.0 - decay);
-            br_sd = br_sd * decay + basic_random_game() * (1.0 - decay);
-        }
-        if i % 100 == 0 {
-            println!("{:10}, {}, {}, {}", i, basic_sd, random_sd, br_sd);
-        }
-        if i % 1000 == 0 {
-            println!("{}", time::now().strftime("%H:%M:%S").ok().unwrap());
-        }
-        sl.train_on_episode(false).expect("training failure");
-    }
+    // metrics go to stdout as CSV; the seed is the first argument
+    let seed = env::args()
+        .nth(1)
+        .map(|s| s.parse().expect("seed must be a non-negative integer"))
+        .unwrap_or(0);
+    TrainingHarness::new(seed, 1000000, 1000)
+        .run(&mut sl, &mut io::stdout())
+        .expect("training failure");
 
     //play_random_game(0, Some(Rank::Ace), true);
 }
@@ -0,0 +1,196 @@
+use card_engine::cards::{self, BasicCard, CardParseError, Rank};
+use card_engine::germanwhist::util::*;
+use card_engine::germanwhist::{BuiltinPlayer, Player, PlayerState, PlayerView};
+use card_engine::learning::neural_net::{ActivationFunction, LayerDesc, NeuralNet};
+use card_engine::{Action, Round};
+use clap::{App, Arg};
+use ndarray::Array;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::process;
+
+const HUMAN: usize = 0;
+const BOT: usize = 1;
+
+/// Load a network with the architecture used by `basic_player`, from
+/// a file of whitespace-separated weights.
+fn load_net(path: &str, hidden: usize) -> Result<NeuralNet, String> {
+    let sa = PlayerState::state_action_size();
+    let mut nn = NeuralNet::new(
+        &[
+            LayerDesc::new(sa, hidden, ActivationFunction::SymmetricSigmoid),
+            LayerDesc::new(hidden, 1, ActivationFunction::Sigmoid),
+        ],
+        0.0,
+    )
+    .expect("valid layers");
+
+    let text = fs::read_to_string(path).map_err(|e| format!("couldn't read {}: {}", path, e))?;
+    let weights = text
+        .split_whitespace()
+        .map(|x| x.parse::<f32>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("bad weight in {}: {}", path, e))?;
+    if weights.len() != nn.num_parameters() {
+        return Err(format!(
+            "{} has {} weights, but a network with {} hidden units needs {}",
+            path,
+            weights.len(),
+            hidden,
+            nn.num_parameters()
+        ));
+    }
+
+    nn.set_weights(Array::from_vec(weights).view());
+    Ok(nn)
+}
+
+/// Parse a card as typed by a person, accepting `10` for ten and the
+/// letters `c`, `d`, `h`, `s` for suits.
+fn parse_card(s: &str) -> Result<BasicCard, CardParseError> {
+    let s = s.trim().to_uppercase();
+    let s = if let Some(rest) = s.strip_prefix("10") {
+        format!("T{}", rest)
+    } else {
+        s
+    };
+    let s = match s.chars().last() {
+        Some('C') => s.replace('C', "♣"),
+        Some('D') => s.replace('D', "♦"),
+        Some('H') => s.replace('H', "♥"),
+        Some('S') => s.replace('S', "♠"),
+        _ => s,
+    };
+    s.parse()
+}
+
+/// Prompt until the human enters a legal card. Returns `None` at the
+/// end of input.
+fn read_human_card(view: &PlayerView) -> Option<BasicCard> {
+    let mut playable = view.playable_cards();
+    playable.sort_by_key(|c| (view.ord_suit(c.suit), c.rank.ord_ace_high()));
+    let options: Vec<_> = playable.iter().map(|c| format!("{}", c)).collect();
+
+    let stdin = io::stdin();
+    loop {
+        print!("Your play ({}): ", options.join(" "));
+        io::stdout().flush().ok();
+
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match parse_card(&line) {
+            Err(_) => println!(
+                "Couldn't read '{}' as a card. Type a rank and suit, like A♠, 10♥ or qd.",
+                line.trim()
+            ),
+            Ok(c) if !view.has_card(c) => println!("You don't hold {}.", c),
+            Ok(c) if !playable.contains(&c) => println!(
+                "You must follow {}.",
+                view.leading_card
+                    .expect("must follow a lead")
+                    .suit
+                    .full_name()
+            ),
+            Ok(c) => return Some(c),
+        }
+    }
+}
+
+fn main() {
+    let m = App::new("play")
+        .about("Play a game of German Whist against a bot")
+        .arg(
+            Arg::with_name("BOT")
+                .long("bot")
+                .takes_value(true)
+                .possible_values(&["random", "basic", "sarsa"])
+                .default_value("basic"),
+        )
+        .arg(
+            Arg::with_name("WEIGHTS")
+                .long("weights")
+                .takes_value(true)
+                .help("whitespace-separated network weights, for the sarsa bot"),
+        )
+        .arg(
+            Arg::with_name("HIDDEN")
+                .long("hidden")
+                .takes_value(true)
+                .default_value("100")
+                .help("number of hidden units in the sarsa network"),
+        )
+        .get_matches();
+
+    cards::configure_colors_from_env();
+
+    let mut bot = match m.value_of("BOT").unwrap() {
+        "random" => BuiltinPlayer::Random,
+        "basic" => BuiltinPlayer::Basic {
+            min_rank: Some(Rank::King),
+        },
+        _ => {
+            let path = m.value_of("WEIGHTS").unwrap_or_else(|| {
+                eprintln!("The sarsa bot needs a weights file (--weights FILE).");
+                process::exit(2);
+            });
+            let hidden = m.value_of("HIDDEN").unwrap().parse().unwrap_or_else(|_| {
+                eprintln!("--hidden must be a positive number.");
+                process::exit(2);
+            });
+            let net = load_net(path, hidden).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                process::exit(1);
+            });
+            BuiltinPlayer::greedy(net)
+        }
+    };
+
+    let mut round = Round::new((0, 1));
+    bot.start_deal(BOT);
+    for ev in &round.start_round(None)[BOT] {
+        bot.on_game_action(ev);
+    }
+    println!("You are player {}.\n", HUMAN + 1);
+
+    while !round.is_game_over() {
+        if round.get_state().played.is_none() {
+            println!("{}", format_round(&round));
+        }
+
+        let card = if round.active_player() == HUMAN {
+            match read_human_card(&round.active_player_view()) {
+                Some(c) => c,
+                None => return,
+            }
+        } else {
+            bot.play_card(&round.active_player_view())
+        };
+
+        let action = Action {
+            player: round.active_player(),
+            card,
+        };
+        println!("{}", format_action(&action));
+
+        let events = round
+            .play_action(action)
+            .expect("card was checked to be playable");
+        for ev in &events[BOT] {
+            bot.on_game_action(ev);
+        }
+    }
+
+    let score = round.get_state().score;
+    println!("\nFinal score: you {}, bot {}.", score[HUMAN], score[BOT]);
+    match round.winner() {
+        Some(HUMAN) => println!("You win!"),
+        Some(_) => println!("The bot wins."),
+        None => println!("It's a draw."),
+    }
+}
@@ -1,13 +1,17 @@
-use atty;
+#[cfg(feature = "terminal-colors")]
 use once_cell::sync::Lazy;
 use rand::{thread_rng, Rng};
 use std::collections::HashMap;
+use std::env;
+use std::error;
 use std::fmt;
 use std::slice::Iter;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "terminal-colors")]
 use termion::color;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(usize)]
 pub enum ColorMode {
     Plain = 0,
@@ -15,7 +19,25 @@ pub enum ColorMode {
     Unique = 2,
 }
 
-static mut SUIT_COLOR_MODE: ColorMode = ColorMode::Unique;
+static SUIT_COLOR_MODE: AtomicUsize = AtomicUsize::new(ColorMode::Unique as usize);
+
+/// Environment variable read by `configure_colors_from_env`.
+pub const COLOR_ENV_VAR: &str = "CARD_ENGINE_COLOR";
+
+/// Set how suits are colored when displayed. Without the
+/// `terminal-colors` feature, suits are always displayed plain.
+pub fn set_suit_color_mode(mode: ColorMode) {
+    SUIT_COLOR_MODE.store(mode as usize, Ordering::Relaxed);
+}
+
+/// Return how suits are currently colored when displayed.
+pub fn suit_color_mode() -> ColorMode {
+    match SUIT_COLOR_MODE.load(Ordering::Relaxed) {
+        0 => ColorMode::Plain,
+        1 => ColorMode::RedBlack,
+        _ => ColorMode::Unique,
+    }
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Color {
@@ -25,6 +47,7 @@ pub enum Color {
 
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Suit {
     Clubs = 0,
     Diamonds = 1,
@@ -50,8 +73,39 @@ impl Suit {
     pub fn ord(&self) -> u8 {
         *self as u8
     }
+
+    /// Return the English name of the suit, e.g. "Spades".
+    pub fn full_name(&self) -> &'static str {
+        match *self {
+            Suit::Clubs => "Clubs",
+            Suit::Diamonds => "Diamonds",
+            Suit::Hearts => "Hearts",
+            Suit::Spades => "Spades",
+        }
+    }
+
+    /// Return the suit's symbol, without any color.
+    pub fn symbol(&self) -> &'static str {
+        match *self {
+            Suit::Clubs => "♣",
+            Suit::Diamonds => "♦",
+            Suit::Hearts => "♥",
+            Suit::Spades => "♠",
+        }
+    }
+
+    /// Like `Suit::from`, but returns an error rather than panicking
+    /// when `x` is 4 or more.
+    pub fn try_from_u8(x: u8) -> Result<Suit, CardParseError> {
+        ALL_SUITS
+            .get(x as usize)
+            .copied()
+            .ok_or(CardParseError::OutOfRange { value: x, limit: 4 })
+    }
 }
 
+/// Inverse of `Suit::ord`. Panics when `x` is 4 or more; see
+/// `Suit::try_from_u8` for untrusted input.
 impl From<u8> for Suit {
     fn from(x: u8) -> Suit {
         ALL_SUITS[x as usize]
@@ -62,6 +116,51 @@ impl From<u8> for Suit {
 pub enum CardParseError {
     BadSuit,
     BadRank,
+
+    /// A value was too large to convert to a suit, rank or card.
+    OutOfRange {
+        value: u8,
+        /// one more than the largest valid value
+        limit: u8,
+    },
+
+    /// A token in a multi-card string failed to parse.
+    BadToken {
+        /// position of the token in the input, starting at 0
+        index: usize,
+        token: String,
+        cause: Box<CardParseError>,
+    },
+}
+
+impl fmt::Display for CardParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CardParseError::BadSuit => write!(f, "bad suit: expected one of ♣♦♥♠"),
+            CardParseError::BadRank => write!(f, "bad rank: expected one of 23456789TJQKA"),
+            CardParseError::OutOfRange { value, limit } => {
+                write!(
+                    f,
+                    "value {} out of range: expected less than {}",
+                    value, limit
+                )
+            }
+            CardParseError::BadToken {
+                index,
+                token,
+                cause,
+            } => write!(f, "bad card '{}' at position {}: {}", token, index, cause),
+        }
+    }
+}
+
+impl error::Error for CardParseError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            CardParseError::BadToken { cause, .. } => Some(cause.as_ref()),
+            _ => None,
+        }
+    }
 }
 
 impl FromStr for Suit {
@@ -79,6 +178,14 @@ impl FromStr for Suit {
     }
 }
 
+#[cfg(not(feature = "terminal-colors"))]
+impl fmt::Display for Suit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.symbol())
+    }
+}
+
+#[cfg(feature = "terminal-colors")]
 impl fmt::Display for Suit {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         use Suit::*;
@@ -88,39 +195,27 @@ impl fmt::Display for Suit {
         static BLUE: Lazy<String> = Lazy::new(|| format!("{}", color::Fg(color::Blue)));
         static RESET: Lazy<String> = Lazy::new(|| format!("{}", color::Fg(color::Reset)));
 
-        let (begin, end) = unsafe {
-            match SUIT_COLOR_MODE {
-                ColorMode::Plain => ("", ""),
-                ColorMode::RedBlack => match self.color() {
-                    Color::Red => (RED.as_str(), RESET.as_str()),
-                    _ => ("", ""),
-                },
-                ColorMode::Unique => match *self {
-                    Clubs => (GREEN.as_str(), RESET.as_str()),
-                    Hearts => (RED.as_str(), RESET.as_str()),
-                    Diamonds => (BLUE.as_str(), RESET.as_str()),
-                    _ => ("", ""),
-                },
-            }
+        let (begin, end) = match suit_color_mode() {
+            ColorMode::Plain => ("", ""),
+            ColorMode::RedBlack => match self.color() {
+                Color::Red => (RED.as_str(), RESET.as_str()),
+                _ => ("", ""),
+            },
+            ColorMode::Unique => match *self {
+                Clubs => (GREEN.as_str(), RESET.as_str()),
+                Hearts => (RED.as_str(), RESET.as_str()),
+                Diamonds => (BLUE.as_str(), RESET.as_str()),
+                _ => ("", ""),
+            },
         };
 
-        write!(
-            f,
-            "{}{}{}",
-            begin,
-            match *self {
-                Clubs => "♣",
-                Diamonds => "♦",
-                Hearts => "♥",
-                Spades => "♠",
-            },
-            end
-        )
+        write!(f, "{}{}{}", begin, self.symbol(), end)
     }
 }
 
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Rank {
     Two = 0,
     Three = 1,
@@ -157,6 +252,18 @@ impl Rank {
     pub fn iterator() -> Iter<'static, Rank> {
         ALL_RANKS.iter()
     }
+
+    /// Like `Rank::from`, but returns an error rather than panicking
+    /// when `x` is 13 or more.
+    pub fn try_from_u8(x: u8) -> Result<Rank, CardParseError> {
+        ALL_RANKS
+            .get(x as usize)
+            .copied()
+            .ok_or(CardParseError::OutOfRange {
+                value: x,
+                limit: 13,
+            })
+    }
     /// Assign numerical values to each rank, with ace as high
     pub fn ord_ace_high(&self) -> u8 {
         *self as u8
@@ -170,8 +277,29 @@ impl Rank {
             x => x as u8,
         }
     }
+    /// Return the English name of the rank, e.g. "Ace" or "Seven".
+    pub fn full_name(&self) -> &'static str {
+        use Rank::*;
+        match *self {
+            Two => "Two",
+            Three => "Three",
+            Four => "Four",
+            Five => "Five",
+            Six => "Six",
+            Seven => "Seven",
+            Eight => "Eight",
+            Nine => "Nine",
+            Ten => "Ten",
+            Jack => "Jack",
+            Queen => "Queen",
+            King => "King",
+            Ace => "Ace",
+        }
+    }
 }
 
+/// Inverse of `Rank::ord_ace_high`. Panics when `x` is 13 or more; see
+/// `Rank::try_from_u8` for untrusted input.
 impl From<u8> for Rank {
     fn from(x: u8) -> Rank {
         ALL_RANKS[x as usize]
@@ -229,20 +357,46 @@ impl fmt::Display for Rank {
 
 /// Normal non-joker playing card
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BasicCard {
     pub rank: Rank,
     pub suit: Suit,
 }
 
 impl BasicCard {
-    /// Returns a Vec of all 52 possible `BasicCard`s, in
-    /// some unspecified order.
-    pub fn all() -> Vec<BasicCard> {
-        use Suit::*;
+    pub const fn new(rank: Rank, suit: Suit) -> BasicCard {
+        BasicCard { rank, suit }
+    }
+
+    /// Like `BasicCard::from`, but returns an error rather than
+    /// panicking when `x` is 52 or more.
+    pub fn try_from_u8(x: u8) -> Result<BasicCard, CardParseError> {
+        if x as usize >= NUM_BASIC_CARDS {
+            return Err(CardParseError::OutOfRange {
+                value: x,
+                limit: NUM_BASIC_CARDS as u8,
+            });
+        }
+        Ok(BasicCard::from(x))
+    }
 
-        let mut cards = Vec::with_capacity(52);
+    /// Format the card as it's parsed, e.g. "T♦", regardless of the
+    /// color mode.
+    pub fn to_plain_string(&self) -> String {
+        format!("{}{}", self.rank, self.suit.symbol())
+    }
+
+    /// Returns a Vec of all 52 possible `BasicCard`s, by ascending
+    /// rank (two to ace), and within a rank in suit order: clubs,
+    /// diamonds, hearts, spades.
+    ///
+    /// This order is guaranteed: the card with rank `r` and suit `s`
+    /// is at index `4 * r.ord_ace_high() + s.ord()`. Note that this
+    /// differs from the `u8` encoding, which is suit-major.
+    pub fn all() -> Vec<BasicCard> {
+        let mut cards = Vec::with_capacity(NUM_BASIC_CARDS);
         for rank in Rank::iterator() {
-            for suit in &[Clubs, Diamonds, Hearts, Spades] {
+            for suit in Suit::iterator() {
                 cards.push(BasicCard {
                     rank: *rank,
                     suit: *suit,
@@ -257,18 +411,54 @@ impl BasicCard {
 impl FromStr for BasicCard {
     type Err = CardParseError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (rank_str, suit_str) = s.split_at(1);
+        let rank_len = s
+            .chars()
+            .next()
+            .map(|c| c.len_utf8())
+            .ok_or(CardParseError::BadRank)?;
+        let (rank_str, suit_str) = s.split_at(rank_len);
         let rank = rank_str.parse()?;
         let suit = suit_str.parse()?;
         Ok(BasicCard { rank, suit })
     }
 }
 
+/// Parse a whitespace-separated list of cards, e.g. `"A♠ K♠ 2♥ T♦"`.
+///
+/// On failure, the error identifies the first token that couldn't be
+/// parsed.
+pub fn parse_hand(s: &str) -> Result<Vec<BasicCard>, CardParseError> {
+    s.split_whitespace()
+        .enumerate()
+        .map(|(index, token)| {
+            token.parse().map_err(|cause| CardParseError::BadToken {
+                index,
+                token: token.to_string(),
+                cause: Box::new(cause),
+            })
+        })
+        .collect()
+}
+
+impl From<BasicCard> for (Rank, Suit) {
+    fn from(c: BasicCard) -> Self {
+        (c.rank, c.suit)
+    }
+}
+
+impl From<(Rank, Suit)> for BasicCard {
+    fn from((rank, suit): (Rank, Suit)) -> Self {
+        BasicCard::new(rank, suit)
+    }
+}
+
+/// Inverse of `u8::from(BasicCard)`. Panics when `s` is 52 or more;
+/// see `BasicCard::try_from_u8` for untrusted input.
 impl From<u8> for BasicCard {
     fn from(s: u8) -> Self {
         BasicCard {
-            rank: (s / 13).into(),
-            suit: (s % 13).into(),
+            rank: (s % 13).into(),
+            suit: (s / 13).into(),
         }
     }
 }
@@ -284,6 +474,35 @@ impl<'a> From<&'a BasicCard> for u8 {
     }
 }
 
+/// Return a bitset of the cards, with bit `u8::from(card)` set for
+/// each card.
+pub fn card_mask<'a, I: IntoIterator<Item = &'a BasicCard>>(cards: I) -> u64 {
+    cards
+        .into_iter()
+        .fold(0u64, |mask, c| mask | (1u64 << u8::from(c)))
+}
+
+/// The bitset of the whole deck, as in `card_mask`.
+pub const FULL_DECK_MASK: u64 = !0 >> (64 - NUM_BASIC_CARDS);
+
+/// Return the bitset of every card in the suit, as in `card_mask`.
+pub fn suit_mask(suit: Suit) -> u64 {
+    0x1fff << (13 * suit as u8)
+}
+
+/// Iterate over the cards in a bitset built by `card_mask`, in index
+/// order.
+pub fn cards_in_mask(mut mask: u64) -> impl Iterator<Item = BasicCard> {
+    std::iter::from_fn(move || {
+        if mask == 0 {
+            return None;
+        }
+        let i = mask.trailing_zeros();
+        mask &= mask - 1;
+        Some(BasicCard::from(i as u8))
+    })
+}
+
 impl fmt::Display for BasicCard {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}{}", self.rank, self.suit)
@@ -330,16 +549,43 @@ impl BasicDeck {
         Self::default()
     }
 
+    /// Create a deck holding exactly `cards`, with the top card last.
+    pub fn from_cards(cards: Vec<BasicCard>) -> BasicDeck {
+        BasicDeck { cards }
+    }
+
     /// Shuffle the remaining cards in the deck
     pub fn shuffle(&mut self) {
-        let mut rng = thread_rng();
+        self.shuffle_with(&mut thread_rng())
+    }
+
+    /// Shuffle the remaining cards in the deck using the given random
+    /// number generator.
+    pub fn shuffle_with<R: Rng>(&mut self, rng: &mut R) {
         rng.shuffle(&mut self.cards)
     }
 
+    /// Shuffle all but the top `keep_top` cards, which stay in place.
+    pub fn shuffle_remaining(&mut self, keep_top: usize) {
+        self.shuffle_remaining_with(keep_top, &mut thread_rng())
+    }
+
+    /// Like `shuffle_remaining`, using the given random number
+    /// generator.
+    pub fn shuffle_remaining_with<R: Rng>(&mut self, keep_top: usize, rng: &mut R) {
+        let n = self.cards.len().saturating_sub(keep_top);
+        rng.shuffle(&mut self.cards[..n])
+    }
+
     pub fn num_cards_left(&self) -> usize {
         self.cards.len()
     }
 
+    /// Return the cards left in the deck, with the top card last.
+    pub fn cards(&self) -> &[BasicCard] {
+        &self.cards
+    }
+
     /// Return the top card from the deck, if there are any cards
     pub fn draw(&mut self) -> Option<BasicCard> {
         self.cards.pop()
@@ -360,6 +606,14 @@ impl BasicDeck {
             None
         }
     }
+
+    /// Return up to the top n cards from the deck, in the same order
+    /// as `draw_n`. If fewer than `n` cards are left, all of them are
+    /// returned and the deck is left empty.
+    pub fn draw_up_to(&mut self, n: usize) -> Vec<BasicCard> {
+        let m = self.cards.len();
+        self.cards.split_off(m.saturating_sub(n))
+    }
 }
 
 // #[derive(Debug, Clone)]
@@ -390,6 +644,49 @@ impl BasicDeck {
 //     pub fn
 // }
 
+/// Serialize a set of cards as a sequence, sorted by card index, for
+/// use with `#[serde(with = "...")]`.
+#[cfg(feature = "serde")]
+pub mod serde_card_set {
+    use super::BasicCard;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::HashSet;
+
+    pub fn serialize<S: Serializer>(set: &HashSet<BasicCard>, s: S) -> Result<S::Ok, S::Error> {
+        let mut cards: Vec<_> = set.iter().collect();
+        cards.sort_by_key(|c| u8::from(**c));
+        cards.serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<HashSet<BasicCard>, D::Error> {
+        Ok(Vec::<BasicCard>::deserialize(d)?.into_iter().collect())
+    }
+}
+
+/// Serialize a map keyed by cards as a sequence of pairs, sorted by
+/// card index, for use with `#[serde(with = "...")]`.
+#[cfg(feature = "serde")]
+pub mod serde_card_map {
+    use super::BasicCard;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::HashMap;
+
+    pub fn serialize<T: Serialize, S: Serializer>(
+        map: &HashMap<BasicCard, T>,
+        s: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut pairs: Vec<_> = map.iter().collect();
+        pairs.sort_by_key(|(c, _)| u8::from(**c));
+        pairs.serialize(s)
+    }
+
+    pub fn deserialize<'de, T: Deserialize<'de>, D: Deserializer<'de>>(
+        d: D,
+    ) -> Result<HashMap<BasicCard, T>, D::Error> {
+        Ok(Vec::<(BasicCard, T)>::deserialize(d)?.into_iter().collect())
+    }
+}
+
 pub fn format_card_map<T: fmt::Display>(
     map: &HashMap<BasicCard, T>,
     fmt: &mut fmt::Formatter,
@@ -434,19 +731,247 @@ pub fn print_card_map<T: fmt::Display>(map: &HashMap<BasicCard, T>) {
     println!("{}*", &col_head);
 }
 
+/// Color suits iff stdout is a terminal. Does nothing without the
+/// `terminal-colors` feature.
 pub fn auto_suit_colors() {
-    unsafe {
-        SUIT_COLOR_MODE = if atty::is(atty::Stream::Stdout) {
-            ColorMode::Unique
-        } else {
-            ColorMode::Plain
-        }
+    #[cfg(feature = "terminal-colors")]
+    set_suit_color_mode(if atty::is(atty::Stream::Stdout) {
+        ColorMode::Unique
+    } else {
+        ColorMode::Plain
+    });
+}
+
+/// Parse a color mode setting. Returns `None` for `auto` or an
+/// unrecognized value.
+fn parse_color_mode(s: &str) -> Option<ColorMode> {
+    match s.trim().to_lowercase().as_str() {
+        "plain" => Some(ColorMode::Plain),
+        "redblack" => Some(ColorMode::RedBlack),
+        "unique" => Some(ColorMode::Unique),
+        _ => None,
+    }
+}
+
+/// Set the suit color mode from `CARD_ENGINE_COLOR` (`plain`,
+/// `redblack`, `unique`, or `auto`). When it's unset, `auto`, or
+/// unrecognized, fall back to `auto_suit_colors`.
+pub fn configure_colors_from_env() {
+    match env::var(COLOR_ENV_VAR)
+        .ok()
+        .and_then(|v| parse_color_mode(&v))
+    {
+        Some(mode) => set_suit_color_mode(mode),
+        None => auto_suit_colors(),
     }
 }
 
 pub mod prelude {
     pub use super::{
-        auto_suit_colors, format_card_map, print_card_map, BasicCard, Rank, Suit, INUM_BASIC_CARDS,
-        NUM_BASIC_CARDS,
+        auto_suit_colors, card_mask, cards_in_mask, configure_colors_from_env, format_card_map,
+        parse_hand, print_card_map, suit_mask, BasicCard, Rank, Suit, FULL_DECK_MASK,
+        INUM_BASIC_CARDS, NUM_BASIC_CARDS,
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_full_names() {
+        let ranks: HashSet<_> = Rank::iterator().map(|r| r.full_name()).collect();
+        assert_eq!(ranks.len(), 13);
+        assert!(ranks.iter().all(|n| !n.is_empty()));
+
+        let suits: HashSet<_> = Suit::iterator().map(|s| s.full_name()).collect();
+        assert_eq!(suits.len(), 4);
+        assert!(suits.iter().all(|n| !n.is_empty()));
+    }
+
+    #[test]
+    fn test_parse_color_mode() {
+        assert_eq!(parse_color_mode("plain"), Some(ColorMode::Plain));
+        assert_eq!(parse_color_mode("RedBlack"), Some(ColorMode::RedBlack));
+        assert_eq!(parse_color_mode(" unique\n"), Some(ColorMode::Unique));
+        assert_eq!(parse_color_mode("auto"), None);
+        assert_eq!(parse_color_mode("rainbow"), None);
+    }
+
+    #[test]
+    fn test_conversions() {
+        for c in BasicCard::all() {
+            let t: (Rank, Suit) = c.into();
+            let i = u8::from(c);
+            assert_eq!(BasicCard::from(t), c);
+            assert_eq!(BasicCard::from(i), c);
+            assert_eq!(BasicCard::new(t.0, t.1), c);
+            assert_eq!(<(Rank, Suit)>::from(BasicCard::from(i)), t);
+            assert_eq!(u8::from(BasicCard::from(t)), i);
+            assert_eq!(BasicCard::try_from_u8(i).unwrap(), c);
+            assert_eq!(Rank::try_from_u8(t.0 as u8).unwrap(), t.0);
+            assert_eq!(Suit::try_from_u8(t.1 as u8).unwrap(), t.1);
+        }
+
+        assert!(Suit::try_from_u8(4).is_err());
+        assert!(Rank::try_from_u8(13).is_err());
+        let err = BasicCard::try_from_u8(52).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "value 52 out of range: expected less than 52"
+        );
+        assert!(BasicCard::try_from_u8(255).is_err());
+    }
+
+    #[test]
+    fn test_all_order() {
+        let expected = "2♣ 2♦ 2♥ 2♠ 3♣ 3♦ 3♥ 3♠ 4♣ 4♦ 4♥ 4♠ 5♣ 5♦ 5♥ 5♠ \
+                        6♣ 6♦ 6♥ 6♠ 7♣ 7♦ 7♥ 7♠ 8♣ 8♦ 8♥ 8♠ 9♣ 9♦ 9♥ 9♠ \
+                        T♣ T♦ T♥ T♠ J♣ J♦ J♥ J♠ Q♣ Q♦ Q♥ Q♠ K♣ K♦ K♥ K♠ \
+                        A♣ A♦ A♥ A♠";
+        let all = BasicCard::all();
+        assert_eq!(all, parse_hand(expected).unwrap());
+        for (i, c) in all.iter().enumerate() {
+            assert_eq!(
+                i,
+                4 * c.rank.ord_ace_high() as usize + c.suit.ord() as usize
+            );
+        }
+    }
+
+    #[test]
+    fn test_from_u8() {
+        // the index is rank-minor: 13 cards of clubs, then diamonds, ...
+        let card = |s: &str| s.parse::<BasicCard>().unwrap();
+        assert_eq!(BasicCard::from(0), card("2♣"));
+        assert_eq!(BasicCard::from(12), card("A♣"));
+        assert_eq!(BasicCard::from(13), card("2♦"));
+        assert_eq!(BasicCard::from(51), card("A♠"));
+        for i in 0..NUM_BASIC_CARDS as u8 {
+            assert_eq!(u8::from(BasicCard::from(i)), i);
+        }
+    }
+
+    #[test]
+    fn test_card_masks() {
+        for c in BasicCard::all() {
+            assert_eq!(BasicCard::from(u8::from(c)), c);
+            assert_ne!(suit_mask(c.suit) & card_mask(&[c]), 0);
+        }
+
+        let hand = parse_hand("2♣ A♣ 7♥ K♠").unwrap();
+        let mask = card_mask(&hand);
+        assert_eq!(cards_in_mask(mask).collect::<Vec<_>>(), hand);
+        assert_eq!(cards_in_mask(0).count(), 0);
+        assert_eq!(
+            cards_in_mask(mask & suit_mask(Suit::Clubs)).collect::<Vec<_>>(),
+            hand[..2].to_vec()
+        );
+        assert_eq!(cards_in_mask(FULL_DECK_MASK).count(), NUM_BASIC_CARDS);
+    }
+
+    #[test]
+    fn test_shuffle_remaining() {
+        let top = parse_hand("A♠ K♥ 2♣").unwrap();
+        let mut cards = parse_hand("3♦ 4♦ 5♦ 6♦ 7♦ 8♦").unwrap();
+        cards.extend(&top);
+
+        let mut deck = BasicDeck::from_cards(cards.clone());
+        assert_eq!(deck.cards(), &cards[..]);
+        deck.shuffle_remaining(3);
+        assert_eq!(deck.draw(), Some(top[2]));
+        assert_eq!(deck.draw_n(2), Some(top[..2].to_vec()));
+
+        let rest: HashSet<_> = deck.cards().iter().collect();
+        assert_eq!(rest, cards[..6].iter().collect());
+
+        // keeping more cards than the deck holds leaves it as is
+        deck.shuffle_remaining(10);
+        assert_eq!(deck.num_cards_left(), 6);
+    }
+
+    #[test]
+    fn test_draw_up_to() {
+        let cards = parse_hand("3♦ 4♦ 5♦ 6♦").unwrap();
+        let mut deck = BasicDeck::from_cards(cards.clone());
+        assert_eq!(deck.draw_up_to(0), vec![]);
+        assert_eq!(deck.draw_up_to(2), cards[2..].to_vec());
+
+        // a short deck gives what it has, unlike draw_n
+        assert_eq!(deck.draw_n(3), None);
+        assert_eq!(deck.num_cards_left(), 2);
+        assert_eq!(deck.draw_up_to(3), cards[..2].to_vec());
+        assert_eq!(deck.num_cards_left(), 0);
+        assert_eq!(deck.draw_up_to(1), vec![]);
+    }
+
+    #[test]
+    fn test_parse_hand() {
+        let hand = parse_hand("A♠ K♠  2♥\tT♦").unwrap();
+        assert_eq!(
+            hand,
+            vec![
+                BasicCard {
+                    rank: Rank::Ace,
+                    suit: Suit::Spades
+                },
+                BasicCard {
+                    rank: Rank::King,
+                    suit: Suit::Spades
+                },
+                BasicCard {
+                    rank: Rank::Two,
+                    suit: Suit::Hearts
+                },
+                BasicCard {
+                    rank: Rank::Ten,
+                    suit: Suit::Diamonds
+                },
+            ]
+        );
+        assert!(parse_hand("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_hand_bad_token() {
+        match parse_hand("A♠ K♠ 2x T♦") {
+            Err(CardParseError::BadToken {
+                index,
+                token,
+                cause,
+            }) => {
+                assert_eq!(index, 2);
+                assert_eq!(token, "2x");
+                assert!(matches!(*cause, CardParseError::BadSuit));
+            }
+            r => panic!("unexpected result: {:?}", r),
+        }
+
+        assert!(matches!(
+            parse_hand("♠A"),
+            Err(CardParseError::BadToken { index: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_error_messages() {
+        use std::error::Error;
+
+        let e = "Kx".parse::<BasicCard>().unwrap_err();
+        assert_eq!(format!("{}", e), "bad suit: expected one of ♣♦♥♠");
+
+        let e = parse_hand("A♠ 1♠").unwrap_err();
+        assert_eq!(
+            format!("{}", e),
+            "bad card '1♠' at position 1: bad rank: expected one of 23456789TJQKA"
+        );
+        assert!(e.source().is_some());
+
+        // usable with `?` into a boxed error
+        fn parse_boxed(s: &str) -> Result<Vec<BasicCard>, Box<dyn Error>> {
+            Ok(parse_hand(s)?)
+        }
+        assert!(parse_boxed("Z♠").is_err());
+    }
+}
@@ -1,8 +1,11 @@
-use crate::cards::{BasicCard, Suit};
+use crate::cards::{BasicCard, BasicDeck, Suit};
 
 use super::phase::{GameOverPhase, GamePhase, PlayingPhase};
 use super::state::{GameState, PlayerView};
-use rand::{thread_rng, Rng};
+use rand::{thread_rng, Rng, SeedableRng, StdRng};
+use std::cmp::Ordering;
+use std::error;
+use std::fmt;
 
 #[derive(Clone, Copy, Debug)]
 pub struct Action {
@@ -10,6 +13,12 @@ pub struct Action {
     pub card: BasicCard,
 }
 
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Player {} plays {}.", self.player + 1, self.card)
+    }
+}
+
 pub type ActionEvent = Action;
 
 #[derive(Clone, Debug)]
@@ -17,15 +26,23 @@ pub struct TrickEvent {
     /// who led
     pub leading_player: usize,
 
-    /// who starts the new round
+    /// who won the trick
+    pub winner: usize,
+
+    /// who starts the new round, which under `LeadRule::Alternate`
+    /// needn't be the winner
     pub active_player: usize,
 
-    /// cards played during the trick, indexed by player
-    pub cards_played: [BasicCard; 2],
+    /// cards played during the trick, indexed by seat
+    pub cards_played: Vec<BasicCard>,
 
     /// new revealed card
     pub revealed: Option<BasicCard>,
 
+    /// tricks left in the round, which is 0 once it's over, including
+    /// when it ends early at the target score
+    pub rounds_left: usize,
+
     /// score after the trick
     pub score: [usize; 2],
 }
@@ -36,16 +53,54 @@ pub struct StartRoundEvent {
     pub revealed: BasicCard,
     pub trump: Suit,
     pub starting_player: usize,
+
+    /// number of tricks in the hand-building phase
+    pub building_tricks: usize,
+
+    /// number of tricks in the scoring phase
+    pub scoring_tricks: usize,
 }
 
 #[derive(Clone, Copy, Debug)]
 pub enum ActionError {
+    /// It is the given player's turn.
     WrongPlayer(usize),
-    MissingCard,
-    NotFollowingSuit,
+
+    /// The player doesn't hold the card.
+    MissingCard(BasicCard),
+
+    /// The player could follow the suit led, but played a different
+    /// suit.
+    NotFollowingSuit {
+        required: Suit,
+        played: BasicCard,
+    },
     GameOver,
+
+    /// No round has been started with `start_round`.
+    NotStarted,
 }
 
+impl fmt::Display for ActionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ActionError::WrongPlayer(p) => write!(f, "it is player {}'s turn", p + 1),
+            ActionError::MissingCard(c) => write!(f, "player doesn't hold {}", c),
+            ActionError::NotFollowingSuit { required, played } => write!(
+                f,
+                "must follow {} ({}), but played {}",
+                required.full_name(),
+                required,
+                played
+            ),
+            ActionError::GameOver => write!(f, "the game is over"),
+            ActionError::NotStarted => write!(f, "the round hasn't started"),
+        }
+    }
+}
+
+impl error::Error for ActionError {}
+
 #[derive(Clone, Copy, Debug)]
 pub struct CardEvent {
     pub player: usize,
@@ -63,54 +118,466 @@ pub enum GameEvent {
     Start(StartRoundEvent),
 }
 
+impl fmt::Display for GameEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            GameEvent::Action(ref action) => write!(f, "{}", action),
+            GameEvent::Trick(ref trick) => {
+                write!(
+                    f,
+                    "Trick won by player {}, score {}-{}",
+                    trick.winner + 1,
+                    trick.score[0],
+                    trick.score[1]
+                )?;
+                match trick.revealed {
+                    Some(c) => write!(f, ", revealed {}", c),
+                    None => Ok(()),
+                }
+            }
+            GameEvent::Card(CardEvent { player, card }) => match card {
+                Some(c) => write!(f, "Player {} receives {}.", player + 1, c),
+                None => write!(f, "Player {} draws a card.", player + 1),
+            },
+            GameEvent::Start(ref start) => {
+                let hand: Vec<_> = start.hand.iter().map(|c| c.to_string()).collect();
+                write!(
+                    f,
+                    "Player {} leads, trump {}, revealed {}, hand: {}",
+                    start.starting_player + 1,
+                    start.trump,
+                    start.revealed,
+                    hand.join(" ")
+                )
+            }
+        }
+    }
+}
+
+/// Receiver for the events seen by a single player.
+pub trait Observer {
+    fn observe(&mut self, player: usize, event: &GameEvent);
+}
+
+impl<O: Observer + ?Sized> Observer for &mut O {
+    fn observe(&mut self, player: usize, event: &GameEvent) {
+        (**self).observe(player, event)
+    }
+}
+
+impl<O: Observer + ?Sized> Observer for Box<O> {
+    fn observe(&mut self, player: usize, event: &GameEvent) {
+        (**self).observe(player, event)
+    }
+}
+
 pub type ScoringRules = (usize, usize);
 
+/// Points per trick in standard German Whist: none for the
+/// hand-building phase, and one for each trick of the scoring phase.
+pub const STANDARD_SCORING: ScoringRules = (0, 1);
+
+/// Number of seats at the table.
+pub const NUM_PLAYERS: usize = 2;
+
+/// Cards left in the deck after dealing, including the first revealed
+/// card.
+const DECK_AFTER_DEAL: usize = 26;
+
+/// Cards dealt to each player.
+const HAND_SIZE: usize = 13;
+
+/// How cards are handed out after each trick of the hand-building
+/// phase.
+///
+/// The winner takes the revealed card, plus `num_revealed - 1` more
+/// cards turned face up from the deck, then draws `winner_draws`
+/// cards face down. The loser draws `loser_draws` cards face down. The
+/// phase ends once the deck can't cover another trick; any cards left
+/// over are out of play.
+///
+/// The scoring phase then lasts `num_scoring_tricks` tricks, by default
+/// as many as the cards each player then holds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PhaseRules {
+    num_revealed: usize,
+    winner_draws: usize,
+    loser_draws: usize,
+    scoring_tricks: usize,
+}
+
+impl Default for PhaseRules {
+    /// Standard German Whist: the winner takes the revealed card and
+    /// the loser draws one.
+    fn default() -> Self {
+        PhaseRules::new(1, 0, 1).expect("standard rules are valid")
+    }
+}
+
+impl PhaseRules {
+    /// Return `None` unless at least one card is revealed, both players
+    /// receive the same number of cards, and the deck covers at least
+    /// one trick.
+    pub fn new(num_revealed: usize, winner_draws: usize, loser_draws: usize) -> Option<PhaseRules> {
+        let mut rules = PhaseRules {
+            num_revealed,
+            winner_draws,
+            loser_draws,
+            scoring_tricks: 0,
+        };
+        if num_revealed == 0
+            || num_revealed + winner_draws != loser_draws
+            || rules.cards_per_trick() > DECK_AFTER_DEAL
+        {
+            return None;
+        }
+        rules.scoring_tricks = rules.hand_size_after_building();
+        Some(rules)
+    }
+
+    pub fn num_revealed(&self) -> usize {
+        self.num_revealed
+    }
+
+    pub fn winner_draws(&self) -> usize {
+        self.winner_draws
+    }
+
+    pub fn loser_draws(&self) -> usize {
+        self.loser_draws
+    }
+
+    /// Number of cards taken from the deck per trick, including the
+    /// revealed card.
+    pub fn cards_per_trick(&self) -> usize {
+        self.num_revealed + self.winner_draws + self.loser_draws
+    }
+
+    /// Number of tricks in the hand-building phase.
+    pub fn num_building_tricks(&self) -> usize {
+        DECK_AFTER_DEAL / self.cards_per_trick()
+    }
+
+    /// Number of cards each player holds once the hand-building phase
+    /// is over. Each building trick costs a card and brings in
+    /// `loser_draws`, whoever wins it.
+    pub fn hand_size_after_building(&self) -> usize {
+        HAND_SIZE + self.num_building_tricks() * (self.loser_draws - 1)
+    }
+
+    /// Number of tricks in the scoring phase.
+    pub fn num_scoring_tricks(&self) -> usize {
+        self.scoring_tricks
+    }
+
+    /// Return the rules with a scoring phase of `n` tricks, or `None`
+    /// unless `n` is between 1 and `hand_size_after_building`. A
+    /// shorter scoring phase leaves cards unplayed.
+    pub fn with_scoring_tricks(mut self, n: usize) -> Option<PhaseRules> {
+        if n == 0 || n > self.hand_size_after_building() {
+            return None;
+        }
+        self.scoring_tricks = n;
+        Some(self)
+    }
+}
+
+/// Rule for choosing the trump suit at the start of each round.
+///
+/// A card is always revealed after dealing, to be played for in the
+/// hand-building phase. Only `TurnUp` derives the trump from it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum TrumpRule {
+    /// The suit of the revealed card is trump.
+    #[default]
+    TurnUp,
+
+    /// The same suit is trump in every round.
+    Fixed(Suit),
+
+    /// Trump cycles through clubs, diamonds, hearts and spades, one
+    /// round at a time. The suit follows from the number of rounds
+    /// the `Round` has started under any rule: clubs for the first,
+    /// diamonds for the second and clubs again for the fifth.
+    Rotating,
+}
+
+/// Rule for choosing who leads each trick of the scoring phase. The
+/// winner of a hand-building trick always leads the next one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum LeadRule {
+    /// The winner of a trick leads the next one.
+    #[default]
+    WinnerLeads,
+
+    /// The player who followed in a trick leads the next one, whoever
+    /// won it.
+    Alternate,
+}
+
+/// The stage a round is in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    /// No round has been started yet.
+    NotStarted,
+
+    /// Tricks are played for the revealed card.
+    HandBuilding,
+
+    /// The deck is exhausted, and tricks are played for points.
+    Scoring,
+
+    /// No more cards can be played.
+    GameOver,
+}
+
+impl Phase {
+    /// Return the phase of a started round from whether a card is
+    /// revealed and the number of tricks left in the round. A round
+    /// that ends early has no tricks left, revealed card or not.
+    pub fn from_parts(revealed: Option<BasicCard>, rounds_left: usize) -> Phase {
+        match revealed {
+            _ if rounds_left == 0 => Phase::GameOver,
+            Some(_) => Phase::HandBuilding,
+            None => Phase::Scoring,
+        }
+    }
+}
+
+/// Callback receiving every event of a round with full information.
+pub type TraceFn = Box<dyn FnMut(&GameEvent)>;
+
+/// Points for a trick in the scoring phase, given the card played by
+/// each seat.
+pub type TrickValueFn = Box<dyn Fn(&[BasicCard; NUM_PLAYERS]) -> usize>;
+
 /// Game engine for a round of German Whist
 pub struct Round {
     state: GameState,
     phase: Box<dyn GamePhase>,
     rules: ScoringRules,
+    trump_rule: TrumpRule,
+    phase_rules: PhaseRules,
+    lead_rule: LeadRule,
+    target_score: Option<usize>,
+    trace: Option<TraceFn>,
+    trick_value: Option<TrickValueFn>,
+
+    /// actions played so far in the current round
+    history: Vec<Action>,
+
+    /// number of rounds started so far
+    num_deals: usize,
+
+    /// seed the current round was dealt from, if known
+    seed: Option<u64>,
 }
 
 impl Round {
     pub fn new(rules: ScoringRules) -> Round {
-        let state = GameState::new(0);
+        // nothing is played until `start_round` deals, so there's no
+        // need to shuffle
+        let state = GameState::from_deck(0, BasicDeck::new());
         let phase: Box<dyn GamePhase> = Box::new(GameOverPhase {});
         Round {
             state,
             phase,
             rules,
+            trump_rule: TrumpRule::default(),
+            phase_rules: PhaseRules::default(),
+            lead_rule: LeadRule::default(),
+            target_score: None,
+            trace: None,
+            trick_value: None,
+            history: Vec::new(),
+            num_deals: 0,
+            seed: None,
         }
     }
 
+    /// Create a round with the standard scoring rules, and start it with
+    /// a random player leading. Returns the round, ready to play, with
+    /// the start events for each seat.
+    pub fn standard() -> (Round, Vec<Vec<GameEvent>>) {
+        let mut round = Round::new(STANDARD_SCORING);
+        let events = round.start_round(None);
+        (round, events)
+    }
+
+    /// Set how the trump suit is chosen for subsequent rounds.
+    pub fn set_trump_rule(&mut self, trump_rule: TrumpRule) {
+        self.trump_rule = trump_rule;
+    }
+
+    pub fn trump_rule(&self) -> TrumpRule {
+        self.trump_rule
+    }
+
+    /// Set how cards are handed out in the hand-building phase of
+    /// subsequent rounds.
+    pub fn set_phase_rules(&mut self, phase_rules: PhaseRules) {
+        self.phase_rules = phase_rules;
+    }
+
+    pub fn phase_rules(&self) -> PhaseRules {
+        self.phase_rules
+    }
+
+    /// Set who leads each trick of the scoring phase in subsequent
+    /// rounds.
+    pub fn set_lead_rule(&mut self, lead_rule: LeadRule) {
+        self.lead_rule = lead_rule;
+    }
+
+    pub fn lead_rule(&self) -> LeadRule {
+        self.lead_rule
+    }
+
+    /// End the round as soon as either player's score reaches
+    /// `target_score`, rather than only once every trick is played.
+    pub fn set_target_score(&mut self, target_score: Option<usize>) {
+        self.target_score = target_score;
+    }
+
+    pub fn target_score(&self) -> Option<usize> {
+        self.target_score
+    }
+
+    /// Set a callback to receive each event produced by `play_action`,
+    /// as seen by an observer who knows every card, for debugging.
+    pub fn set_trace(&mut self, f: Option<TraceFn>) {
+        self.trace = f;
+    }
+
+    /// Set how many points each trick of the scoring phase is worth,
+    /// e.g. to value tricks holding honours more. By default, and when
+    /// set to `None`, every trick is worth the second scoring rule.
+    pub fn set_trick_value(&mut self, f: Option<TrickValueFn>) {
+        self.trick_value = f;
+    }
+
+    /// Return true iff some player has reached the target score.
+    fn target_reached(&self) -> bool {
+        self.target_score
+            .map(|t| self.state.score.iter().any(|&s| s >= t))
+            .unwrap_or(false)
+    }
+
+    /// Deal a new round, with a random seed that `seed` then returns.
     pub fn start_round<T: Into<Option<usize>>>(
         &mut self,
         starting_player: T,
-    ) -> [Vec<GameEvent>; 2] {
-        self.phase = Box::new(PlayingPhase {});
+    ) -> Vec<Vec<GameEvent>> {
+        self.start_round_with_seed(starting_player, thread_rng().gen())
+    }
+
+    /// Like `start_round`, dealing deterministically from `seed`, which
+    /// also picks the starting player if none is given. Starting a
+    /// round with the seed of an earlier one, and the same settings,
+    /// repeats its deal. Under `TrumpRule::Rotating`, the trump also
+    /// depends on how many rounds were started before, so a replay
+    /// only gets the same trump at the same point in the rotation.
+    pub fn start_round_with_seed<T: Into<Option<usize>>>(
+        &mut self,
+        starting_player: T,
+        seed: u64,
+    ) -> Vec<Vec<GameEvent>> {
+        let seed_words = [(seed & 0xffff_ffff) as usize, (seed >> 32) as usize];
+        let events =
+            self.start_round_with_rng(starting_player, &mut StdRng::from_seed(&seed_words[..]));
+        self.seed = Some(seed);
+        events
+    }
+
+    /// Return the seed the current round was dealt from, if it was
+    /// started with `start_round` or `start_round_with_seed`.
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    /// Like `start_round`, using `rng` to shuffle the deck and to pick
+    /// the starting player if none is given.
+    pub fn start_round_with_rng<T: Into<Option<usize>>, R: Rng>(
+        &mut self,
+        starting_player: T,
+        rng: &mut R,
+    ) -> Vec<Vec<GameEvent>> {
         let start = starting_player
             .into()
-            .unwrap_or(if thread_rng().gen::<bool>() { 1 } else { 0 });
-        self.state = GameState::new(start);
+            .unwrap_or(if rng.gen::<bool>() { 1 } else { 0 });
+        let mut state = GameState::with_rng(start, rng);
+        match self.trump_rule {
+            TrumpRule::TurnUp => (),
+            TrumpRule::Fixed(suit) => state.trump = suit,
+            TrumpRule::Rotating => state.trump = Suit::from((self.num_deals % 4) as u8),
+        }
+        self.start_with_state(state)
+    }
+
+    /// Start a round from a freshly dealt state, whose trump is already
+    /// chosen.
+    pub(crate) fn start_with_state(&mut self, state: GameState) -> Vec<Vec<GameEvent>> {
+        self.phase = Box::new(PlayingPhase::new(self.phase_rules, self.lead_rule));
+        self.state = state;
+        let building_tricks = self.phase_rules.num_building_tricks();
+        let scoring_tricks = self.phase_rules.num_scoring_tricks();
+        self.state.rounds_left = building_tricks + scoring_tricks;
+        self.state.phase_tricks_left = building_tricks;
+        self.history.clear();
+        self.num_deals += 1;
+        self.seed = None;
 
         let p0 = StartRoundEvent {
             hand: self.state.hands[0].to_vec(),
             revealed: self.state.revealed.expect("start of round"),
             trump: self.state.trump,
-            starting_player: start,
+            starting_player: self.state.active,
+            building_tricks,
+            scoring_tricks,
         };
         let p1 = StartRoundEvent {
             hand: self.state.hands[1].to_vec(),
             ..p0
         };
 
-        [vec![GameEvent::Start(p0)], vec![GameEvent::Start(p1)]]
+        vec![vec![GameEvent::Start(p0)], vec![GameEvent::Start(p1)]]
+    }
+
+    /// Number of seats, which is also the length of every per-seat
+    /// event list returned by the round.
+    pub fn num_players(&self) -> usize {
+        NUM_PLAYERS
     }
 
     pub fn get_state(&self) -> &GameState {
         &self.state
     }
 
+    pub fn scoring_rules(&self) -> ScoringRules {
+        self.rules
+    }
+
+    /// Return the trump suit of the current round.
+    pub fn trump(&self) -> Suit {
+        self.state.trump
+    }
+
+    /// Return the points each player has scored in the current round.
+    pub fn score(&self) -> [usize; 2] {
+        self.state.score
+    }
+
+    /// Return the face-up card to be won in the current trick, if any.
+    pub fn revealed(&self) -> Option<BasicCard> {
+        self.state.revealed
+    }
+
+    /// Return the actions played so far in the current round, in
+    /// order.
+    pub fn history(&self) -> &[Action] {
+        &self.history
+    }
+
     pub fn active_player(&self) -> usize {
         self.state.active
     }
@@ -119,38 +586,859 @@ impl Round {
         self.state.player_view(self.state.active)
     }
 
+    /// Return true iff no more cards can be played, which includes a
+    /// round that was never started.
     pub fn is_game_over(&self) -> bool {
         self.phase.is_game_over()
     }
 
+    /// Return true iff `start_round` has been called.
+    pub fn is_started(&self) -> bool {
+        self.num_deals > 0
+    }
+
+    /// Return the stage the round is in.
+    pub fn phase_kind(&self) -> Phase {
+        if !self.is_started() {
+            Phase::NotStarted
+        } else {
+            Phase::from_parts(self.state.revealed, self.state.rounds_left)
+        }
+    }
+
+    /// Return the player with the higher score, once the game is
+    /// over.
+    ///
+    /// Returns `None` while the game is in progress, or if the game
+    /// ended in a draw.
     pub fn winner(&self) -> Option<usize> {
-        if self.is_game_over() {
+        if self.is_started() && self.is_game_over() {
             let s = &self.state.score;
-            Some(if s[0] < s[1] { 1 } else { 0 })
+            match s[0].cmp(&s[1]) {
+                Ordering::Less => Some(1),
+                Ordering::Greater => Some(0),
+                Ordering::Equal => None,
+            }
+        } else {
+            None
+        }
+    }
+
+    /// Return true iff the game is over and both players have the same
+    /// score.
+    pub fn is_draw(&self) -> bool {
+        self.is_started() && self.is_game_over() && self.state.score[0] == self.state.score[1]
+    }
+
+    /// Return the most points either player can still score under the
+    /// round's scoring rules, or `None` with a custom
+    /// `set_trick_value`, which doesn't bound them.
+    pub fn points_remaining(&self) -> Option<usize> {
+        match self.trick_value {
+            Some(_) => None,
+            None => Some(self.state.points_remaining(self.rules)),
+        }
+    }
+
+    /// Return the player who is certain to win, because their lead
+    /// exceeds the points still available, even before the round is
+    /// over. Once it's over, this is the `winner`.
+    ///
+    /// Returns `None` while either player can still win or draw. With
+    /// a custom `set_trick_value`, the points still available aren't
+    /// bounded, so only a finished game is decided.
+    pub fn is_decided(&self) -> Option<usize> {
+        if !self.is_started() || self.is_game_over() {
+            return self.winner();
+        }
+        let available = self.points_remaining()?;
+
+        let s = &self.state.score;
+        if s[0] > s[1] + available {
+            Some(0)
+        } else if s[1] > s[0] + available {
+            Some(1)
         } else {
             None
         }
     }
 
+    /// Return the actions the active player may take. Empty before the
+    /// round starts as well as once it's over; `phase_kind` tells the
+    /// two apart.
     pub fn possible_actions(&self) -> Vec<Action> {
         self.phase.as_ref().possible_actions(&self.state)
     }
 
+    /// Return the cards the active player may play as a bitset, in the
+    /// encoding of `card_mask`. Agrees with `possible_actions`, without
+    /// allocating.
+    pub fn legal_card_mask(&self) -> u64 {
+        self.phase.as_ref().legal_card_mask(&self.state)
+    }
+
     pub fn get_phase(&self) -> &dyn GamePhase {
         use std::borrow::Borrow;
         self.phase.as_ref().borrow()
     }
 
-    pub fn play_action(&mut self, action: Action) -> Result<[Vec<GameEvent>; 2], ActionError> {
-        let events = self
-            .phase
+    /// Play the action, returning the resulting events for each seat.
+    pub fn play_action(&mut self, action: Action) -> Result<Vec<Vec<GameEvent>>, ActionError> {
+        let mut events = Vec::new();
+        self.play_action_into(action, &mut events)?;
+        Ok(events)
+    }
+
+    /// Like `play_action`, writing the events for each seat into
+    /// `events` instead. The lists already in `events` are cleared and
+    /// reused, so a caller playing many actions can keep one buffer
+    /// rather than allocating new lists for every card. On error,
+    /// `events` is left empty.
+    pub fn play_action_into(
+        &mut self,
+        action: Action,
+        events: &mut Vec<Vec<GameEvent>>,
+    ) -> Result<(), ActionError> {
+        events.resize_with(NUM_PLAYERS, Vec::new);
+        for evs in events.iter_mut() {
+            evs.clear();
+        }
+        if !self.is_started() {
+            return Err(ActionError::NotStarted);
+        }
+        let constant_value = self.rules.1;
+        let constant = move |_: &[BasicCard; NUM_PLAYERS]| constant_value;
+        let trick_value: &dyn Fn(&[BasicCard; NUM_PLAYERS]) -> usize = match self.trick_value {
+            Some(ref f) => f.as_ref(),
+            None => &constant,
+        };
+        self.phase
             .as_mut()
-            .on_action(&mut self.state, &self.rules, action)?;
+            .on_action(&mut self.state, &self.rules, trick_value, action, events)?;
+        debug_assert_eq!(self.state.validate(), Ok(()));
+        self.history.push(action);
 
         if self.state.rounds_left == 0 {
             self.phase = self.phase.as_mut().transition(&mut self.state);
+        } else if self.target_reached() {
+            // the rest of the round is forfeited
+            self.state.rounds_left = 0;
+            self.state.phase_tricks_left = 0;
+            for event in events.iter_mut().flatten() {
+                if let GameEvent::Trick(ref mut trick) = *event {
+                    trick.rounds_left = 0;
+                }
+            }
+            self.phase = Box::new(GameOverPhase {});
         }
 
-        Ok(events)
+        if let Some(ref mut trace) = self.trace {
+            // Both seats see the same sequence of events, except that a
+            // card drawn face down is only known to the player drawing it.
+            for (e0, e1) in events[0].iter().zip(&events[1]) {
+                match *e0 {
+                    GameEvent::Card(CardEvent { card: None, .. }) => trace(e1),
+                    _ => trace(e0),
+                }
+            }
+        }
+
+        debug_assert!(
+            self.is_game_over() || !self.state.hands[self.state.active].is_empty(),
+            "player {} has no cards mid-trick",
+            self.state.active + 1
+        );
+
+        Ok(())
+    }
+
+    /// If the active player has exactly one legal card, play it,
+    /// returning the action and the resulting events for each seat as
+    /// in `play_action`. Otherwise, including before the round starts
+    /// and once it's over, do nothing and return `None`.
+    ///
+    /// The events must be delivered to the players as usual.
+    pub fn auto_play_forced(&mut self) -> Option<(Action, Vec<Vec<GameEvent>>)> {
+        let mask = self.legal_card_mask();
+        if mask.count_ones() != 1 {
+            return None;
+        }
+        let action = Action {
+            player: self.active_player(),
+            card: BasicCard::from(mask.trailing_zeros() as u8),
+        };
+        let events = self
+            .play_action(action)
+            .expect("the only legal card can be played");
+        Some((action, events))
+    }
+
+    /// Play the action, delivering each player's events to the
+    /// corresponding observer. There must be one observer per seat.
+    pub fn play_action_notifying<O: Observer>(
+        &mut self,
+        action: Action,
+        observers: &mut [O],
+    ) -> Result<(), ActionError> {
+        assert_eq!(observers.len(), self.num_players());
+        let events = self.play_action(action)?;
+        for (player, (evs, observer)) in events.iter().zip(observers.iter_mut()).enumerate() {
+            for ev in evs {
+                observer.observe(player, ev);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cards::card_mask;
+    use crate::germanwhist::PlayerState;
+
+    struct Recorder(Vec<usize>);
+
+    impl Observer for Recorder {
+        fn observe(&mut self, player: usize, _event: &GameEvent) {
+            self.0.push(player);
+        }
+    }
+
+    #[test]
+    fn test_action_errors() {
+        let mut round = Round::new((0, 1));
+        round.start_round(0);
+
+        let other = round.get_state().hands[1][0];
+        match round.play_action(Action {
+            player: 0,
+            card: other,
+        }) {
+            Err(ActionError::MissingCard(c)) => assert_eq!(c, other),
+            r => panic!("unexpected result: {:?}", r.map(|_| ())),
+        }
+
+        // lead a card that player 1 can follow, then try to play off-suit
+        let follower = round.get_state().hands[1].clone();
+        let lead = round.get_state().hands[0].iter().cloned().find(|c| {
+            follower.iter().any(|f| f.suit == c.suit) && follower.iter().any(|f| f.suit != c.suit)
+        });
+        if let Some(lead) = lead {
+            round
+                .play_action(Action {
+                    player: 0,
+                    card: lead,
+                })
+                .unwrap();
+            let off_suit = *follower.iter().find(|f| f.suit != lead.suit).unwrap();
+            match round.play_action(Action {
+                player: 1,
+                card: off_suit,
+            }) {
+                Err(ActionError::NotFollowingSuit { required, played }) => {
+                    assert_eq!(required, lead.suit);
+                    assert_eq!(played, off_suit);
+                }
+                r => panic!("unexpected result: {:?}", r.map(|_| ())),
+            }
+
+            // the failed play leaves the trick intact
+            assert_eq!(round.get_state().played, Some(lead));
+            assert_eq!(
+                round.possible_actions().len(),
+                round.active_player_view().suit_count(lead.suit)
+            );
+        }
+    }
+
+    #[test]
+    fn test_draw() {
+        let mut round = Round::new((0, 1));
+        round.start_round(0);
+        assert_eq!(round.winner(), None);
+        assert!(!round.is_draw());
+
+        round.phase = Box::new(GameOverPhase {});
+        round.state.score = [6, 6];
+        assert!(round.is_game_over());
+        assert!(round.is_draw());
+        assert_eq!(round.winner(), None);
+
+        round.state.score = [6, 7];
+        assert!(!round.is_draw());
+        assert_eq!(round.winner(), Some(1));
+    }
+
+    #[test]
+    fn test_trump_rule() {
+        let mut round = Round::new((0, 1));
+        round.start_round(0);
+        assert_eq!(
+            round.get_state().trump,
+            round.get_state().revealed.unwrap().suit
+        );
+
+        round.set_trump_rule(TrumpRule::Fixed(Suit::Hearts));
+        for _ in 0..4 {
+            round.start_round(0);
+            assert_eq!(round.get_state().trump, Suit::Hearts);
+        }
+
+        fn trumps(round: &mut Round, n: usize) -> Vec<Suit> {
+            (0..n)
+                .map(|_| {
+                    round.start_round(0);
+                    round.get_state().trump
+                })
+                .collect()
+        }
+        let (c, d, h, s) = (Suit::Clubs, Suit::Diamonds, Suit::Hearts, Suit::Spades);
+
+        // five rounds were started, so the rotation is at its second suit
+        round.set_trump_rule(TrumpRule::Rotating);
+        assert_eq!(trumps(&mut round, 4), vec![d, h, s, c]);
+
+        let mut fresh = Round::new((0, 1));
+        fresh.set_trump_rule(TrumpRule::Rotating);
+        assert_eq!(trumps(&mut fresh, 5), vec![c, d, h, s, c]);
+    }
+
+    #[test]
+    fn test_play_action_notifying() {
+        let mut round = Round::new((0, 1));
+        round.start_round(0);
+        let mut observers = [Recorder(Vec::new()), Recorder(Vec::new())];
+
+        while let Some(action) = round.possible_actions().first().cloned() {
+            round.play_action_notifying(action, &mut observers).unwrap();
+        }
+
+        assert!(round.is_game_over());
+        for (i, obs) in observers.iter().enumerate() {
+            // every trick produces two actions and a trick event
+            assert!(obs.0.len() >= 26 * 3);
+            assert!(obs.0.iter().all(|p| *p == i));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "player 1 has no cards mid-trick")]
+    fn test_empty_hand_mid_game() {
+        let mut round = Round::new((0, 1));
+        round.start_round(0);
+        round.state.hands[0].clear();
+        round.possible_actions();
+    }
+
+    #[test]
+    fn test_phase_rules() {
+        assert_eq!(PhaseRules::new(1, 0, 1), Some(PhaseRules::default()));
+        assert_eq!(PhaseRules::new(2, 0, 1), None);
+        assert_eq!(PhaseRules::new(0, 1, 1), None);
+        assert_eq!(PhaseRules::new(7, 7, 14), None);
+
+        let rules = PhaseRules::new(2, 1, 3).unwrap();
+        assert_eq!(rules.num_building_tricks(), 4);
+
+        let mut round = Round::new((0, 1));
+        round.set_phase_rules(rules);
+        let mut ps = PlayerState::from_events(0, &round.start_round(0)[0]);
+
+        let mut tricks = 0;
+        while !round.is_game_over() {
+            let building = round.get_state().revealed.is_some();
+            let action = round.possible_actions()[0];
+            let events = round.play_action(action).unwrap();
+
+            if round.get_state().played.is_none() {
+                tricks += 1;
+                let hands = &round.get_state().hands;
+                assert_eq!(hands[0].len(), hands[1].len());
+
+                // each player receives three cards after a building trick
+                if building {
+                    for evs in &events {
+                        let received = evs
+                            .iter()
+                            .filter(|e| matches!(e, GameEvent::Card(_)))
+                            .count();
+                        assert_eq!(received, 6);
+                    }
+                }
+            }
+
+            for ev in &events[0] {
+                ps.on_event(ev);
+            }
+            assert!(ps.oppo.matches_hand(round.get_state().hands[1].iter()));
+        }
+        assert_eq!(rules.hand_size_after_building(), 21);
+        assert_eq!(tricks, 4 + 21);
+        assert!(round.get_state().hands.iter().all(|h| h.is_empty()));
+    }
+
+    #[test]
+    fn test_scoring_tricks() {
+        assert_eq!(PhaseRules::default().num_scoring_tricks(), 13);
+        assert_eq!(PhaseRules::default().with_scoring_tricks(0), None);
+        assert_eq!(PhaseRules::default().with_scoring_tricks(14), None);
+
+        for (rules, building) in [
+            (PhaseRules::default().with_scoring_tricks(5).unwrap(), 13),
+            (
+                PhaseRules::new(2, 1, 3)
+                    .unwrap()
+                    .with_scoring_tricks(5)
+                    .unwrap(),
+                4,
+            ),
+        ] {
+            let mut round = Round::new((0, 1));
+            round.set_phase_rules(rules);
+            let mut ps = PlayerState::from_events(0, &round.start_round(0)[0]);
+            assert_eq!(round.get_state().phase_tricks_left, building);
+            assert_eq!(round.points_remaining(), Some(5));
+
+            let mut counts = [0, 0];
+            while !round.is_game_over() {
+                let phase = round.phase_kind();
+                let action = round.possible_actions()[0];
+                for ev in &round.play_action(action).unwrap()[0] {
+                    ps.on_event(ev);
+                }
+                if round.get_state().played.is_none() {
+                    counts[(phase == Phase::Scoring) as usize] += 1;
+                }
+
+                let gs = round.get_state();
+                let view = round.active_player_view();
+                assert_eq!(view.phase(), round.phase_kind());
+                assert_eq!(ps.phase(), round.phase_kind());
+                assert_eq!(ps.phase_tricks_left(), gs.phase_tricks_left);
+                assert_eq!(ps.rounds_left(), gs.rounds_left);
+                assert_eq!(
+                    gs.points_remaining(round.scoring_rules()) + gs.score[0] + gs.score[1],
+                    5
+                );
+            }
+            assert_eq!(counts, [building, 5]);
+            assert!(!round.get_state().hands[0].is_empty());
+        }
+
+        // the full scoring phase plays out the grown hands
+        let rules = PhaseRules::new(2, 1, 3).unwrap();
+        assert_eq!(rules.num_scoring_tricks(), 21);
+        assert_eq!(rules.with_scoring_tricks(21), Some(rules));
+        assert_eq!(rules.with_scoring_tricks(22), None);
+    }
+
+    #[test]
+    fn test_lead_rule() {
+        for &lead_rule in &[LeadRule::WinnerLeads, LeadRule::Alternate] {
+            let mut round = Round::new((0, 1));
+            round.set_lead_rule(lead_rule);
+            assert_eq!(round.lead_rule(), lead_rule);
+            round.start_round(0);
+
+            while !round.is_game_over() {
+                let building = round.revealed().is_some();
+                let lead = round.active_player();
+                round.play_action(round.possible_actions()[0]).unwrap();
+                let follow = *round.possible_actions().last().unwrap();
+                let lead_card = round.get_state().played.unwrap();
+                let lead_wins = round.get_state().score_hand(&lead_card, &follow.card);
+                let winner = if lead_wins == Some(true) {
+                    lead
+                } else {
+                    1 - lead
+                };
+                let events = round.play_action(follow).unwrap();
+                let trick = events[0].iter().find_map(|e| match e {
+                    GameEvent::Trick(t) => Some(t),
+                    _ => None,
+                });
+                assert_eq!(trick.unwrap().winner, winner);
+
+                if !round.is_game_over() {
+                    let expected = match lead_rule {
+                        LeadRule::Alternate if !building => 1 - lead,
+                        _ => winner,
+                    };
+                    assert_eq!(round.active_player(), expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_event_display() {
+        let card = |s: &str| s.parse::<BasicCard>().unwrap();
+        let action = Action {
+            player: 0,
+            card: card("A♠"),
+        };
+        // suits may be colored, so compare against the cards' own formatting
+        assert_eq!(
+            action.to_string(),
+            format!("Player 1 plays {}.", card("A♠"))
+        );
+        assert_eq!(GameEvent::Action(action).to_string(), action.to_string());
+
+        let trick = TrickEvent {
+            leading_player: 0,
+            winner: 1,
+            active_player: 0,
+            cards_played: vec![card("2♠"), card("A♠")],
+            revealed: Some(card("9♦")),
+            rounds_left: 20,
+            score: [3, 2],
+        };
+        assert_eq!(
+            GameEvent::Trick(trick.clone()).to_string(),
+            format!("Trick won by player 2, score 3-2, revealed {}", card("9♦"))
+        );
+        let last = TrickEvent {
+            revealed: None,
+            ..trick
+        };
+        assert_eq!(
+            GameEvent::Trick(last).to_string(),
+            "Trick won by player 2, score 3-2"
+        );
+
+        let drawn = CardEvent {
+            player: 1,
+            card: None,
+        };
+        assert_eq!(GameEvent::Card(drawn).to_string(), "Player 2 draws a card.");
+        let received = CardEvent {
+            player: 0,
+            card: Some(card("K♥")),
+        };
+        assert_eq!(
+            GameEvent::Card(received).to_string(),
+            format!("Player 1 receives {}.", card("K♥"))
+        );
+    }
+
+    #[test]
+    fn test_trace() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut round = Round::new((0, 1));
+        let l = log.clone();
+        round.set_trace(Some(Box::new(move |ev: &GameEvent| {
+            l.borrow_mut().push(ev.clone())
+        })));
+
+        round.start_round(None);
+        let mut num_events = 0;
+        while !round.is_game_over() {
+            let action = round.possible_actions()[0];
+            num_events += round.play_action(action).unwrap()[0].len();
+        }
+
+        let log = log.borrow();
+        assert_eq!(log.len(), num_events);
+        let count = |f: fn(&GameEvent) -> bool| log.iter().filter(|e| f(e)).count();
+        assert_eq!(count(|e| matches!(e, GameEvent::Action(_))), 52);
+        assert_eq!(count(|e| matches!(e, GameEvent::Trick(_))), 26);
+        assert_eq!(
+            count(|e| matches!(e, GameEvent::Card(CardEvent { card: Some(_), .. }))),
+            26
+        );
+    }
+
+    #[test]
+    fn test_legal_card_mask() {
+        let mut round = Round::new((0, 1));
+        assert_eq!(round.legal_card_mask(), 0);
+
+        round.start_round(None);
+        while !round.is_game_over() {
+            let mask = round.legal_card_mask();
+            let view = round.active_player_view();
+            assert_eq!(mask, card_mask(&view.playable_cards()));
+
+            let actions = round.possible_actions();
+            assert_eq!(actions.len(), mask.count_ones() as usize);
+            round.play_action(actions[actions.len() - 1]).unwrap();
+        }
+        assert_eq!(round.legal_card_mask(), 0);
+    }
+
+    #[test]
+    fn test_auto_play_forced() {
+        let mut round = Round::new((0, 1));
+        assert!(round.auto_play_forced().is_none());
+
+        round.start_round(None);
+        let mut forced = 0;
+        while !round.is_game_over() {
+            let actions = round.possible_actions();
+            let before = round.history().len();
+            match round.auto_play_forced() {
+                Some((action, events)) => {
+                    assert_eq!(actions.len(), 1);
+                    assert_eq!(actions[0].card, action.card);
+                    assert_eq!(round.history().len(), before + 1);
+                    assert_eq!(events.len(), round.num_players());
+                    assert!(matches!(events[0][0], GameEvent::Action(a) if a.card == action.card));
+                    forced += 1;
+                }
+                None => {
+                    assert!(actions.len() > 1);
+                    assert_eq!(round.history().len(), before);
+                    round.play_action(actions[0]).unwrap();
+                }
+            }
+        }
+        // at least the last card of the deal is forced
+        assert!(forced > 0);
+        assert!(round.auto_play_forced().is_none());
+    }
+
+    #[test]
+    fn test_seed() {
+        let mut round = Round::new((0, 1));
+        assert_eq!(round.seed(), None);
+        round.start_round(None);
+        let seed = round.seed().expect("start_round records its seed");
+        let hands = round.get_state().hands.clone();
+        let revealed = round.revealed();
+        let active = round.active_player();
+
+        let mut replay = Round::new((0, 1));
+        replay.start_round_with_seed(None, seed);
+        assert_eq!(replay.seed(), Some(seed));
+        assert_eq!(replay.get_state().hands, hands);
+        assert_eq!(replay.revealed(), revealed);
+        assert_eq!(replay.active_player(), active);
+
+        replay.start_round_with_seed(None, seed ^ 1);
+        assert_ne!(replay.get_state().hands, hands);
+
+        replay.start_round_with_rng(None, &mut thread_rng());
+        assert_eq!(replay.seed(), None);
+    }
+
+    #[test]
+    fn test_phase_kind() {
+        let mut round = Round::new((0, 1));
+        assert_eq!(round.phase_kind(), Phase::NotStarted);
+
+        round.start_round(None);
+        let mut phases = vec![round.phase_kind()];
+        while !round.is_game_over() {
+            let action = round.possible_actions()[0];
+            round.play_action(action).unwrap();
+            if round.get_state().played.is_none() {
+                phases.push(round.phase_kind());
+            }
+        }
+
+        let count = |p| phases.iter().filter(|&&q| q == p).count();
+        assert_eq!(count(Phase::HandBuilding), 13);
+        assert_eq!(count(Phase::Scoring), 13);
+        assert_eq!(phases.last(), Some(&Phase::GameOver));
+    }
+
+    #[test]
+    fn test_is_decided() {
+        let mut decided_early = 0;
+        for seed in 0..10 {
+            // points for building-phase tricks too, so both phases count
+            let mut round = Round::new((1, 1));
+            assert_eq!(round.is_decided(), None);
+            round.start_round_with_seed(0, seed);
+
+            let mut decided = None;
+            while !round.is_game_over() {
+                match (decided, round.is_decided()) {
+                    (None, Some(w)) => {
+                        decided = Some(w);
+                        decided_early += 1;
+                    }
+                    (Some(w), d) => assert_eq!(d, Some(w)),
+                    (None, None) => (),
+                }
+                let action = round.possible_actions()[0];
+                round.play_action(action).unwrap();
+            }
+            assert_eq!(round.is_decided(), round.winner());
+            if decided.is_some() {
+                assert_eq!(decided, round.winner());
+            }
+        }
+        assert!(decided_early > 0);
+
+        // a custom trick value leaves the points available unbounded
+        let mut round = Round::new((0, 1));
+        round.set_trick_value(Some(Box::new(|_| 1)));
+        round.start_round(0);
+        while round.get_state().rounds_left > 1 {
+            assert_eq!(round.is_decided(), None);
+            let action = round.possible_actions()[0];
+            round.play_action(action).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_not_started() {
+        let mut round = Round::new((0, 1));
+        assert!(!round.is_started());
+        assert!(round.possible_actions().is_empty());
+        assert_eq!(round.winner(), None);
+        assert!(!round.is_draw());
+
+        let action = Action {
+            player: 0,
+            card: round.get_state().hands[0][0],
+        };
+        assert!(matches!(
+            round.play_action(action),
+            Err(ActionError::NotStarted)
+        ));
+
+        round.start_round(0);
+        assert!(round.is_started());
+        let action = round.possible_actions()[0];
+        assert!(round.play_action(action).is_ok());
+    }
+
+    #[test]
+    fn test_standard() {
+        let (mut round, events) = Round::standard();
+        assert_eq!(events.len(), round.num_players());
+        assert!(matches!(events[0][0], GameEvent::Start(_)));
+        assert!(!round.is_game_over());
+
+        while let Some(action) = round.possible_actions().first().cloned() {
+            round.play_action(action).unwrap();
+        }
+        let score = round.get_state().score;
+        assert_eq!(score[0] + score[1], 13);
+    }
+
+    #[test]
+    fn test_accessors() {
+        let mut round = Round::new((0, 1));
+        round.set_trump_rule(TrumpRule::Fixed(Suit::Hearts));
+        round.start_round(0);
+        assert_eq!(round.trump(), Suit::Hearts);
+        assert_eq!(round.score(), [0, 0]);
+        assert!(round.revealed().is_some());
+        assert_eq!(round.revealed(), round.get_state().revealed);
+
+        while let Some(action) = round.possible_actions().first().cloned() {
+            round.play_action(action).unwrap();
+        }
+        assert_eq!(round.score(), round.get_state().score);
+        assert_eq!(round.revealed(), None);
+    }
+
+    #[test]
+    fn test_target_score() {
+        let mut round = Round::new((0, 1));
+        round.set_target_score(Some(3));
+        round.start_round(0);
+
+        while !round.is_game_over() {
+            let action = round.possible_actions()[0];
+            round.play_action(action).unwrap();
+        }
+
+        let gs = round.get_state();
+        let winner = round.winner().expect("the first to the target wins");
+        assert_eq!(gs.score[winner], 3);
+        assert!(gs.score[1 - winner] < 3);
+        assert!(round.possible_actions().is_empty());
+    }
+
+    #[test]
+    fn test_target_score_phase() {
+        // the target is reached in the scoring phase, or while a card
+        // is still revealed when building tricks score too
+        for &rules in &[(0, 1), (1, 1)] {
+            let mut round = Round::new(rules);
+            round.set_target_score(Some(3));
+            let mut ps = PlayerState::from_events(0, &round.start_round(0)[0]);
+
+            let mut tricks = 0;
+            while !round.is_game_over() {
+                let action = round.possible_actions()[0];
+                for ev in &round.play_action(action).unwrap()[0] {
+                    ps.on_event(ev);
+                }
+                if round.get_state().played.is_none() {
+                    tricks += 1;
+                }
+            }
+            assert!(tricks < 26);
+            assert_eq!(round.get_state().revealed.is_some(), rules.0 > 0);
+
+            assert_eq!(round.phase_kind(), Phase::GameOver);
+            assert_eq!(round.active_player_view().phase(), Phase::GameOver);
+            assert_eq!(ps.phase(), Phase::GameOver);
+            assert_eq!(ps.rounds_left(), 0);
+            assert_eq!(round.points_remaining(), Some(0));
+        }
+    }
+
+    #[test]
+    fn test_trick_value() {
+        use crate::cards::Rank;
+
+        let mut round = Round::new((0, 1));
+        round.set_trick_value(Some(Box::new(|cards| {
+            1 + cards
+                .iter()
+                .filter(|c| c.rank == Rank::Ace || c.rank == Rank::King)
+                .count()
+        })));
+        round.start_round(0);
+
+        let mut expected = 0;
+        while !round.is_game_over() {
+            let scoring = round.phase_kind() == Phase::Scoring;
+            let action = round.possible_actions()[0];
+            for ev in round.play_action(action).unwrap().swap_remove(0) {
+                match ev {
+                    GameEvent::Trick(ref t) if scoring => {
+                        let honours = t
+                            .cards_played
+                            .iter()
+                            .filter(|c| c.rank == Rank::Ace || c.rank == Rank::King)
+                            .count();
+                        expected += 1 + honours;
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        let gs = round.get_state();
+        assert_eq!(gs.score[0] + gs.score[1], expected);
+        assert!(expected >= 13);
+    }
+
+    #[test]
+    fn test_seat_events() {
+        let mut round = Round::new((0, 1));
+        assert_eq!(round.num_players(), 2);
+        assert_eq!(round.start_round(0).len(), round.num_players());
+
+        while let Some(action) = round.possible_actions().first().cloned() {
+            let events = round.play_action(action).unwrap();
+            assert_eq!(events.len(), round.num_players());
+            for ev in events.iter().flatten() {
+                if let GameEvent::Trick(ref t) = *ev {
+                    assert_eq!(t.cards_played.len(), round.num_players());
+                }
+            }
+        }
     }
 }
@@ -1,9 +1,21 @@
 pub mod engine;
 pub mod phase;
 pub mod player;
+pub mod player_policy;
+pub mod simulate;
 pub mod state;
+pub mod transcript;
 pub mod util;
 
-pub use self::engine::{Action, ActionError, GameEvent, Round, ScoringRules};
+pub use self::engine::{
+    Action, ActionError, GameEvent, LeadRule, Observer, Phase, PhaseRules, Round, ScoringRules,
+    TraceFn, TrickValueFn, TrumpRule, NUM_PLAYERS, STANDARD_SCORING,
+};
 pub use self::player::PlayerState;
-pub use self::state::PlayerView;
+pub use self::player_policy::{
+    play_match, play_match_with_rng, run_tournament, run_tournament_with_rng, BuiltinPlayer,
+    Player, RandomPlayer, ScriptedPlayer, TournamentStats,
+};
+pub use self::simulate::{simulate_scoring_phase, two_card_endgame};
+pub use self::state::{PlayerView, PositionKey};
+pub use self::transcript::TranscriptError;
@@ -1,20 +1,39 @@
-use super::engine::{Action, ActionError, ScoringRules};
+use super::engine::{Action, ActionError, LeadRule, PhaseRules, ScoringRules, NUM_PLAYERS};
 use super::engine::{CardEvent, GameEvent, TrickEvent};
 use super::state::GameState;
+use crate::cards::{card_mask, cards_in_mask, suit_mask, BasicCard};
 
 pub trait GamePhase {
-    /// Return the number of possible actions
-    fn possible_actions(&self, gs: &GameState) -> Vec<Action>;
+    /// Return the cards the active player may play, as a bitset in
+    /// the encoding of `card_mask`.
+    fn legal_card_mask(&self, gs: &GameState) -> u64;
+
+    /// Return the possible actions, one per card in
+    /// `legal_card_mask`.
+    fn possible_actions(&self, gs: &GameState) -> Vec<Action> {
+        cards_in_mask(self.legal_card_mask(gs))
+            .map(|card| Action {
+                player: gs.active,
+                card,
+            })
+            .collect()
+    }
 
     fn is_game_over(&self) -> bool;
 
     /// perform action on a submitted, return rounds left in this state
+    ///
+    /// `trick_value` gives the points for a trick in the scoring phase.
+    /// The resulting events for each seat are appended to `events`,
+    /// which holds one list per player; nothing is appended on error.
     fn on_action(
         &mut self,
         gs: &mut GameState,
         rules: &ScoringRules,
+        trick_value: &dyn Fn(&[BasicCard; NUM_PLAYERS]) -> usize,
         action: Action,
-    ) -> Result<[Vec<GameEvent>; 2], ActionError>;
+        events: &mut [Vec<GameEvent>],
+    ) -> Result<(), ActionError>;
 
     fn format(&self, gs: &GameState) -> String;
 
@@ -22,65 +41,80 @@ pub trait GamePhase {
     fn transition(&mut self, gs: &mut GameState) -> Box<dyn GamePhase>;
 }
 
-pub struct PlayingPhase;
+pub struct PlayingPhase {
+    rules: PhaseRules,
+    lead_rule: LeadRule,
+}
 
-impl GamePhase for PlayingPhase {
-    /// Available actions
-    fn possible_actions(&self, gs: &GameState) -> Vec<Action> {
-        let view = gs.player_view(gs.active);
+impl PlayingPhase {
+    pub fn new(rules: PhaseRules, lead_rule: LeadRule) -> PlayingPhase {
+        PlayingPhase { rules, lead_rule }
+    }
+}
 
-        view.playable_cards()
-            .into_iter()
-            .map(|c| Action {
-                player: gs.active,
-                card: c,
-            })
-            .collect()
+impl GamePhase for PlayingPhase {
+    /// Available cards
+    fn legal_card_mask(&self, gs: &GameState) -> u64 {
+        assert!(
+            !gs.hands[gs.active].is_empty(),
+            "player {} has no cards mid-trick",
+            gs.active + 1
+        );
+        let hand = card_mask(&gs.hands[gs.active]);
+
+        // Second player must follow suit, if possible.
+        match gs.played {
+            Some(c) if hand & suit_mask(c.suit) != 0 => hand & suit_mask(c.suit),
+            _ => hand,
+        }
     }
 
     fn on_action(
         &mut self,
         gs: &mut GameState,
         rules: &ScoringRules,
+        trick_value: &dyn Fn(&[BasicCard; NUM_PLAYERS]) -> usize,
         action: Action,
-    ) -> Result<[Vec<GameEvent>; 2], ActionError> {
+        events: &mut [Vec<GameEvent>],
+    ) -> Result<(), ActionError> {
         if action.player != gs.active {
             return Err(ActionError::WrongPlayer(gs.active));
         }
 
         // make sure the player owns the card
         if !gs.hands[action.player].contains(&action.card) {
-            return Err(ActionError::MissingCard);
+            return Err(ActionError::MissingCard(action.card));
         }
 
-        let mut events = [Vec::new(), Vec::new()];
-
         if gs.played.is_none() {
-            // this is the first card played
+            // this is the first card played, seen by both players
             gs.player_view_mut(action.player)
                 .remove_card(&action.card)?;
 
-            events[0].push(GameEvent::Action(action));
-            events[1].push(GameEvent::Action(action));
-
             gs.played = Some(action.card);
 
             gs.active = 1 - gs.active;
+
+            for evs in events.iter_mut() {
+                evs.push(GameEvent::Action(action));
+            }
+            Ok(())
         } else {
-            let leading_card = gs
-                .played
-                .take()
-                .expect("on_action: already checked !is_none");
+            let leading_card = gs.played.expect("on_action: already checked !is_none");
             {
                 let mut player = gs.player_view_mut(action.player);
 
                 // If the player has the suit, the card must match
                 if player.has_suit(&leading_card.suit) && action.card.suit != leading_card.suit {
-                    return Err(ActionError::NotFollowingSuit);
+                    return Err(ActionError::NotFollowingSuit {
+                        required: leading_card.suit,
+                        played: action.card,
+                    });
                 }
 
                 player.remove_card(&action.card)?;
             }
+            gs.played = None;
 
             let action_ev = GameEvent::Action(action);
             events[0].push(action_ev.clone());
@@ -99,7 +133,7 @@ impl GamePhase for PlayingPhase {
             };
             let loser = 1 - winner;
 
-            let mut cards_played = [leading_card, action.card];
+            let mut cards_played = vec![leading_card, action.card];
             let mut cards_received = [None, None];
 
             if lead == 1 {
@@ -107,41 +141,53 @@ impl GamePhase for PlayingPhase {
             }
 
             // hand-building phase
-            if gs.revealed.is_some() {
+            let building = gs.revealed.is_some();
+            if building {
                 // Give players their new cards
                 {
                     let r = gs.revealed.take().expect("must be a revealed card");
-                    gs.player_view_mut(winner).add_card(r);
-
                     cards_received[winner] = Some(r);
-                    let rec_ev = GameEvent::Card(CardEvent {
-                        player: winner,
-                        card: Some(r),
-                    });
 
-                    // both players know what the winning player got.
-                    events[winner].push(rec_ev.clone());
-                    events[loser].push(rec_ev);
+                    for i in 0..self.rules.num_revealed() {
+                        // any further revealed cards are turned up from the deck
+                        let c = if i == 0 {
+                            r
+                        } else {
+                            gs.draw().expect("rules must leave enough cards")
+                        };
+                        gs.player_view_mut(winner).add_card(c);
+                        let rec_ev = GameEvent::Card(CardEvent {
+                            player: winner,
+                            card: Some(c),
+                        });
+
+                        // both players know what the winning player got.
+                        events[winner].push(rec_ev.clone());
+                        events[loser].push(rec_ev);
+                    }
                 }
 
-                {
-                    let draw = gs.draw().expect("must have a card left after trick");
-                    gs.player_view_mut(loser).add_card(draw);
-                    cards_received[loser] = Some(draw);
-
-                    // the loser knows what they got, but the winner doesn't
-                    events[loser].push(GameEvent::Card(CardEvent {
-                        player: loser,
-                        card: Some(draw),
-                    }));
-                    events[winner].push(GameEvent::Card(CardEvent {
-                        player: loser,
-                        card: None,
-                    }));
+                let draws = [
+                    (loser, self.rules.loser_draws()),
+                    (winner, self.rules.winner_draws()),
+                ];
+                for &(player, n) in &draws {
+                    for _ in 0..n {
+                        let draw = gs.draw().expect("must have a card left after trick");
+                        gs.player_view_mut(player).add_card(draw);
+                        cards_received[player] = Some(draw);
+
+                        // the player knows what they got, but the other doesn't
+                        events[player].push(GameEvent::Card(CardEvent {
+                            player,
+                            card: Some(draw),
+                        }));
+                        events[1 - player].push(GameEvent::Card(CardEvent { player, card: None }));
+                    }
                 }
 
-                // Draw a new card, if any
-                if gs.deck.num_cards_left() > 0 {
+                // Reveal a new card, if the deck covers another trick
+                if gs.deck.num_cards_left() >= self.rules.cards_per_trick() {
                     gs.revealed = gs.draw();
                 }
 
@@ -149,25 +195,36 @@ impl GamePhase for PlayingPhase {
             }
             // scoring phase
             else {
-                gs.increment_score(winner, rules.1);
+                let value = trick_value(&[cards_played[0], cards_played[1]]);
+                gs.increment_score(winner, value);
             }
 
-            gs.active = winner;
+            gs.active = match self.lead_rule {
+                LeadRule::Alternate if !building => follow,
+                _ => winner,
+            };
             gs.rounds_left -= 1;
+            gs.phase_tricks_left -= 1;
+            if building && gs.phase_tricks_left == 0 {
+                // the rest of the round is the scoring phase
+                gs.phase_tricks_left = gs.rounds_left;
+            }
 
             let trick = GameEvent::Trick(TrickEvent {
                 leading_player: lead,
+                winner,
                 active_player: gs.active,
                 cards_played,
                 revealed: gs.revealed,
+                rounds_left: gs.rounds_left,
                 score: gs.score,
             });
 
             events[loser].push(trick.clone());
             events[winner].push(trick);
-        }
 
-        Ok(events)
+            Ok(())
+        }
     }
 
     fn is_game_over(&self) -> bool {
@@ -201,16 +258,18 @@ impl GamePhase for PlayingPhase {
 pub struct GameOverPhase;
 
 impl GamePhase for GameOverPhase {
-    fn possible_actions(&self, _: &GameState) -> Vec<Action> {
-        Vec::new()
+    fn legal_card_mask(&self, _: &GameState) -> u64 {
+        0
     }
 
     fn on_action(
         &mut self,
         _: &mut GameState,
         _: &ScoringRules,
+        _: &dyn Fn(&[BasicCard; NUM_PLAYERS]) -> usize,
         _: Action,
-    ) -> Result<[Vec<GameEvent>; 2], ActionError> {
+        _: &mut [Vec<GameEvent>],
+    ) -> Result<(), ActionError> {
         Err(ActionError::GameOver)
     }
 
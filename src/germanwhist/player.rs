@@ -1,63 +1,143 @@
 use crate::cards::prelude::*;
-use crate::germanwhist::engine::{Action, GameEvent};
+use crate::germanwhist::engine::{Action, GameEvent, Observer, Phase};
+use crate::germanwhist::util::explain_trick;
 /// Player state representation
-use crate::hand_belief::HandBelief;
+use crate::hand_belief::{HandBelief, OpponentModel};
 use itertools::Itertools;
 use ndarray::prelude::*;
 use std::collections::HashSet;
 use std::fmt;
 
+/// The parts of a `PlayerState` that an action can change, as written
+/// to the state vector.
+struct StateParts<'a> {
+    hand: &'a HashSet<BasicCard>,
+    played_cards: &'a HashSet<BasicCard>,
+    revealed: Option<BasicCard>,
+    leading_card: Option<BasicCard>,
+    active: usize,
+    score: [usize; 2],
+    suit_order: [Suit; 4],
+}
+
 /// Representation of current state for learning value function.
-pub struct PlayerState {
+///
+/// The opponent's hand is tracked by `B`, a `HandBelief` by default.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PlayerState<B = HandBelief> {
     // Round-static, implied state
     player_id: usize,
     trump: Suit,
 
     // Explicit state components
+    #[cfg_attr(feature = "serde", serde(with = "crate::cards::serde_card_set"))]
     hand: HashSet<BasicCard>,
-    pub oppo: HandBelief,
+    pub oppo: B,
     active: usize,
     revealed: Option<BasicCard>,
     leading_card: Option<BasicCard>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::cards::serde_card_set"))]
     played_cards: HashSet<BasicCard>,
     score: [usize; 2],
 
+    /// tricks in the round, as announced at its start
+    round_length: usize,
+
+    /// tricks left in the round, across both phases
+    rounds_left: usize,
+
+    /// tricks left in the current phase
+    phase_tricks_left: usize,
+
     // state vector
     suit_order: [Suit; 4],
+
+    /// set when the cards `suit_order` depends on changed since it was
+    /// computed
+    #[cfg_attr(feature = "serde", serde(skip))]
+    suit_order_dirty: bool,
 }
 
 impl PlayerState {
+    /// Size of the vector produced by `state_vector`.
+    pub const STATE_SIZE: usize = NUM_BASIC_CARDS * 5 + 3;
+
+    /// Size of the vector produced by `state_vector_v2`.
+    pub const STATE_SIZE_V2: usize = Self::STATE_SIZE + 1;
+
+    /// Size of the action part of a state-action vector.
+    pub const ACTION_SIZE: usize = NUM_BASIC_CARDS;
+
+    /// Size of a state-action vector.
+    pub const STATE_ACTION_SIZE: usize = Self::STATE_SIZE + Self::ACTION_SIZE;
+
     pub fn new(id: usize) -> PlayerState {
+        PlayerState::with_belief(id, HandBelief::new())
+    }
+
+    /// Reconstruct a player's state by replaying their event log, which
+    /// must begin with the `Start` event of the round.
+    pub fn from_events(player: usize, events: &[GameEvent]) -> PlayerState {
+        assert!(
+            matches!(events.first(), Some(GameEvent::Start(_))),
+            "event log must begin with a Start event"
+        );
+
+        let mut ps = PlayerState::new(player);
+        for ev in events {
+            ps.on_event(ev);
+        }
+        ps
+    }
+
+    pub fn state_size() -> usize {
+        Self::STATE_SIZE
+    }
+
+    pub fn state_size_v2() -> usize {
+        Self::STATE_SIZE_V2
+    }
+
+    pub fn action_size() -> usize {
+        Self::ACTION_SIZE
+    }
+    pub fn state_action_size() -> usize {
+        Self::STATE_ACTION_SIZE
+    }
+}
+
+impl<B: OpponentModel> PlayerState<B> {
+    /// Create the state with the given model of the opponent's hand.
+    pub fn with_belief(id: usize, oppo: B) -> PlayerState<B> {
         PlayerState {
             player_id: id,
             hand: HashSet::new(),
-            oppo: HandBelief::new(),
+            oppo,
             active: 0,
             trump: Suit::Hearts,
             revealed: None,
             leading_card: None,
             played_cards: HashSet::new(),
             score: [0, 0],
+            round_length: 0,
+            rounds_left: 0,
+            phase_tricks_left: 0,
             suit_order: [Suit::Clubs, Suit::Diamonds, Suit::Hearts, Suit::Spades],
+            suit_order_dirty: false,
         }
     }
 
     pub fn state_vector_size(&self) -> usize {
-        NUM_BASIC_CARDS * 5 + 3
-    }
-    pub fn state_size() -> usize {
-        NUM_BASIC_CARDS * 5 + 3
+        PlayerState::STATE_SIZE
     }
 
-    pub fn action_vector_size(&self) -> usize {
-        NUM_BASIC_CARDS
+    /// Size of the vector produced by `state_vector_v2`.
+    pub fn state_vector_v2_size(&self) -> usize {
+        PlayerState::STATE_SIZE_V2
     }
 
-    pub fn action_size() -> usize {
-        NUM_BASIC_CARDS
-    }
-    pub fn state_action_size() -> usize {
-        Self::state_size() + Self::action_size()
+    pub fn action_vector_size(&self) -> usize {
+        PlayerState::ACTION_SIZE
     }
 
     /// Update the state vector in response to a game action.
@@ -72,6 +152,10 @@ impl PlayerState {
                 self.played_cards = HashSet::new();
                 self.leading_card = None;
                 self.score = [0, 0];
+                self.round_length = start.building_tricks + start.scoring_tricks;
+                self.rounds_left = self.round_length;
+                self.phase_tricks_left = start.building_tricks;
+                self.suit_order_dirty = true;
 
                 self.oppo.clear();
                 self.oppo.random_cards_drawn(13);
@@ -83,6 +167,7 @@ impl PlayerState {
             Action(ref action) => {
                 if action.player == self.player_id {
                     self.hand.remove(&action.card);
+                    self.suit_order_dirty = true;
                 } else {
                     self.oppo.card_played(&action.card);
 
@@ -90,6 +175,17 @@ impl PlayerState {
                     if let Some(p) = self.leading_card {
                         if p.suit != action.card.suit {
                             self.oppo.empty_suit(p.suit);
+                        } else if self.oppo.observes_play_choices() {
+                            // the opponent followed, and may have passed up
+                            // a card that would have won the trick
+                            let winners: Vec<_> = BasicCard::all()
+                                .into_iter()
+                                .filter(|c| {
+                                    c.suit == p.suit
+                                        && c.rank.ord_ace_high() > p.rank.ord_ace_high()
+                                })
+                                .collect();
+                            self.oppo.observe_play_choice(&action.card, &winners);
                         }
                     }
                 }
@@ -97,6 +193,7 @@ impl PlayerState {
                 // mark the leading card, if it exists
                 if self.leading_card.is_none() {
                     self.leading_card = Some(action.card);
+                    self.suit_order_dirty = true;
                 }
                 self.active = 1 - action.player;
             }
@@ -107,6 +204,7 @@ impl PlayerState {
                         .expect("Player always knows what card that player gets.");
                     self.hand.insert(c);
                     self.oppo.card_seen(&c);
+                    self.suit_order_dirty = true;
                 } else {
                     match card.card {
                         Some(ref c) => self.oppo.card_drawn(c),
@@ -116,6 +214,16 @@ impl PlayerState {
             }
 
             Trick(ref trick) => {
+                self.rounds_left = trick.rounds_left;
+                self.phase_tricks_left = self.phase_tricks_left.saturating_sub(1);
+                if self.rounds_left == 0 {
+                    // the round may end early, at the target score
+                    self.phase_tricks_left = 0;
+                } else if self.revealed.is_some() && self.phase_tricks_left == 0 {
+                    // the rest of the round is the scoring phase
+                    self.phase_tricks_left = self.rounds_left;
+                }
+
                 self.leading_card = None;
                 self.revealed = trick.revealed;
                 self.active = trick.active_player;
@@ -126,37 +234,155 @@ impl PlayerState {
                 for card in &trick.cards_played {
                     self.played_cards.insert(*card);
                 }
+                self.suit_order_dirty = true;
             }
         };
 
-        self.update_suit_order();
+        if self.suit_order_dirty {
+            self.update_suit_order();
+        }
+    }
+
+    /// Return the order of the suits in the state vector.
+    pub fn suit_order(&self) -> [Suit; 4] {
+        self.suit_order
+    }
+
+    /// Return the phase the round is in, or `Phase::GameOver` once no
+    /// tricks are left.
+    pub fn phase(&self) -> Phase {
+        Phase::from_parts(self.revealed, self.rounds_left)
+    }
+
+    /// Return the number of tricks left in the round, across both
+    /// phases.
+    pub fn rounds_left(&self) -> usize {
+        self.rounds_left
+    }
+
+    /// Return the number of tricks left in the current phase.
+    pub fn phase_tricks_left(&self) -> usize {
+        self.phase_tricks_left
     }
 
     /// update the state vector
+    ///
+    /// `state_view` must have length `state_vector_size()`, which is
+    /// only checked in debug builds.
     pub fn state_vector(&self, state_view: ArrayViewMut<f32, Ix1>) {
-        assert_eq!(state_view.dim(), self.state_vector_size());
+        debug_assert_eq!(state_view.dim(), self.state_vector_size());
+        let parts = StateParts {
+            hand: &self.hand,
+            played_cards: &self.played_cards,
+            revealed: self.revealed,
+            leading_card: self.leading_card,
+            active: self.active,
+            score: self.score,
+            suit_order: self.suit_order,
+        };
+        self.write_state_vector(&parts, state_view);
+    }
+
+    /// Write the state vector as it would be after this player takes
+    /// the action, leaving the state itself untouched, e.g. to compare
+    /// actions by the value of the states they lead to.
+    ///
+    /// A leading card is simply played. A following card completes
+    /// the trick, which is resolved as far as the player can tell: the
+    /// winner leads next and, in the scoring phase, takes a point as
+    /// in the default scoring rules. In the hand-building phase, a
+    /// winning player takes the revealed card, but the cards drawn
+    /// afterwards are unknown, so the revealed card is cleared and a
+    /// losing player's hand is one card short.
+    ///
+    /// The player state doesn't know the round's rules, so both steps
+    /// assume the defaults. The result is wrong under
+    /// `LeadRule::Alternate`, where the follower leads next whoever
+    /// won, and when tricks are scored other than by
+    /// `STANDARD_SCORING`, e.g. with points for hand-building tricks
+    /// or a custom `Round::set_trick_value`.
+    ///
+    /// `out` must have length `state_vector_size()`, which is only
+    /// checked in debug builds.
+    pub fn state_vector_after(&self, action: &Action, out: ArrayViewMut<f32, Ix1>) {
+        debug_assert_eq!(out.dim(), self.state_vector_size());
+        assert_eq!(
+            action.player, self.player_id,
+            "action must be this player's"
+        );
+
+        let mut hand = self.hand.clone();
+        hand.remove(&action.card);
+        let mut played_cards = self.played_cards.clone();
+        let mut revealed = self.revealed;
+        let mut leading_card = Some(action.card);
+        let mut active = 1 - self.player_id;
+        let mut score = self.score;
+
+        if let Some(lead) = self.leading_card {
+            let winner = if explain_trick(&lead, &action.card, self.trump).leader_wins {
+                1 - self.player_id
+            } else {
+                self.player_id
+            };
+            match self.revealed {
+                Some(c) if winner == self.player_id => {
+                    hand.insert(c);
+                }
+                Some(_) => (),
+                None => score[winner] += 1,
+            }
+            played_cards.insert(lead);
+            played_cards.insert(action.card);
 
+            revealed = None;
+            leading_card = None;
+            active = winner;
+        }
+
+        let parts = StateParts {
+            suit_order: Self::canonical_suit_order(
+                self.trump,
+                &hand,
+                &played_cards,
+                revealed,
+                leading_card,
+            ),
+            hand: &hand,
+            played_cards: &played_cards,
+            revealed,
+            leading_card,
+            active,
+            score,
+        };
+        self.write_state_vector(&parts, out);
+    }
+
+    /// Write the state vector for the parts given, with the opponent
+    /// belief and seat of this player.
+    fn write_state_vector(&self, parts: &StateParts, state_view: ArrayViewMut<f32, Ix1>) {
+        let suit_order = &parts.suit_order;
         let mut state_view = {
             let (hand_view, state_view) = state_view.split_at(Axis(0), NUM_BASIC_CARDS);
-            Self::cards_to_vector(hand_view, &self.hand, &self.suit_order);
+            Self::cards_to_vector(hand_view, parts.hand, suit_order);
 
             let (oppo_view, state_view) = state_view.split_at(Axis(0), NUM_BASIC_CARDS);
-            Self::oppo_to_vector(oppo_view, &self.oppo, &self.suit_order);
+            Self::oppo_to_vector(oppo_view, &self.oppo, suit_order);
 
             let (played_view, state_view) = state_view.split_at(Axis(0), NUM_BASIC_CARDS);
-            Self::cards_to_vector(played_view, &self.played_cards, &self.suit_order);
+            Self::cards_to_vector(played_view, parts.played_cards, suit_order);
 
             let (mut revealed_view, state_view) = state_view.split_at(Axis(0), NUM_BASIC_CARDS);
-            Self::card_to_vector(&mut revealed_view, &self.revealed, &self.suit_order);
+            Self::card_to_vector(&mut revealed_view, &parts.revealed, suit_order);
 
             let (mut leading_view, state_view) = state_view.split_at(Axis(0), NUM_BASIC_CARDS);
-            Self::card_to_vector(&mut leading_view, &self.leading_card, &self.suit_order);
+            Self::card_to_vector(&mut leading_view, &parts.leading_card, suit_order);
 
             state_view
         };
 
         // whose turn it is
-        state_view[0] = if self.active == self.player_id {
+        state_view[0] = if parts.active == self.player_id {
             1.0
         } else {
             -1.0
@@ -167,12 +393,56 @@ impl PlayerState {
         }
 
         // current score (scaled to -1.0 -> 1.0)
-        state_view[1] = score_to_state(self.score[0]);
-        state_view[2] = score_to_state(self.score[1]);
+        state_view[1] = score_to_state(parts.score[0]);
+        state_view[2] = score_to_state(parts.score[1]);
+    }
+
+    /// Return the state vector as a new array, e.g. to evaluate many
+    /// actions from the same state.
+    pub fn state_vector_cached(&self) -> Array1<f32> {
+        let mut v = Array::zeros(self.state_vector_size());
+        self.state_vector(v.view_mut());
+        v
+    }
+
+    /// Alternate state encoding, with scores normalized by the points
+    /// still in play.
+    ///
+    /// The layout matches `state_vector`, except that each score is
+    /// encoded as a fraction of the points achievable by the end of
+    /// the round (current points plus one per remaining scoring-phase
+    /// trick, as in the default scoring rules), and a final element
+    /// holds the fraction of the round's tricks still to be played,
+    /// both scaled to -1.0 -> 1.0.
+    pub fn state_vector_v2(&self, state_view: ArrayViewMut<f32, Ix1>) {
+        debug_assert_eq!(state_view.dim(), self.state_vector_v2_size());
+
+        let (mut prefix, mut rest) = state_view.split_at(Axis(0), self.state_vector_size());
+        self.state_vector(prefix.view_mut());
+
+        let n = prefix.dim();
+        let scoring_left = match self.phase() {
+            Phase::HandBuilding => self.rounds_left - self.phase_tricks_left,
+            _ => self.rounds_left,
+        };
+        let achievable = (self.score[0] + self.score[1] + scoring_left) as f32;
+        for i in 0..2 {
+            prefix[n - 2 + i] = if achievable > 0.0 {
+                self.score[i] as f32 * 2.0 / achievable - 1.0
+            } else {
+                0.0
+            };
+        }
+
+        rest[0] = if self.round_length > 0 {
+            self.rounds_left as f32 * 2.0 / self.round_length as f32 - 1.0
+        } else {
+            -1.0
+        };
     }
 
     pub fn action_vector(&self, action: &Action, action_view: &mut ArrayViewMut<f32, Ix1>) {
-        assert_eq!(action_view.dim(), self.action_vector_size());
+        debug_assert_eq!(action_view.dim(), self.action_vector_size());
         assert!(self.hand.contains(&action.card));
 
         Self::card_to_vector(action_view, &Some(action.card), &self.suit_order)
@@ -185,6 +455,7 @@ impl PlayerState {
         eval_state: bool,
         action: Option<&Action>,
     ) {
+        assert_eq!(state_action_view.dim(), PlayerState::STATE_ACTION_SIZE);
         let (state_view, mut action_view) =
             state_action_view.split_at(Axis(0), self.state_vector_size());
         if !eval_state {
@@ -195,6 +466,73 @@ impl PlayerState {
         }
     }
 
+    /// Return the cards this player knows are out of the opponent's
+    /// reach: the player's own hand, every card played, including the
+    /// lead of the current trick, and the revealed card, if any.
+    pub fn public_known_cards(&self) -> HashSet<BasicCard> {
+        self.hand
+            .iter()
+            .chain(&self.played_cards)
+            .chain(&self.leading_card)
+            .chain(&self.revealed)
+            .cloned()
+            .collect()
+    }
+
+    /// Return the cards not in `public_known_cards`, in the order of
+    /// `BasicCard::all`: the opponent's hand and the undrawn deck. Any
+    /// card the opponent may hold, as judged by `oppo`, is among them.
+    pub fn unknown_cards(&self) -> Vec<BasicCard> {
+        let known = self.public_known_cards();
+        BasicCard::all()
+            .into_iter()
+            .filter(|c| !known.contains(c))
+            .collect()
+    }
+
+    /// Estimate how many of the remaining tricks this player will win.
+    ///
+    /// This is a deterministic, closed-form heuristic rather than a
+    /// sampled estimate. Each card in hand is given a chance of winning
+    /// a trick: the probability that the opponent holds none of the
+    /// higher cards of its suit, and, for non-trumps, that the opponent
+    /// can't ruff it (is void in the suit while holding a trump). The
+    /// per-card average is then scaled to the tricks left in the round,
+    /// on the assumption that cards drawn later are of similar strength.
+    pub fn expected_tricks(&self) -> f32 {
+        if self.hand.is_empty() {
+            return 0.0;
+        }
+
+        let p_none = |pred: &dyn Fn(&BasicCard) -> bool| -> f32 {
+            BasicCard::all()
+                .iter()
+                .filter(|c| pred(c))
+                // beliefs are approximate, and can stray slightly above 1
+                .map(|c| (1.0 - self.oppo.p(c)).max(0.0))
+                .product()
+        };
+        let p_no_trump = p_none(&|c| c.suit == self.trump);
+
+        let total: f32 = self
+            .hand
+            .iter()
+            .map(|card| {
+                let p_unbeaten = p_none(&|c| {
+                    c.suit == card.suit && c.rank.ord_ace_high() > card.rank.ord_ace_high()
+                });
+                if card.suit == self.trump {
+                    p_unbeaten
+                } else {
+                    let p_void = p_none(&|c| c.suit == card.suit);
+                    p_unbeaten * (1.0 - p_void * (1.0 - p_no_trump))
+                }
+            })
+            .sum();
+
+        total / self.hand.len() as f32 * self.rounds_left as f32
+    }
+
     /// Return the index of the card in a vector representation
     fn card_index(card: &BasicCard, suit_order: &[Suit]) -> usize {
         card.rank as usize + 13 * suit_order.iter().position(|c| *c == card.suit).unwrap()
@@ -206,7 +544,7 @@ impl PlayerState {
         card: &Option<BasicCard>,
         suit_order: &[Suit],
     ) {
-        assert_eq!(x.dim(), 52);
+        debug_assert_eq!(x.dim(), 52);
         x.fill(-1.0);
         if let Some(ref c) = card {
             x[Self::card_index(c, suit_order)] = 1.0;
@@ -214,8 +552,8 @@ impl PlayerState {
     }
 
     /// Translate the opponent belief set to a state vector
-    fn oppo_to_vector(mut x: ArrayViewMut<f32, Ix1>, hb: &HandBelief, suit_order: &[Suit]) {
-        assert_eq!(x.dim(), 52);
+    fn oppo_to_vector(mut x: ArrayViewMut<f32, Ix1>, hb: &B, suit_order: &[Suit]) {
+        debug_assert_eq!(x.dim(), 52);
         for c in BasicCard::all() {
             x[Self::card_index(&c, suit_order)] = hb.p(&c) * 2.0 - 1.0;
         }
@@ -227,27 +565,69 @@ impl PlayerState {
         card: &HashSet<BasicCard>,
         suit_order: &[Suit],
     ) {
-        assert_eq!(x.dim(), 52);
+        debug_assert_eq!(x.dim(), 52);
         x.fill(-1.0);
         for c in card {
             x[Self::card_index(c, suit_order)] = 1.0;
         }
     }
 
+    /// Return the ranks of the cards of the suit, as a bitset.
+    fn rank_mask(cards: &HashSet<BasicCard>, suit: Suit) -> u16 {
+        cards
+            .iter()
+            .filter(|c| c.suit == suit)
+            .fold(0, |mask, c| mask | (1 << c.rank as u16))
+    }
+
     /// Return the order of the suits as they should be represented in the state vector.
+    ///
+    /// The order only depends on the ranks in each suit of the hand,
+    /// the cards played, and the revealed and leading cards; suits
+    /// that agree on all of them are ordered by name. The opponent
+    /// belief isn't considered, so two suits that differ only there
+    /// (e.g. after the opponent took a revealed card) keep their
+    /// order under a permutation of suit names.
     fn update_suit_order(&mut self) {
-        let mut suits = self.suit_order;
+        self.suit_order = Self::canonical_suit_order(
+            self.trump,
+            &self.hand,
+            &self.played_cards,
+            self.revealed,
+            self.leading_card,
+        );
+        self.suit_order_dirty = false;
+    }
+
+    /// Return the suit order of `update_suit_order` for the given
+    /// trump, hand, cards played, and revealed and leading cards.
+    fn canonical_suit_order(
+        trump: Suit,
+        hand: &HashSet<BasicCard>,
+        played_cards: &HashSet<BasicCard>,
+        revealed: Option<BasicCard>,
+        leading_card: Option<BasicCard>,
+    ) -> [Suit; 4] {
+        let rank_in = |card: Option<BasicCard>, suit: Suit| {
+            card.filter(|c| c.suit == suit).map(|c| c.rank as u8)
+        };
+        let mut suits = [Suit::Clubs, Suit::Diamonds, Suit::Hearts, Suit::Spades];
         suits.sort_by_key(|s| {
             // trump comes first
             (
-                if *s == self.trump { 0 } else { 1 },
+                if *s == trump { 0 } else { 1 },
                 // then, highest card count
-                self.hand.iter().filter(|c| c.suit == *s).count(),
-                // normally ordinal as tie-breaker
+                hand.iter().filter(|c| c.suit == *s).count(),
+                // then, the ranks held and played
+                Self::rank_mask(hand, *s),
+                Self::rank_mask(played_cards, *s),
+                rank_in(revealed, *s),
+                rank_in(leading_card, *s),
+                // ordinal as the final tie-breaker
                 s.ord(),
             )
         });
-        self.suit_order = suits;
+        suits
     }
 
     fn format_hand(&self) -> String {
@@ -257,7 +637,13 @@ impl PlayerState {
     }
 }
 
-impl fmt::Display for PlayerState {
+impl<B: OpponentModel> Observer for PlayerState<B> {
+    fn observe(&mut self, _player: usize, event: &GameEvent) {
+        self.on_event(event)
+    }
+}
+
+impl<B: OpponentModel + fmt::Display> fmt::Display for PlayerState<B> {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         writeln!(fmt, "Trump: {}", self.trump)?;
         writeln!(fmt, "Active Player: {}", self.active)?;
@@ -278,3 +664,352 @@ impl fmt::Display for PlayerState {
         writeln!(fmt, "Opposition:\n{}", self.oppo)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::PlayerState;
+    use crate::cards::{parse_hand, BasicCard, Suit};
+    use crate::germanwhist::engine::StartRoundEvent;
+    use crate::germanwhist::{Action, GameEvent, PhaseRules, Round};
+    use crate::hand_belief::OpponentModel;
+    use crate::lean_belief::LeanBelief;
+    use ndarray::{s, Array};
+
+    #[test]
+    fn test_state_vector_v2() {
+        // the second rules grow the hands, for a round of 4 + 21 tricks
+        for rules in [PhaseRules::default(), PhaseRules::new(2, 1, 3).unwrap()] {
+            let mut round = Round::new((0, 1));
+            round.set_phase_rules(rules);
+            let mut ps = PlayerState::new(0);
+            for ev in &round.start_round(0)[0] {
+                ps.on_event(ev);
+            }
+
+            let n = PlayerState::state_size();
+            let mut v1 = Array::zeros(n);
+            let mut v2 = Array::zeros(PlayerState::state_size_v2());
+            assert_eq!(ps.state_vector_v2_size(), n + 1);
+
+            ps.state_vector(v1.view_mut());
+            ps.state_vector_v2(v2.view_mut());
+            assert_eq!(v1.slice(s![..n - 2]), v2.slice(s![..n - 2]));
+            assert_eq!(v2[n - 2], -1.0);
+            assert_eq!(v2[n], 1.0);
+
+            while !round.is_game_over() {
+                let action = round.possible_actions()[0];
+                for ev in &round.play_action(action).unwrap()[0] {
+                    ps.on_event(ev);
+                }
+            }
+
+            // all points have been played for; the scores sum to the total
+            ps.state_vector_v2(v2.view_mut());
+            assert_eq!(v2[n], -1.0);
+            assert!(((v2[n - 2] + 1.0) + (v2[n - 1] + 1.0) - 2.0).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_stack_state_action_vector() {
+        use ndarray::ArrayViewMut;
+
+        let mut round = Round::new((0, 1));
+        let mut ps = PlayerState::new(0);
+        for ev in &round.start_round(0)[0] {
+            ps.on_event(ev);
+        }
+        assert_eq!(
+            PlayerState::state_action_size(),
+            PlayerState::STATE_ACTION_SIZE
+        );
+
+        let action = round.possible_actions()[0];
+        let mut buf = [0.0f32; PlayerState::STATE_ACTION_SIZE];
+        ps.state_action_vector(ArrayViewMut::from(&mut buf[..]), false, Some(&action));
+
+        let mut heap = Array::zeros(PlayerState::state_action_size());
+        ps.state_action_vector(heap.view_mut(), false, Some(&action));
+        assert_eq!(heap.as_slice().unwrap(), &buf[..]);
+    }
+
+    #[test]
+    fn test_suit_order_is_current() {
+        let mut round = Round::new((0, 1));
+        let mut ps = PlayerState::new(0);
+        for ev in &round.start_round(0)[0] {
+            ps.on_event(ev);
+        }
+
+        while !round.is_game_over() {
+            let action = round.possible_actions()[0];
+            for ev in &round.play_action(action).unwrap()[0] {
+                ps.on_event(ev);
+
+                let order = ps.suit_order();
+                ps.update_suit_order();
+                assert_eq!(order, ps.suit_order());
+            }
+        }
+    }
+
+    #[test]
+    fn test_suit_order_is_canonical() {
+        let start = |hand: &str| {
+            let ev = GameEvent::Start(StartRoundEvent {
+                hand: parse_hand(hand).unwrap(),
+                revealed: "9♠".parse().unwrap(),
+                trump: Suit::Spades,
+                starting_player: 0,
+                building_tricks: 13,
+                scoring_tricks: 13,
+            });
+            PlayerState::from_events(0, &[ev])
+        };
+
+        // clubs and diamonds swapped, with the same count in each
+        let a = start("2♣ 5♣ K♣ 3♦ 7♦ A♦ 4♥ A♠");
+        let b = start("2♦ 5♦ K♦ 3♣ 7♣ A♣ 4♥ A♠");
+        assert_ne!(a.suit_order(), b.suit_order());
+        assert_eq!(a.state_vector_cached(), b.state_vector_cached());
+
+        // clubs and diamonds swapped, where neither is in hand and the
+        // two only differ in the revealed and leading cards
+        let start_led = |revealed: &str, lead: &str| {
+            let events = [
+                GameEvent::Start(StartRoundEvent {
+                    hand: parse_hand("4♥ 8♥ J♥ K♠ A♠").unwrap(),
+                    revealed: revealed.parse().unwrap(),
+                    trump: Suit::Spades,
+                    starting_player: 1,
+                    building_tricks: 13,
+                    scoring_tricks: 13,
+                }),
+                GameEvent::Action(Action {
+                    player: 1,
+                    card: lead.parse().unwrap(),
+                }),
+            ];
+            PlayerState::from_events(0, &events)
+        };
+        let a = start_led("9♣", "5♦");
+        let b = start_led("9♦", "5♣");
+        assert_ne!(a.suit_order(), b.suit_order());
+        assert_eq!(a.state_vector_cached(), b.state_vector_cached());
+    }
+
+    #[test]
+    fn test_from_events() {
+        let mut round = Round::new((0, 1));
+        let mut log = round.start_round(0)[1].clone();
+        let mut ps = PlayerState::new(1);
+        for ev in &log {
+            ps.on_event(ev);
+        }
+
+        for _ in 0..15 {
+            let action = round.possible_actions()[0];
+            for ev in &round.play_action(action).unwrap()[1] {
+                ps.on_event(ev);
+                log.push(ev.clone());
+            }
+        }
+
+        let replayed = PlayerState::from_events(1, &log);
+        let mut v1 = Array::zeros(PlayerState::state_size());
+        let mut v2 = Array::zeros(PlayerState::state_size());
+        ps.state_vector(v1.view_mut());
+        replayed.state_vector(v2.view_mut());
+        assert_eq!(v1, v2);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_from_events_requires_start() {
+        let mut round = Round::new((0, 1));
+        let log = round.start_round(0)[1].clone();
+        PlayerState::from_events(1, &log[1..]);
+    }
+
+    #[test]
+    fn test_expected_tricks() {
+        let mut round = Round::new((0, 1));
+        let events = round.start_round(0);
+        let mut ps = [
+            PlayerState::from_events(0, &events[0]),
+            PlayerState::from_events(1, &events[1]),
+        ];
+
+        while !round.is_game_over() {
+            for p in &ps {
+                let e = p.expected_tricks();
+                assert!(e >= 0.0 && e <= p.rounds_left as f32);
+            }
+
+            let action = round.possible_actions()[0];
+            let events = round.play_action(action).unwrap();
+            for (p, evs) in ps.iter_mut().zip(events.iter()) {
+                for ev in evs {
+                    p.on_event(ev);
+                }
+            }
+        }
+
+        assert_eq!(ps[0].expected_tricks(), 0.0);
+
+        // holding every trump, each card is sure to win its trick, and
+        // later cards are assumed to be as strong
+        let ev = GameEvent::Start(StartRoundEvent {
+            hand: BasicCard::all()
+                .into_iter()
+                .filter(|c| c.suit == Suit::Spades)
+                .collect(),
+            revealed: "2♥".parse().unwrap(),
+            trump: Suit::Spades,
+            starting_player: 0,
+            building_tricks: 13,
+            scoring_tricks: 13,
+        });
+        let ps = PlayerState::from_events(0, &[ev]);
+        assert_eq!(ps.expected_tricks(), 26.0);
+    }
+
+    #[test]
+    fn test_unknown_cards() {
+        let mut round = Round::new((0, 1));
+        let events = round.start_round(0);
+        let mut ps = [
+            PlayerState::from_events(0, &events[0]),
+            PlayerState::from_events(1, &events[1]),
+        ];
+
+        while !round.is_game_over() {
+            for (i, p) in ps.iter().enumerate() {
+                let known = p.public_known_cards();
+                let unknown = p.unknown_cards();
+                assert_eq!(known.len() + unknown.len(), 52);
+                assert!(unknown.iter().all(|c| !known.contains(c)));
+
+                // the belief is supported on the unknown cards, which
+                // hold the opponent's actual hand
+                for c in BasicCard::all() {
+                    if p.oppo.p(&c) > 0.0 {
+                        assert!(unknown.contains(&c), "{} is known", c);
+                    }
+                }
+                for c in &round.get_state().hands[1 - i] {
+                    assert!(unknown.contains(c));
+                }
+            }
+
+            let action = round.possible_actions()[0];
+            let events = round.play_action(action).unwrap();
+            for (p, evs) in ps.iter_mut().zip(events.iter()) {
+                for ev in evs {
+                    p.on_event(ev);
+                }
+            }
+        }
+        assert_eq!(ps[0].unknown_cards(), vec![]);
+    }
+
+    #[test]
+    fn test_state_vector_after() {
+        let mut round = Round::new((0, 1));
+        let mut ps = PlayerState::from_events(0, &round.start_round(0)[0]);
+        let n = PlayerState::state_size();
+        let (mut before, mut after, mut actual) =
+            (Array::zeros(n), Array::zeros(n), Array::zeros(n));
+
+        let mut checked = 0;
+        while !round.is_game_over() {
+            let action = round.possible_actions()[0];
+            let events = round.play_action(action).unwrap();
+            if action.player != 0 {
+                for ev in &events[0] {
+                    ps.on_event(ev);
+                }
+                continue;
+            }
+
+            ps.state_vector(before.view_mut());
+            ps.state_vector_after(&action, after.view_mut());
+            let exact = ps.leading_card.is_none() || ps.revealed.is_none();
+            let mut unchanged = Array::zeros(n);
+            ps.state_vector(unchanged.view_mut());
+            assert_eq!(before, unchanged);
+
+            for ev in &events[0] {
+                ps.on_event(ev);
+            }
+            // leading plays, and scoring-phase tricks, hold no unknowns
+            if exact {
+                ps.state_vector(actual.view_mut());
+                assert_eq!(after, actual);
+                checked += 1;
+            }
+        }
+        assert!(checked > 13);
+    }
+
+    #[test]
+    fn test_lean_belief_state() {
+        let mut round = Round::new((0, 1));
+        let events = round.start_round(0);
+        let mut ps: Vec<PlayerState<LeanBelief>> = (0..2)
+            .map(|i| PlayerState::with_belief(i, LeanBelief::new()))
+            .collect();
+        for (p, evs) in ps.iter_mut().zip(events.iter()) {
+            for ev in evs {
+                p.on_event(ev);
+            }
+        }
+
+        let mut v = Array::zeros(PlayerState::state_size());
+        while !round.is_game_over() {
+            for (i, p) in ps.iter().enumerate() {
+                let hand = &round.get_state().hands[1 - i];
+                assert_eq!(p.oppo.num_cards(), hand.len() as f32);
+                assert!(hand.iter().all(|c| p.oppo.p(c) > 0.0));
+                p.state_vector(v.view_mut());
+            }
+
+            let action = round.possible_actions()[0];
+            let events = round.play_action(action).unwrap();
+            for (p, evs) in ps.iter_mut().zip(events.iter()) {
+                for ev in evs {
+                    p.on_event(ev);
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let mut round = Round::new((0, 1));
+        let mut ps = PlayerState::new(0);
+        for ev in &round.start_round(0)[0] {
+            ps.on_event(ev);
+        }
+
+        // play into the middle of the game
+        for _ in 0..21 {
+            let action = round.possible_actions()[0];
+            for ev in &round.play_action(action).unwrap()[0] {
+                ps.on_event(ev);
+            }
+        }
+
+        let json = serde_json::to_string(&ps).unwrap();
+        let restored: PlayerState = serde_json::from_str(&json).unwrap();
+        assert_eq!(json, serde_json::to_string(&restored).unwrap());
+
+        let mut v1 = Array::zeros(PlayerState::state_size());
+        let mut v2 = Array::zeros(PlayerState::state_size());
+        ps.state_vector(v1.view_mut());
+        restored.state_vector(v2.view_mut());
+        assert_eq!(v1, v2);
+    }
+}
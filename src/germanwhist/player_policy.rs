@@ -0,0 +1,441 @@
+use super::engine::{Action, GameEvent, Round};
+use super::player::PlayerState;
+use super::state::PlayerView;
+use crate::cards::{BasicCard, Rank};
+use crate::learning::neural_net::NeuralNet;
+use crate::learning::training::SarsaPlayer;
+use ndarray::Array;
+use rand::{thread_rng, Rng};
+
+/// A policy for choosing which card to play.
+pub trait Player {
+    /// Called at the start of each deal, with the seat the player
+    /// occupies.
+    fn start_deal(&mut self, _seat: usize) {}
+
+    /// Receive an event, as seen from the player's seat.
+    fn on_game_action(&mut self, _ev: &GameEvent) {}
+
+    /// Return a card to play, based on the current view of the world.
+    fn play_card(&mut self, view: &PlayerView) -> BasicCard;
+}
+
+/// Player choosing greedily with respect to a learned action-value
+/// network.
+pub struct GreedyPlayer {
+    net: NeuralNet,
+    player: SarsaPlayer,
+}
+
+impl GreedyPlayer {
+    pub fn new(net: NeuralNet) -> GreedyPlayer {
+        let player = SarsaPlayer::new(PlayerState::new(0), net.num_parameters());
+        GreedyPlayer { net, player }
+    }
+}
+
+impl Player for GreedyPlayer {
+    fn start_deal(&mut self, seat: usize) {
+        self.player = SarsaPlayer::new(PlayerState::new(seat), self.net.num_parameters());
+    }
+
+    fn on_game_action(&mut self, ev: &GameEvent) {
+        self.player.state.on_event(ev);
+    }
+
+    fn play_card(&mut self, view: &PlayerView) -> BasicCard {
+        let actions: Vec<_> = view
+            .playable_cards()
+            .into_iter()
+            .map(|card| Action {
+                player: view.player,
+                card,
+            })
+            .collect();
+        let mut sa = Array::zeros(PlayerState::state_action_size());
+        self.player
+            .greedy_action(&self.net, &actions, sa.view_mut())
+            .card
+    }
+}
+
+/// Player choosing uniformly among its legal cards, with its own
+/// random number generator.
+pub struct RandomPlayer<R: Rng> {
+    rng: R,
+}
+
+impl<R: Rng> RandomPlayer<R> {
+    pub fn new(rng: R) -> RandomPlayer<R> {
+        RandomPlayer { rng }
+    }
+}
+
+impl<R: Rng> Player for RandomPlayer<R> {
+    fn play_card(&mut self, view: &PlayerView) -> BasicCard {
+        random_card(view, &mut self.rng)
+    }
+}
+
+/// Player playing a fixed sequence of cards, for deterministic tests.
+///
+/// Panics if the next card in the script isn't legal, or the script
+/// runs out.
+pub struct ScriptedPlayer {
+    /// remaining moves, with the next one last
+    moves: Vec<BasicCard>,
+}
+
+impl ScriptedPlayer {
+    pub fn new(mut moves: Vec<BasicCard>) -> ScriptedPlayer {
+        moves.reverse();
+        ScriptedPlayer { moves }
+    }
+
+    /// Number of scripted cards not yet played.
+    pub fn moves_left(&self) -> usize {
+        self.moves.len()
+    }
+}
+
+impl Player for ScriptedPlayer {
+    fn play_card(&mut self, view: &PlayerView) -> BasicCard {
+        let card = self
+            .moves
+            .pop()
+            .unwrap_or_else(|| panic!("player {} has run out of scripted moves", view.player + 1));
+        assert!(
+            view.playable_cards().contains(&card),
+            "scripted card {} is not legal for player {}",
+            card,
+            view.player + 1
+        );
+        card
+    }
+}
+
+fn random_card<R: Rng>(view: &PlayerView, rng: &mut R) -> BasicCard {
+    *rng.choose(&view.playable_cards())
+        .expect("must have a playable card")
+}
+
+/// Players provided by the library.
+pub enum BuiltinPlayer {
+    /// Play a uniformly random legal card, using the thread's random
+    /// number generator. See `RandomPlayer` to supply one.
+    Random,
+
+    /// Try to win trumps and non-trumps of at least `min_rank` in the
+    /// hand-building phase, and win tricks as cheaply as possible in
+    /// the scoring phase.
+    Basic { min_rank: Option<Rank> },
+
+    /// Play greedily with respect to a learned network.
+    Greedy(Box<GreedyPlayer>),
+}
+
+impl BuiltinPlayer {
+    pub fn greedy(net: NeuralNet) -> BuiltinPlayer {
+        BuiltinPlayer::Greedy(Box::new(GreedyPlayer::new(net)))
+    }
+}
+
+fn basic_card(view: &PlayerView, min_rank: Option<Rank>) -> BasicCard {
+    let mut cards = view.playable_cards();
+
+    cards.sort_by_key(|c| (c.rank.ord_ace_high(), view.ord_suit(c.suit)));
+
+    match view.contest_revealed_above(min_rank) {
+        // playing for cards
+        Some(try_to_win) => {
+            if try_to_win {
+                // Play the highest non-trump, otherwise play the lowest trump
+                *cards
+                    .iter()
+                    .rev()
+                    .find(|p| p.suit != view.trump)
+                    .unwrap_or(&cards[0])
+            } else {
+                // try to ditch
+                *cards
+                    .iter()
+                    .find(|p| p.suit != view.trump)
+                    .unwrap_or(&cards[0])
+            }
+        }
+
+        // playing for points
+        None => {
+            match &view.leading_card {
+                Some(ref lc) => {
+                    // play the lowest card to beat it, otherwise ditch
+                    *cards
+                        .iter()
+                        .find(|p| view.wins_against(lc, p))
+                        .unwrap_or(&cards[0])
+                }
+                None => cards[0],
+            }
+        }
+    }
+}
+
+impl Player for BuiltinPlayer {
+    fn start_deal(&mut self, seat: usize) {
+        if let BuiltinPlayer::Greedy(ref mut p) = *self {
+            p.start_deal(seat);
+        }
+    }
+
+    fn on_game_action(&mut self, ev: &GameEvent) {
+        if let BuiltinPlayer::Greedy(ref mut p) = *self {
+            p.on_game_action(ev);
+        }
+    }
+
+    fn play_card(&mut self, view: &PlayerView) -> BasicCard {
+        match *self {
+            BuiltinPlayer::Random => random_card(view, &mut thread_rng()),
+            BuiltinPlayer::Basic { min_rank } => basic_card(view, min_rank),
+            BuiltinPlayer::Greedy(ref mut p) => p.play_card(view),
+        }
+    }
+}
+
+/// Play `deals` deals between two players, with `p0` in seat 0 and
+/// `p1` in seat 1, and a random player leading each deal. Returns the
+/// number of deals won by each player; drawn deals count for neither.
+pub fn play_match(p0: &mut dyn Player, p1: &mut dyn Player, deals: usize) -> [usize; 2] {
+    play_match_with_rng(p0, p1, deals, &mut thread_rng())
+}
+
+/// Like `play_match`, using `rng` to deal and to choose the leader of
+/// each deal.
+pub fn play_match_with_rng<R: Rng>(
+    p0: &mut dyn Player,
+    p1: &mut dyn Player,
+    deals: usize,
+    rng: &mut R,
+) -> [usize; 2] {
+    let mut players: [&mut dyn Player; 2] = [p0, p1];
+    let mut won = [0, 0];
+    let mut round = Round::new((0, 1));
+
+    for _ in 0..deals {
+        play_deal(&mut players, &mut round, None, rng);
+        if let Some(w) = round.winner() {
+            won[w] += 1;
+        }
+    }
+
+    won
+}
+
+/// Aggregate results of `run_tournament`, indexed by seat.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TournamentStats {
+    pub wins: [usize; 2],
+    pub draws: usize,
+    pub total_score: [usize; 2],
+
+    /// mean of seat 0's score less seat 1's, per deal
+    pub avg_margin: f32,
+}
+
+/// Play `deals` deals between two players, with `p0` in seat 0 and
+/// `p1` in seat 1. If `alternate_start`, the seats take turns leading,
+/// starting with seat 0; otherwise a random player leads each deal.
+pub fn run_tournament(
+    p0: &mut dyn Player,
+    p1: &mut dyn Player,
+    deals: usize,
+    alternate_start: bool,
+) -> TournamentStats {
+    run_tournament_with_rng(p0, p1, deals, alternate_start, &mut thread_rng())
+}
+
+/// Like `run_tournament`, using `rng` to deal and to choose the leader
+/// of each deal.
+pub fn run_tournament_with_rng<R: Rng>(
+    p0: &mut dyn Player,
+    p1: &mut dyn Player,
+    deals: usize,
+    alternate_start: bool,
+    rng: &mut R,
+) -> TournamentStats {
+    let mut players: [&mut dyn Player; 2] = [p0, p1];
+    let mut round = Round::new((0, 1));
+    let mut stats = TournamentStats {
+        wins: [0, 0],
+        draws: 0,
+        total_score: [0, 0],
+        avg_margin: 0.0,
+    };
+
+    for deal in 0..deals {
+        let start = if alternate_start {
+            Some(deal % 2)
+        } else {
+            None
+        };
+        play_deal(&mut players, &mut round, start, rng);
+
+        let score = round.get_state().score;
+        match round.winner() {
+            Some(w) => stats.wins[w] += 1,
+            None => stats.draws += 1,
+        }
+        stats.total_score[0] += score[0];
+        stats.total_score[1] += score[1];
+    }
+
+    if deals > 0 {
+        let margin = stats.total_score[0] as f32 - stats.total_score[1] as f32;
+        stats.avg_margin = margin / deals as f32;
+    }
+    stats
+}
+
+/// Play a single deal to the end, notifying both players of their
+/// events.
+fn play_deal<R: Rng>(
+    players: &mut [&mut dyn Player; 2],
+    round: &mut Round,
+    start: Option<usize>,
+    rng: &mut R,
+) {
+    let events = round.start_round_with_rng(start, rng);
+    for (seat, (player, evs)) in players.iter_mut().zip(&events).enumerate() {
+        player.start_deal(seat);
+        for ev in evs {
+            player.on_game_action(ev);
+        }
+    }
+
+    while !round.is_game_over() {
+        let action = {
+            let view = round.active_player_view();
+            Action {
+                player: view.player,
+                card: players[view.player].play_card(&view),
+            }
+        };
+
+        let events = round
+            .play_action(action)
+            .expect("players must choose legal cards");
+        for (player, evs) in players.iter_mut().zip(&events) {
+            for ev in evs {
+                player.on_game_action(ev);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        play_match, play_match_with_rng, run_tournament, run_tournament_with_rng, BuiltinPlayer,
+        Player, RandomPlayer, ScriptedPlayer,
+    };
+    use crate::cards::{parse_hand, BasicCard, Rank};
+    use crate::germanwhist::{PhaseRules, PlayerState, PlayerView};
+    use crate::learning::neural_net::{ActivationFunction, LayerDesc, NeuralNet};
+    use rand::{SeedableRng, StdRng};
+
+    /// Basic player remembering the cards it played.
+    struct Recorder(BuiltinPlayer, Vec<BasicCard>);
+
+    impl Player for Recorder {
+        fn play_card(&mut self, view: &PlayerView) -> BasicCard {
+            let card = self.0.play_card(view);
+            self.1.push(card);
+            card
+        }
+    }
+
+    #[test]
+    fn test_scripted_player() {
+        let basic = || BuiltinPlayer::Basic { min_rank: None };
+        let mut recorders = [Recorder(basic(), Vec::new()), Recorder(basic(), Vec::new())];
+        let [ref mut r0, ref mut r1] = recorders;
+        let won = play_match_with_rng(r0, r1, 1, &mut StdRng::from_seed(&[5][..]));
+
+        // replaying the same deal with the recorded moves gives the same result
+        let mut s0 = ScriptedPlayer::new(recorders[0].1.clone());
+        let mut s1 = ScriptedPlayer::new(recorders[1].1.clone());
+        let replayed = play_match_with_rng(&mut s0, &mut s1, 1, &mut StdRng::from_seed(&[5][..]));
+        assert_eq!(replayed, won);
+        assert_eq!(s0.moves_left() + s1.moves_left(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "is not legal")]
+    fn test_scripted_player_illegal() {
+        // nobody can hold every card of the suit
+        let mut s0 =
+            ScriptedPlayer::new(parse_hand("2♣ 3♣ 4♣ 5♣ 6♣ 7♣ 8♣ 9♣ T♣ J♣ Q♣ K♣ A♣").unwrap());
+        let mut s1 =
+            ScriptedPlayer::new(parse_hand("2♦ 3♦ 4♦ 5♦ 6♦ 7♦ 8♦ 9♦ T♦ J♦ Q♦ K♦ A♦").unwrap());
+        play_match(&mut s0, &mut s1, 1);
+    }
+
+    #[test]
+    fn test_play_match() {
+        // an odd number of one-point scoring tricks can't be split
+        // evenly, so every deal has a winner
+        assert_eq!(PhaseRules::default().num_scoring_tricks() % 2, 1);
+
+        let mut basic = BuiltinPlayer::Basic {
+            min_rank: Some(Rank::King),
+        };
+        let mut seeded = RandomPlayer::new(StdRng::from_seed(&[1][..]));
+        let mut rng = StdRng::from_seed(&[2][..]);
+        let won = play_match_with_rng(&mut basic, &mut seeded, 20, &mut rng);
+        assert_eq!(won[0] + won[1], 20);
+        assert!(won[0] > won[1], "basic won only {:?}", won);
+
+        let sa = PlayerState::state_action_size();
+        let net = NeuralNet::new(
+            &[
+                LayerDesc::new(sa, 10, ActivationFunction::SymmetricSigmoid),
+                LayerDesc::new(10, 1, ActivationFunction::Sigmoid),
+            ],
+            0.1,
+        )
+        .unwrap();
+        let mut greedy = BuiltinPlayer::greedy(net);
+        let mut random = BuiltinPlayer::Random;
+        let won = play_match(&mut random, &mut greedy, 5);
+        assert_eq!(won[0] + won[1], 5);
+    }
+
+    #[test]
+    fn test_run_tournament() {
+        let mut basic = BuiltinPlayer::Basic { min_rank: None };
+        let mut random = BuiltinPlayer::Random;
+        let stats = run_tournament(&mut basic, &mut random, 10, true);
+        assert_eq!(stats.wins[0] + stats.wins[1] + stats.draws, 10);
+
+        // every trick of the scoring phase is worth a point
+        assert_eq!(stats.total_score[0] + stats.total_score[1], 10 * 13);
+        let margin = (stats.total_score[0] as f32 - stats.total_score[1] as f32) / 10.0;
+        assert_eq!(stats.avg_margin, margin);
+
+        let empty = run_tournament(&mut basic, &mut random, 0, false);
+        assert_eq!(empty.wins, [0, 0]);
+        assert_eq!(empty.avg_margin, 0.0);
+    }
+
+    #[test]
+    fn test_run_tournament_with_rng() {
+        // with every source of randomness seeded, results are reproducible
+        let run = || {
+            let mut p0 = RandomPlayer::new(StdRng::from_seed(&[1][..]));
+            let mut p1 = BuiltinPlayer::Basic { min_rank: None };
+            let mut rng = StdRng::from_seed(&[2][..]);
+            run_tournament_with_rng(&mut p0, &mut p1, 5, false, &mut rng)
+        };
+        assert_eq!(run(), run());
+    }
+}
@@ -0,0 +1,182 @@
+use super::util::trick_winner;
+use crate::cards::{BasicCard, Suit};
+use rand::Rng;
+
+/// Remove and return a random card from the hand, following `suit`
+/// if possible.
+fn random_follow<R: Rng>(hand: &mut Vec<BasicCard>, suit: Suit, rng: &mut R) -> BasicCard {
+    let n = hand.iter().filter(|c| c.suit == suit).count();
+    let i = if n == 0 {
+        rng.gen_range(0, hand.len())
+    } else {
+        let k = rng.gen_range(0, n);
+        hand.iter()
+            .enumerate()
+            .filter(|&(_, c)| c.suit == suit)
+            .nth(k)
+            .expect("k is less than the number of matching cards")
+            .0
+    };
+    hand.swap_remove(i)
+}
+
+/// Play out the scoring phase of a deal with known hands, each player
+/// choosing uniformly among their legal cards. Returns the number of
+/// tricks won by each player.
+///
+/// `lead` is the player who leads the first trick. Both hands must be
+/// the same size.
+pub fn simulate_scoring_phase<R: Rng>(
+    mut hands: [Vec<BasicCard>; 2],
+    trump: Suit,
+    mut lead: usize,
+    rng: &mut R,
+) -> [usize; 2] {
+    assert_eq!(
+        hands[0].len(),
+        hands[1].len(),
+        "hands must be the same size"
+    );
+
+    let mut tricks = [0, 0];
+
+    while !hands[lead].is_empty() {
+        let i = rng.gen_range(0, hands[lead].len());
+        let lc = hands[lead].swap_remove(i);
+        let fc = random_follow(&mut hands[1 - lead], lc.suit, rng);
+
+        if trick_winner(&lc, &fc, trump) == fc {
+            lead = 1 - lead;
+        }
+        tricks[lead] += 1;
+    }
+
+    tricks
+}
+
+/// Return the tricks won by each player from here under optimal play
+/// by both, by exhaustive search.
+// `Option::is_none_or` would need Rust 1.82
+#[allow(clippy::unnecessary_map_or)]
+fn solve_exhaustive(hands: &mut [Vec<BasicCard>; 2], trump: Suit, lead: usize) -> [usize; 2] {
+    if hands[lead].is_empty() {
+        return [0, 0];
+    }
+
+    let follow = 1 - lead;
+    let mut best_for_lead: Option<[usize; 2]> = None;
+    for i in 0..hands[lead].len() {
+        let lc = hands[lead].remove(i);
+        let must_follow = hands[follow].iter().any(|c| c.suit == lc.suit);
+
+        let mut best_for_follow: Option<[usize; 2]> = None;
+        for j in 0..hands[follow].len() {
+            if must_follow && hands[follow][j].suit != lc.suit {
+                continue;
+            }
+            let fc = hands[follow].remove(j);
+
+            let winner = if trick_winner(&lc, &fc, trump) == fc {
+                follow
+            } else {
+                lead
+            };
+            let mut result = solve_exhaustive(hands, trump, winner);
+            result[winner] += 1;
+            if best_for_follow.map_or(true, |b| result[follow] > b[follow]) {
+                best_for_follow = Some(result);
+            }
+
+            hands[follow].insert(j, fc);
+        }
+        hands[lead].insert(i, lc);
+
+        let result = best_for_follow.expect("follower must have a card");
+        if best_for_lead.map_or(true, |b| result[lead] > b[lead]) {
+            best_for_lead = Some(result);
+        }
+    }
+
+    best_for_lead.expect("leader has a card")
+}
+
+/// Exactly solve the last two tricks of a deal. Returns the number of
+/// tricks won by `my` (index 0) and `opp` (index 1) when both play
+/// perfectly, with `lead` the index of the player leading.
+pub fn two_card_endgame(
+    my: [BasicCard; 2],
+    opp: [BasicCard; 2],
+    trump: Suit,
+    lead: usize,
+) -> [usize; 2] {
+    solve_exhaustive(&mut [my.to_vec(), opp.to_vec()], trump, lead)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{simulate_scoring_phase, two_card_endgame};
+    use crate::cards::{parse_hand, Suit};
+    use rand::thread_rng;
+
+    #[test]
+    fn test_simulate_scoring_phase() {
+        let mut rng = thread_rng();
+
+        // player 1 holds every trump, so wins every trick
+        let hands = [
+            parse_hand("2♣ 3♣ 4♦ 5♦ 6♠").unwrap(),
+            parse_hand("2♥ 3♥ 4♥ 5♥ 6♥").unwrap(),
+        ];
+        for lead in 0..2 {
+            let tricks = simulate_scoring_phase(hands.clone(), Suit::Hearts, lead, &mut rng);
+            assert_eq!(tricks, [0, 5]);
+        }
+
+        // with no trumps, the leader of all-higher cards can't lose
+        let hands = [
+            parse_hand("A♣ K♣ A♦ K♦").unwrap(),
+            parse_hand("2♣ 3♣ 2♦ 3♦").unwrap(),
+        ];
+        assert_eq!(
+            simulate_scoring_phase(hands.clone(), Suit::Spades, 0, &mut rng),
+            [4, 0]
+        );
+
+        for _ in 0..100 {
+            let tricks = simulate_scoring_phase(hands.clone(), Suit::Clubs, 1, &mut rng);
+            assert_eq!(tricks[0] + tricks[1], 4);
+        }
+    }
+
+    #[test]
+    fn test_two_card_endgame() {
+        let h = |s: &str| {
+            let v = parse_hand(s).unwrap();
+            [v[0], v[1]]
+        };
+
+        // the low trump wins the last trick, after the ace is cashed
+        assert_eq!(
+            two_card_endgame(h("A♠ 2♥"), h("K♠ 3♣"), Suit::Hearts, 0),
+            [2, 0]
+        );
+
+        // leading the ace first keeps one trick; the deuce loses both
+        assert_eq!(
+            two_card_endgame(h("A♠ 2♠"), h("K♠ 3♥"), Suit::Hearts, 0),
+            [1, 1]
+        );
+
+        // the follower must follow suit, then ruffs or wins either way
+        assert_eq!(
+            two_card_endgame(h("2♥ A♣"), h("K♣ 5♦"), Suit::Hearts, 1),
+            [2, 0]
+        );
+
+        // no trumps in play: the opponent's ace beats the lead
+        assert_eq!(
+            two_card_endgame(h("K♦ 4♣"), h("A♦ 5♣"), Suit::Spades, 0),
+            [0, 2]
+        );
+    }
+}
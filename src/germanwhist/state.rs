@@ -1,5 +1,9 @@
-use super::engine::ActionError;
-use crate::cards::{BasicCard, BasicDeck, Suit};
+use super::engine::{ActionError, Phase, ScoringRules};
+use super::util::trick_winner;
+use crate::cards::{card_mask, BasicCard, BasicDeck, Rank, Suit, FULL_DECK_MASK, NUM_BASIC_CARDS};
+use crate::hand_belief::HandBelief;
+use itertools::Itertools;
+use rand::{thread_rng, Rng, SeedableRng, StdRng};
 use std::cmp::Ordering;
 use std::slice;
 
@@ -23,26 +27,68 @@ pub struct GameState {
     /// player whose turn it is
     pub active: usize,
 
-    /// number of rounds left in this phase
+    /// number of tricks left in the round, across both phases, or 0
+    /// once it's over
     pub rounds_left: usize,
 
+    /// number of tricks left in the current phase
+    pub phase_tricks_left: usize,
+
     // currently revealed card, if any
     pub revealed: Option<BasicCard>,
+
+    /// the deck before dealing, with the top card last
+    initial_deck: Vec<BasicCard>,
+
+    /// player dealt the first card
+    first_dealt: usize,
 }
 
 impl GameState {
     /// Create a new round
     pub fn new<T: Into<Option<usize>>>(player: T) -> GameState {
+        Self::with_rng(player, &mut thread_rng())
+    }
+
+    /// Create a new round, dealt deterministically from `seed`.
+    pub fn from_seed<T: Into<Option<usize>>>(player: T, seed: &[usize]) -> GameState {
+        Self::with_rng(player, &mut StdRng::from_seed(seed))
+    }
+
+    /// Create a new round, shuffling with the given random number
+    /// generator.
+    ///
+    /// Cards are dealt one at a time, alternating between the players
+    /// and starting with the player to lead, until each has 13. The
+    /// next card is then revealed.
+    pub fn with_rng<T: Into<Option<usize>>, R: Rng>(player: T, rng: &mut R) -> GameState {
         let mut deck = BasicDeck::new();
-        deck.shuffle();
+        deck.shuffle_with(rng);
+        Self::from_deck(player, deck)
+    }
+
+    /// Create a new round, dealing from `deck` as in `with_rng`. The
+    /// deck must hold all 52 cards.
+    pub fn from_deck<T: Into<Option<usize>>>(player: T, mut deck: BasicDeck) -> GameState {
+        assert!(
+            deck.num_cards_left() == NUM_BASIC_CARDS && card_mask(deck.cards()) == FULL_DECK_MASK,
+            "deck must hold every card once"
+        );
+        let initial_deck = deck.cards().to_vec();
+
+        let active: usize = player.into().unwrap_or(0);
+        let mut hands = [Vec::with_capacity(13), Vec::with_capacity(13)];
+        for i in 0..26 {
+            let c = deck.draw().expect("deck has enough cards to deal");
+            hands[(active + i) % 2].push(c);
+        }
 
-        let hands = [deck.draw_n(13).unwrap(), deck.draw_n(13).unwrap()];
         let c = deck.draw().expect("deck has 26 cards left");
         let trump = c.suit;
         let score = [0, 0];
-        let active: usize = player.into().unwrap_or(0);
         let played = None;
         let rounds_left = 26;
+        let phase_tricks_left = 13;
 
         GameState {
             deck,
@@ -52,7 +98,59 @@ impl GameState {
             active,
             played,
             rounds_left,
+            phase_tricks_left,
             revealed: Some(c),
+            initial_deck,
+            first_dealt: active,
+        }
+    }
+
+    /// Return the cards dealt at the start of the round, as (player,
+    /// card) pairs in the order they were drawn from the deck, along
+    /// with the card revealed after dealing.
+    pub fn deal_transcript(&self) -> (Vec<(usize, BasicCard)>, BasicCard) {
+        let mut drawn = self.initial_deck.iter().rev();
+        let deal = drawn
+            .by_ref()
+            .take(26)
+            .enumerate()
+            .map(|(i, c)| ((self.first_dealt + i) % 2, *c))
+            .collect();
+        let revealed = *drawn.next().expect("deck holds every card");
+        (deal, revealed)
+    }
+
+    /// Return the deck as it was before dealing, with the top card
+    /// last. Dealing from it with `from_deck` reproduces the round.
+    pub fn initial_deck(&self) -> &[BasicCard] {
+        &self.initial_deck
+    }
+
+    /// Check that no card appears in more than one place (or twice in
+    /// the same place) among the hands, the deck, the revealed card,
+    /// and the played card. Returns the duplicated cards otherwise.
+    pub fn validate(&self) -> Result<(), Vec<BasicCard>> {
+        let mut seen = 0u64;
+        let mut dups = Vec::new();
+
+        let all = self.hands[0]
+            .iter()
+            .chain(&self.hands[1])
+            .chain(self.deck.cards())
+            .chain(&self.revealed)
+            .chain(&self.played);
+        for c in all {
+            let bit = 1u64 << u8::from(c);
+            if seen & bit != 0 && !dups.contains(c) {
+                dups.push(*c);
+            }
+            seen |= bit;
+        }
+
+        if dups.is_empty() {
+            Ok(())
+        } else {
+            Err(dups)
         }
     }
 
@@ -73,21 +171,7 @@ impl GameState {
         if leading == following {
             return None;
         }
-
-        if leading.suit == self.trump {
-            Some(
-                following.suit != self.trump
-                    || leading.rank.ord_ace_high() > following.rank.ord_ace_high(),
-            )
-        } else {
-            Some(if following.suit == self.trump {
-                false
-            } else if following.suit != leading.suit {
-                true
-            } else {
-                leading.rank.ord_ace_high() > following.rank.ord_ace_high()
-            })
-        }
+        Some(trick_winner(leading, following, self.trump) == *leading)
     }
 
     /// Reveal a new top card
@@ -97,7 +181,32 @@ impl GameState {
 
     /// Add score to specified player
     pub fn increment_score(&mut self, player: usize, points: usize) {
-        self.score[player] += points;
+        self.score[player] = self.score[player].saturating_add(points);
+    }
+
+    /// Return the most points either player can still score, with
+    /// tricks worth `rules` points in each phase. A custom trick value
+    /// isn't accounted for; see `Round::points_remaining`.
+    pub fn points_remaining(&self, rules: ScoringRules) -> usize {
+        self.player_view(self.active).points_remaining(rules)
+    }
+
+    /// Return a key identifying the position, suitable for hashing
+    /// in transposition tables.
+    ///
+    /// Hands are stored as bitsets, so the order of cards within a
+    /// hand doesn't affect the key. The undealt deck is not part of
+    /// the key, so it only identifies positions fully once the deck
+    /// is exhausted.
+    pub fn position_key(&self) -> PositionKey {
+        PositionKey {
+            hands: [card_mask(&self.hands[0]), card_mask(&self.hands[1])],
+            trump: self.trump,
+            revealed: self.revealed,
+            played: self.played,
+            active: self.active,
+            score: self.score,
+        }
     }
 
     /// Utility function to display cards in order.
@@ -110,6 +219,18 @@ impl GameState {
     }
 }
 
+/// Compact, collision-free summary of a `GameState`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PositionKey {
+    /// card bitsets for each player
+    pub hands: [u64; 2],
+    pub trump: Suit,
+    pub revealed: Option<BasicCard>,
+    pub played: Option<BasicCard>,
+    pub active: usize,
+    pub score: [usize; 2],
+}
+
 pub struct PlayerViewMut<'a> {
     hand: &'a mut Vec<BasicCard>,
 }
@@ -136,7 +257,7 @@ impl<'a> PlayerViewMut<'a> {
                 self.hand.remove(i);
                 Ok(())
             }
-            None => Err(ActionError::MissingCard),
+            None => Err(ActionError::MissingCard(*c)),
         }
     }
 }
@@ -147,29 +268,156 @@ pub struct PlayerView<'a> {
 
     pub player: usize,
 
+    /// player whose turn it is
+    pub active: usize,
+
     pub revealed: Option<BasicCard>,
     pub leading_card: Option<BasicCard>,
 
     pub trump: Suit,
 
     pub score: [usize; 2],
+
+    /// number of tricks left in the round, across both phases, or 0
+    /// once it's over
+    pub rounds_left: usize,
+
+    /// number of tricks left in the current phase
+    pub phase_tricks_left: usize,
 }
 
+/// Lowest non-trump rank that `PlayerView::should_contest_revealed`
+/// considers worth winning.
+pub const CONTEST_MIN_RANK: Rank = Rank::Jack;
+
+/// Average strength of a card, as in `PlayerView::trick_value`: a
+/// quarter of the cards are trumps.
+const MEAN_CARD_STRENGTH: f32 = 0.75 * 0.25 + 0.25 * 0.75;
+
 impl<'a> PlayerView<'a> {
     pub fn from_state(player: usize, gs: &GameState) -> PlayerView {
         PlayerView {
             player,
             hand: &gs.hands[player],
+            active: gs.active,
             revealed: gs.revealed,
             leading_card: gs.played,
             trump: gs.trump,
             score: gs.score,
+            rounds_left: gs.rounds_left,
+            phase_tricks_left: gs.phase_tricks_left,
         }
     }
 
+    /// Return the phase the round is in, or `Phase::GameOver` once no
+    /// tricks are left.
+    pub fn phase(&self) -> Phase {
+        Phase::from_parts(self.revealed, self.rounds_left)
+    }
+
+    /// Return the number of cards the opponent holds.
+    ///
+    /// Both players hold the same number of cards between tricks, so
+    /// the hands only differ by the card led in the current trick.
+    pub fn opponent_hand_size(&self) -> usize {
+        match self.leading_card {
+            // the opponent led
+            Some(_) if self.active == self.player => self.hand.len() - 1,
+            // this player led
+            Some(_) => self.hand.len() + 1,
+            None => self.hand.len(),
+        }
+    }
+
+    /// Return the most points either player can still score, as in
+    /// `GameState::points_remaining`.
+    pub fn points_remaining(&self, rules: ScoringRules) -> usize {
+        let building_left = match self.phase() {
+            Phase::HandBuilding => self.phase_tricks_left,
+            _ => 0,
+        };
+        let scoring_left = self.rounds_left - building_left;
+        building_left * rules.0 + scoring_left * rules.1
+    }
+
+    /// Split the opponent's possible hands into the cards they must
+    /// hold, the cards they might hold, and how many of the latter.
+    fn opponent_hand_constraints(
+        &self,
+        belief: &HandBelief,
+    ) -> (Vec<BasicCard>, Vec<BasicCard>, Option<usize>) {
+        let owned = belief.owned_cards();
+        let candidates: Vec<_> = belief
+            .uncertain_cards()
+            .into_iter()
+            .filter(|c| !self.hand.contains(c))
+            .collect();
+        let k = self
+            .opponent_hand_size()
+            .checked_sub(owned.len())
+            .filter(|k| *k <= candidates.len());
+        (owned, candidates, k)
+    }
+
+    /// Enumerate every opponent hand consistent with the belief: it
+    /// contains the cards the opponent is known to own, none that were
+    /// played or ruled out, and has the right number of cards.
+    ///
+    /// Early in a deal, there are astronomically many such hands; see
+    /// `sample_consistent`. Assumes the player is active.
+    pub fn consistent_opponent_hands(
+        &self,
+        belief: &HandBelief,
+    ) -> impl Iterator<Item = Vec<BasicCard>> {
+        let (owned, candidates, k) = self.opponent_hand_constraints(belief);
+
+        // itertools 0.6's `combinations(0)` yields one empty combination,
+        // except over an empty source, where it yields nothing. A fully
+        // known hand leaves no candidates, so yield it directly; `k = 1`
+        // over no candidates then adds nothing.
+        let (known, candidates, k) = match k {
+            Some(0) => (Some(owned.clone()), Vec::new(), 1),
+            Some(k) => (None, candidates, k),
+            None => (None, Vec::new(), 1),
+        };
+
+        known
+            .into_iter()
+            .chain(candidates.into_iter().combinations(k).map(move |mut hand| {
+                hand.extend(owned.iter().cloned());
+                hand
+            }))
+    }
+
+    /// Sample `n` opponent hands uniformly from those enumerated by
+    /// `consistent_opponent_hands`, with replacement. Returns no hands
+    /// if none are consistent.
+    pub fn sample_consistent<R: Rng>(
+        &self,
+        belief: &HandBelief,
+        n: usize,
+        rng: &mut R,
+    ) -> Vec<Vec<BasicCard>> {
+        let (owned, mut candidates, k) = self.opponent_hand_constraints(belief);
+        let k = match k {
+            Some(k) => k,
+            None => return Vec::new(),
+        };
+
+        (0..n)
+            .map(|_| {
+                rng.shuffle(&mut candidates);
+                let mut hand = candidates[..k].to_vec();
+                hand.extend(owned.iter().cloned());
+                hand
+            })
+            .collect()
+    }
+
     /// Return the set of cards playable in the current state.
     ///
-    /// Assumes the player is active.
+    /// Assumes the player is active. This is empty only if the hand is,
+    /// which the engine never allows before the game is over.
     pub fn playable_cards(&self) -> Vec<BasicCard> {
         match self.leading_card {
             // Second player must follow suit, if possible.
@@ -190,6 +438,30 @@ impl<'a> PlayerView<'a> {
         self.hand.iter().any(|card| card.suit == *s)
     }
 
+    /// Return whether the revealed card is a trump, or `None` in the
+    /// scoring phase.
+    pub fn revealed_is_trump(&self) -> Option<bool> {
+        self.revealed.map(|c| c.suit == self.trump)
+    }
+
+    /// Return whether the revealed card is worth trying to win: any
+    /// trump, or a non-trump of rank `CONTEST_MIN_RANK` or higher.
+    /// Returns `None` in the scoring phase.
+    pub fn should_contest_revealed(&self) -> Option<bool> {
+        self.contest_revealed_above(Some(CONTEST_MIN_RANK))
+    }
+
+    /// Like `should_contest_revealed`, with a custom threshold for
+    /// non-trumps. With no threshold, only trumps are worth winning.
+    pub fn contest_revealed_above(&self, min_rank: Option<Rank>) -> Option<bool> {
+        self.revealed.map(|c| {
+            c.suit == self.trump
+                || min_rank
+                    .map(|r| c.rank.ord_ace_high() >= r.ord_ace_high())
+                    .unwrap_or(false)
+        })
+    }
+
     pub fn ord_suit(&self, s: Suit) -> u8 {
         s.ord() + if s == self.trump { 4 } else { 0 }
     }
@@ -198,14 +470,364 @@ impl<'a> PlayerView<'a> {
     ///
     /// When cards are equivalent, return false.
     pub fn wins_against(&self, leading: &BasicCard, follow: &BasicCard) -> bool {
-        if follow.suit == leading.suit {
-            follow.rank.ord_ace_high() > leading.rank.ord_ace_high()
-        } else {
-            follow.suit == self.trump
-        }
+        trick_winner(leading, follow, self.trump) != *leading
     }
 
     pub fn iter(&self) -> slice::Iter<BasicCard> {
         self.hand.iter()
     }
+
+    /// Return the number of cards held in the suit.
+    pub fn suit_count(&self, s: Suit) -> usize {
+        self.count_where(|c| c.suit == s)
+    }
+
+    /// Return the number of cards held satisfying the predicate.
+    pub fn count_where<F: Fn(&BasicCard) -> bool>(&self, pred: F) -> usize {
+        self.hand.iter().filter(|c| pred(c)).count()
+    }
+
+    /// Return the highest card (ace high) held in the suit, if any.
+    pub fn highest_card_of_suit(&self, s: Suit) -> Option<BasicCard> {
+        self.hand
+            .iter()
+            .filter(|c| c.suit == s)
+            .max_by_key(|c| c.rank.ord_ace_high())
+            .cloned()
+    }
+
+    /// Rough worth of holding the card for the scoring phase, as a
+    /// chance of it winning a trick: non-trumps range from 0.0 (two)
+    /// to 0.5 (ace), and trumps from 0.5 to 1.0.
+    fn card_strength(&self, c: &BasicCard) -> f32 {
+        let rank = f32::from(c.rank.ord_ace_high()) / 24.0;
+        rank + if c.suit == self.trump { 0.5 } else { 0.0 }
+    }
+
+    /// Return how much more winning the current trick is worth than
+    /// losing it, in points.
+    ///
+    /// In the scoring phase, the winner takes a point. In the
+    /// hand-building phase, the winner takes the revealed card, but
+    /// the loser then draws the hidden card below it, so winning is
+    /// only worth the revealed card's strength beyond that of an
+    /// average card. This is negative when the revealed card is weaker
+    /// than average. Strength is the chance of a card winning a trick
+    /// in the scoring phase, see `trick_is_worth_contesting`; scoring
+    /// rules that give points in the hand-building phase are ignored.
+    pub fn trick_value(&self) -> f32 {
+        match self.revealed {
+            Some(ref c) => self.card_strength(c) - MEAN_CARD_STRENGTH,
+            None => 1.0,
+        }
+    }
+
+    /// Return whether the current trick is worth trying to win.
+    ///
+    /// A one-ply heuristic generalizing `contest_revealed_above` to
+    /// both phases: a card is spent either way, so contesting costs
+    /// the strength of the card needed to win beyond that of the
+    /// weakest playable card. When following, that's the cheapest
+    /// card beating the lead, and the trick isn't worth contesting if
+    /// there's none; when leading, it's the strongest card. The trick
+    /// is worth contesting if its `trick_value` exceeds the cost.
+    ///
+    /// Assumes the player is active.
+    pub fn trick_is_worth_contesting(&self) -> bool {
+        let cards = self.playable_cards();
+        let strengths = || cards.iter().map(|c| self.card_strength(c));
+        let weakest = strengths().fold(f32::INFINITY, f32::min);
+
+        let needed = match self.leading_card {
+            Some(ref lc) => cards
+                .iter()
+                .filter(|c| self.wins_against(lc, c))
+                .map(|c| self.card_strength(c))
+                .fold(f32::INFINITY, f32::min),
+            None => strengths().fold(f32::NEG_INFINITY, f32::max),
+        };
+        // no card can win, or there is no card at all
+        if !needed.is_finite() {
+            return false;
+        }
+        self.trick_value() > needed - weakest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GameState, PlayerView};
+    use crate::cards::{parse_hand, BasicCard, Rank, Suit};
+    use crate::germanwhist::{PlayerState, Round, STANDARD_SCORING};
+    use rand::thread_rng;
+
+    #[test]
+    fn test_view_counts() {
+        let hand = parse_hand("A♠ 4♠ K♠ 2♥ T♦ J♦").unwrap();
+        let view = PlayerView {
+            hand: &hand,
+            player: 0,
+            active: 0,
+            revealed: None,
+            leading_card: None,
+            trump: Suit::Diamonds,
+            score: [0, 0],
+            rounds_left: 13,
+            phase_tricks_left: 13,
+        };
+
+        assert_eq!(view.suit_count(Suit::Spades), 3);
+        assert_eq!(view.suit_count(Suit::Clubs), 0);
+        assert_eq!(
+            view.count_where(|c| c.rank.ord_ace_high() > Rank::Jack.ord_ace_high()),
+            2
+        );
+        assert_eq!(
+            view.highest_card_of_suit(Suit::Spades),
+            Some(BasicCard {
+                rank: Rank::Ace,
+                suit: Suit::Spades
+            })
+        );
+        assert_eq!(view.highest_card_of_suit(Suit::Clubs), None);
+    }
+
+    #[test]
+    fn test_should_contest_revealed() {
+        let hand = parse_hand("A♠ 4♠").unwrap();
+        let mut view = PlayerView {
+            hand: &hand,
+            player: 0,
+            active: 0,
+            revealed: None,
+            leading_card: None,
+            trump: Suit::Diamonds,
+            score: [0, 0],
+            rounds_left: 13,
+            phase_tricks_left: 13,
+        };
+        assert_eq!(view.revealed_is_trump(), None);
+        assert_eq!(view.should_contest_revealed(), None);
+
+        view.revealed = Some("2♦".parse().unwrap());
+        assert_eq!(view.revealed_is_trump(), Some(true));
+        assert_eq!(view.should_contest_revealed(), Some(true));
+        assert_eq!(view.contest_revealed_above(None), Some(true));
+
+        view.revealed = Some("J♣".parse().unwrap());
+        assert_eq!(view.revealed_is_trump(), Some(false));
+        assert_eq!(view.should_contest_revealed(), Some(true));
+        assert_eq!(view.contest_revealed_above(Some(Rank::King)), Some(false));
+        assert_eq!(view.contest_revealed_above(None), Some(false));
+
+        view.revealed = Some("T♣".parse().unwrap());
+        assert_eq!(view.should_contest_revealed(), Some(false));
+    }
+
+    #[test]
+    fn test_trick_is_worth_contesting() {
+        let hand = parse_hand("3♦ Q♠ 2♣").unwrap();
+        let mut view = PlayerView {
+            hand: &hand,
+            player: 1,
+            active: 1,
+            revealed: Some("A♦".parse().unwrap()),
+            leading_card: Some("T♠".parse().unwrap()),
+            trump: Suit::Diamonds,
+            score: [0, 0],
+            rounds_left: 13,
+            phase_tricks_left: 13,
+        };
+
+        // must follow with the queen, which costs nothing extra
+        assert!(view.trick_value() > 0.0);
+        assert!(view.trick_is_worth_contesting());
+
+        // a low non-trump is worth less than the hidden card the loser
+        // draws
+        view.revealed = Some("4♣".parse().unwrap());
+        assert!(view.trick_value() < 0.0);
+        assert!(!view.trick_is_worth_contesting());
+
+        // void in the led suit: winning means ruffing, which is worth
+        // a trump for a high trump but not for a non-trump ace
+        view.leading_card = Some("T♥".parse().unwrap());
+        view.revealed = Some("A♣".parse().unwrap());
+        assert!(!view.trick_is_worth_contesting());
+        view.revealed = Some("K♦".parse().unwrap());
+        assert!(view.trick_is_worth_contesting());
+
+        // in the scoring phase, a point is worth any card, but only if
+        // the trick can be won
+        view.revealed = None;
+        assert_eq!(view.trick_value(), 1.0);
+        assert!(view.trick_is_worth_contesting());
+        view.leading_card = Some("K♠".parse().unwrap());
+        assert!(!view.trick_is_worth_contesting());
+        view.leading_card = None;
+        assert!(view.trick_is_worth_contesting());
+    }
+
+    #[test]
+    fn test_opponent_hand_size() {
+        let mut round = Round::new(STANDARD_SCORING);
+        round.start_round(0);
+        while !round.is_game_over() {
+            let gs = round.get_state();
+            for player in 0..2 {
+                assert_eq!(
+                    gs.player_view(player).opponent_hand_size(),
+                    gs.hands[1 - player].len()
+                );
+            }
+            let action = round.possible_actions()[0];
+            round.play_action(action).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_consistent_opponent_hands() {
+        let mut round = Round::new((0, 1));
+        let mut ps = PlayerState::from_events(0, &round.start_round(0)[0]);
+        while round.get_state().rounds_left > 3 || round.active_player() != 0 {
+            let action = round.possible_actions()[0];
+            for ev in &round.play_action(action).unwrap()[0] {
+                ps.on_event(ev);
+            }
+        }
+
+        let view = round.active_player_view();
+        let sorted = |mut h: Vec<BasicCard>| {
+            h.sort_by_key(|c| u8::from(c));
+            h
+        };
+        let actual = sorted(round.get_state().hands[1].clone());
+
+        let hands: Vec<_> = view
+            .consistent_opponent_hands(&ps.oppo)
+            .map(sorted)
+            .collect();
+        assert!(hands.contains(&actual));
+        for h in &hands {
+            assert_eq!(h.len(), view.opponent_hand_size());
+            assert!(h.iter().all(|c| !view.has_card(*c)));
+        }
+
+        for h in view.sample_consistent(&ps.oppo, 10, &mut thread_rng()) {
+            assert!(hands.contains(&sorted(h)));
+        }
+    }
+
+    #[test]
+    fn test_consistent_opponent_hands_fully_known() {
+        let mut round = Round::new((0, 1));
+        let mut ps = PlayerState::from_events(0, &round.start_round(0)[0]);
+        while round.get_state().rounds_left > 3 || round.active_player() != 0 {
+            let action = round.possible_actions()[0];
+            for ev in &round.play_action(action).unwrap()[0] {
+                ps.on_event(ev);
+            }
+        }
+
+        let mut actual = round.get_state().hands[1].clone();
+        actual.sort_by_key(|c| u8::from(c));
+        for c in &actual {
+            ps.oppo.card_drawn(c);
+        }
+
+        let view = round.active_player_view();
+        let hands: Vec<_> = view
+            .consistent_opponent_hands(&ps.oppo)
+            .map(|mut h| {
+                h.sort_by_key(|c| u8::from(c));
+                h
+            })
+            .collect();
+        assert_eq!(hands, vec![actual]);
+    }
+
+    #[test]
+    fn test_points_remaining() {
+        for &(rules, total) in &[(STANDARD_SCORING, 13), ((1, 2), 13 + 26)] {
+            let mut round = Round::new(rules);
+            round.start_round(0);
+            assert_eq!(round.points_remaining(), Some(total));
+
+            while !round.is_game_over() {
+                let gs = round.get_state();
+                let score = gs.score[0] + gs.score[1];
+                assert_eq!(gs.points_remaining(rules) + score, total);
+                assert_eq!(
+                    round.active_player_view().points_remaining(rules),
+                    gs.points_remaining(rules)
+                );
+
+                let action = round.possible_actions()[0];
+                round.play_action(action).unwrap();
+            }
+            assert_eq!(round.points_remaining(), Some(0));
+        }
+
+        let mut round = Round::new(STANDARD_SCORING);
+        round.set_trick_value(Some(Box::new(|_| 2)));
+        round.start_round(0);
+        assert_eq!(round.points_remaining(), None);
+    }
+
+    #[test]
+    fn test_deal_transcript() {
+        let gs = GameState::from_seed(1, &[1, 2, 3]);
+        let (deal, revealed) = gs.deal_transcript();
+        assert_eq!(deal.len(), 26);
+        assert_eq!(Some(revealed), gs.revealed);
+
+        // players alternate, starting with the leader
+        for (i, &(p, _)) in deal.iter().enumerate() {
+            assert_eq!(p, (i + 1) % 2);
+        }
+
+        // replaying the deal reproduces the hands
+        let mut hands = [Vec::new(), Vec::new()];
+        for &(p, c) in &deal {
+            hands[p].push(c);
+        }
+        assert_eq!(hands, gs.hands);
+
+        // the same seed gives the same deal
+        let again = GameState::from_seed(1, &[1, 2, 3]);
+        assert_eq!(again.deal_transcript(), (deal, revealed));
+        assert_eq!(again.deck.cards(), gs.deck.cards());
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut gs = GameState::new(0);
+        assert_eq!(gs.validate(), Ok(()));
+
+        let c = gs.hands[1][3];
+        gs.hands[0].push(c);
+        gs.played = Some(c);
+        gs.revealed = Some(gs.hands[0][0]);
+        assert_eq!(gs.validate(), Err(vec![c, gs.hands[0][0]]));
+    }
+
+    #[test]
+    fn test_position_key() {
+        let mut gs = GameState::new(0);
+        let key = gs.position_key();
+
+        // card order within a hand doesn't matter
+        gs.hands[0].reverse();
+        gs.hands[1].swap(0, 5);
+        assert_eq!(key, gs.position_key());
+
+        gs.active = 1;
+        assert_ne!(key, gs.position_key());
+        gs.active = 0;
+
+        // moving a card between hands changes the key
+        let c = gs.hands[0].pop().unwrap();
+        gs.hands[1].push(c);
+        assert_ne!(key, gs.position_key());
+    }
 }
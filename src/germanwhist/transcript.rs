@@ -0,0 +1,318 @@
+use super::engine::{Action, ActionError, LeadRule, Round};
+use super::state::GameState;
+use crate::cards::{
+    card_mask, parse_hand, BasicDeck, CardParseError, Suit, FULL_DECK_MASK, NUM_BASIC_CARDS,
+};
+use std::error;
+use std::fmt;
+
+/// Reason a transcript couldn't be replayed. Line numbers start at 1.
+#[derive(Debug)]
+pub enum TranscriptError {
+    /// The line isn't a header or a move.
+    Malformed(usize),
+
+    /// A card on the line couldn't be parsed.
+    Card(usize, CardParseError),
+
+    /// The deck doesn't hold each card exactly once.
+    BadDeck(usize),
+
+    /// A header is missing before the first move.
+    MissingHeader(&'static str),
+
+    /// The move on the line isn't legal.
+    IllegalMove(usize, ActionError),
+}
+
+impl fmt::Display for TranscriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TranscriptError::Malformed(line) => write!(f, "line {}: malformed line", line),
+            TranscriptError::Card(line, ref e) => write!(f, "line {}: {}", line, e),
+            TranscriptError::BadDeck(line) => {
+                write!(f, "line {}: deck must hold each card exactly once", line)
+            }
+            TranscriptError::MissingHeader(name) => write!(f, "missing '{}' line", name),
+            TranscriptError::IllegalMove(line, ref e) => {
+                write!(f, "line {}: illegal move: {}", line, e)
+            }
+        }
+    }
+}
+
+impl error::Error for TranscriptError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            TranscriptError::Card(_, ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Headers of a transcript, before any move.
+#[derive(Default)]
+struct Headers {
+    rules: Option<(usize, usize)>,
+    lead_rule: Option<LeadRule>,
+    leader: Option<usize>,
+    trump: Option<Suit>,
+    deck: Option<BasicDeck>,
+}
+
+impl Round {
+    /// Write the current round as a transcript, for use as a
+    /// regression fixture.
+    ///
+    /// The transcript has a header line each for the scoring rules, the
+    /// lead rule, the leading player, the trump suit and the deck (in
+    /// the order the cards are drawn), followed by one line per card
+    /// played, giving the player and the card. Players are numbered
+    /// from 1. Blank lines and lines starting with `#` are ignored when
+    /// reading, and a missing lead rule means `LeadRule::WinnerLeads`.
+    ///
+    /// Only the scoring and lead rules are recorded; other settings of
+    /// the round, such as its phase rules, are not.
+    pub fn to_transcript(&self) -> String {
+        assert!(self.is_started(), "round must be started");
+        let gs = self.get_state();
+        let (deal, _) = gs.deal_transcript();
+        let rules = self.scoring_rules();
+        let deck: Vec<_> = gs
+            .initial_deck()
+            .iter()
+            .rev()
+            .map(|c| c.to_plain_string())
+            .collect();
+
+        let lead_rule = match self.lead_rule() {
+            LeadRule::WinnerLeads => "winner",
+            LeadRule::Alternate => "alternate",
+        };
+
+        let mut out = format!(
+            "rules {} {}\nlead_rule {}\nleader {}\ntrump {}\ndeck {}\n",
+            rules.0,
+            rules.1,
+            lead_rule,
+            deal[0].0 + 1,
+            gs.trump.symbol(),
+            deck.join(" ")
+        );
+        for action in self.history() {
+            out += &format!("{} {}\n", action.player + 1, action.card.to_plain_string());
+        }
+        out
+    }
+
+    /// Replay a transcript written by `to_transcript`, returning the
+    /// round after its last move.
+    pub fn from_transcript(transcript: &str) -> Result<Round, TranscriptError> {
+        let mut headers = Headers::default();
+        let mut round: Option<Round> = None;
+
+        for (i, line) in transcript.lines().enumerate() {
+            let line_no = i + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, rest) = line
+                .split_once(' ')
+                .ok_or(TranscriptError::Malformed(line_no))?;
+            let rest = rest.trim();
+
+            // a move, once every header is known
+            if let Ok(player) = key.parse::<usize>() {
+                if round.is_none() {
+                    round = Some(headers.start()?);
+                }
+                let round = round.as_mut().expect("round was just started");
+                if player == 0 || player > round.num_players() {
+                    return Err(TranscriptError::Malformed(line_no));
+                }
+                let card = rest
+                    .parse()
+                    .map_err(|e| TranscriptError::Card(line_no, e))?;
+                round
+                    .play_action(Action {
+                        player: player - 1,
+                        card,
+                    })
+                    .map_err(|e| TranscriptError::IllegalMove(line_no, e))?;
+                continue;
+            }
+
+            if round.is_some() {
+                return Err(TranscriptError::Malformed(line_no));
+            }
+            let malformed = || TranscriptError::Malformed(line_no);
+            match key {
+                "rules" => {
+                    let parts: Vec<usize> = rest
+                        .split_whitespace()
+                        .map(|p| p.parse().map_err(|_| malformed()))
+                        .collect::<Result<_, _>>()?;
+                    match parts[..] {
+                        [building, scoring] => headers.rules = Some((building, scoring)),
+                        _ => return Err(malformed()),
+                    }
+                }
+                "lead_rule" => {
+                    headers.lead_rule = Some(match rest {
+                        "winner" => LeadRule::WinnerLeads,
+                        "alternate" => LeadRule::Alternate,
+                        _ => return Err(malformed()),
+                    })
+                }
+                "leader" => match rest.parse::<usize>() {
+                    Ok(p @ 1..=2) => headers.leader = Some(p - 1),
+                    _ => return Err(malformed()),
+                },
+                "trump" => {
+                    headers.trump = Some(
+                        rest.parse()
+                            .map_err(|e| TranscriptError::Card(line_no, e))?,
+                    )
+                }
+                "deck" => {
+                    let mut cards =
+                        parse_hand(rest).map_err(|e| TranscriptError::Card(line_no, e))?;
+                    let deck_mask = card_mask(&cards);
+                    if cards.len() != NUM_BASIC_CARDS || deck_mask != FULL_DECK_MASK {
+                        return Err(TranscriptError::BadDeck(line_no));
+                    }
+                    cards.reverse();
+                    headers.deck = Some(BasicDeck::from_cards(cards));
+                }
+                _ => return Err(malformed()),
+            }
+        }
+
+        match round {
+            Some(round) => Ok(round),
+            None => headers.start(),
+        }
+    }
+}
+
+impl Headers {
+    /// Start a round with the headers read so far.
+    fn start(&mut self) -> Result<Round, TranscriptError> {
+        let rules = self.rules.ok_or(TranscriptError::MissingHeader("rules"))?;
+        let leader = self
+            .leader
+            .ok_or(TranscriptError::MissingHeader("leader"))?;
+        let trump = self.trump.ok_or(TranscriptError::MissingHeader("trump"))?;
+        let deck = self
+            .deck
+            .take()
+            .ok_or(TranscriptError::MissingHeader("deck"))?;
+
+        let mut state = GameState::from_deck(leader, deck);
+        state.trump = trump;
+        let mut round = Round::new(rules);
+        round.set_lead_rule(self.lead_rule.unwrap_or_default());
+        round.start_with_state(state);
+        Ok(round)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TranscriptError;
+    use crate::cards::Suit;
+    use crate::germanwhist::{ActionError, LeadRule, Round, TrumpRule};
+
+    #[test]
+    fn test_round_trip() {
+        let mut round = Round::new((1, 2));
+        round.set_trump_rule(TrumpRule::Fixed(Suit::Clubs));
+        round.start_round(None);
+        for _ in 0..30 {
+            let action = round.possible_actions()[0];
+            round.play_action(action).unwrap();
+        }
+
+        let transcript = round.to_transcript();
+        assert_eq!(transcript.lines().count(), 5 + 30);
+        assert!(transcript.contains("\ntrump ♣\n"));
+
+        let replayed = Round::from_transcript(&transcript).unwrap();
+        assert_eq!(replayed.to_transcript(), transcript);
+        assert_eq!(replayed.get_state().score, round.get_state().score);
+        assert_eq!(replayed.get_state().hands, round.get_state().hands);
+        assert_eq!(replayed.active_player(), round.active_player());
+
+        // comments and blank lines are ignored, and moves can continue
+        let annotated = format!("# fixture\n\n{}", transcript);
+        let mut replayed = Round::from_transcript(&annotated).unwrap();
+        while let Some(action) = replayed.possible_actions().first().cloned() {
+            replayed.play_action(action).unwrap();
+        }
+        assert!(replayed.is_game_over());
+    }
+
+    #[test]
+    fn test_lead_rule() {
+        let mut round = Round::new((0, 1));
+        round.set_lead_rule(LeadRule::Alternate);
+        round.start_round(0);
+        while !round.is_game_over() {
+            let action = round.possible_actions()[0];
+            round.play_action(action).unwrap();
+        }
+
+        let transcript = round.to_transcript();
+        assert!(transcript.contains("\nlead_rule alternate\n"));
+        let replayed = Round::from_transcript(&transcript).unwrap();
+        assert_eq!(replayed.lead_rule(), LeadRule::Alternate);
+        assert_eq!(replayed.get_state().score, round.get_state().score);
+
+        // transcripts without the header default to the winner leading
+        let mut round = Round::new((0, 1));
+        round.start_round(0);
+        for _ in 0..30 {
+            let action = round.possible_actions()[0];
+            round.play_action(action).unwrap();
+        }
+        let old = round.to_transcript().replace("lead_rule winner\n", "");
+        let replayed = Round::from_transcript(&old).unwrap();
+        assert_eq!(replayed.lead_rule(), LeadRule::WinnerLeads);
+        assert_eq!(replayed.get_state().score, round.get_state().score);
+    }
+
+    #[test]
+    fn test_illegal_move() {
+        let mut round = Round::new((0, 1));
+        round.start_round(0);
+        let action = round.possible_actions()[0];
+        round.play_action(action).unwrap();
+        let transcript = round.to_transcript();
+
+        // the leader plays a second card in a row
+        let repeated = transcript.lines().last().unwrap();
+        let bad = format!("{}{}\n", transcript, repeated);
+        match Round::from_transcript(&bad) {
+            Err(TranscriptError::IllegalMove(7, ActionError::WrongPlayer(1))) => (),
+            other => panic!("unexpected result: {:?}", other.err()),
+        }
+
+        let missing = transcript.replace("leader 1\n", "");
+        assert!(matches!(
+            Round::from_transcript(&missing),
+            Err(TranscriptError::MissingHeader("leader"))
+        ));
+
+        let short_deck = transcript.replace("deck ", "deck 2♣ ");
+        assert!(matches!(
+            Round::from_transcript(&short_deck),
+            Err(TranscriptError::BadDeck(5))
+        ));
+
+        let err = Round::from_transcript("rules 0 1\nhello world\n")
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "line 2: malformed line");
+    }
+}
@@ -1,6 +1,10 @@
 use super::engine::{Action, Round};
-use super::state::GameState;
-use crate::cards::BasicCard;
+use super::state::{GameState, PlayerView};
+use crate::cards::{BasicCard, Suit};
+use crate::hand_belief::{count_distribution, HandBelief};
+use rand::Rng;
+use std::cmp::Reverse;
+use std::fmt;
 
 pub fn format_hand(hand: &[BasicCard], gs: &GameState) -> String {
     let mut cards: Vec<_> = hand.iter().collect();
@@ -30,5 +34,548 @@ pub fn format_round(round: &Round) -> String {
 }
 
 pub fn format_action(action: &Action) -> String {
-    format!("Player {} plays {}.", action.player + 1, action.card)
+    action.to_string()
+}
+
+/// Return the cards in the player's hand that would win the trick if
+/// led, whatever the opponent holds.
+///
+/// A card is a certain winner if the opponent can't hold a higher card
+/// of the same suit, and either the card is trump, the opponent can't
+/// hold a trump, or the opponent definitely holds a card of the suit
+/// and so must follow.
+pub fn certain_winners(view: &PlayerView, belief: &HandBelief) -> Vec<BasicCard> {
+    let possible: Vec<_> = BasicCard::all()
+        .into_iter()
+        .filter(|c| belief.p(c) > 0.0)
+        .collect();
+
+    view.iter()
+        .filter(|card| {
+            let beaten_in_suit = possible
+                .iter()
+                .any(|o| o.suit == card.suit && o.rank.ord_ace_high() > card.rank.ord_ace_high());
+            if beaten_in_suit {
+                return false;
+            }
+
+            card.suit == view.trump
+                || !possible.iter().any(|o| o.suit == view.trump)
+                || possible
+                    .iter()
+                    .any(|o| o.suit == card.suit && belief.p(o) >= 1.0)
+        })
+        .cloned()
+        .collect()
+}
+
+/// Estimate, for each suit other than the player's longest, the
+/// probability that the opponent is forced to give up a card of that
+/// suit if the player leads every card of the long suit in turn.
+///
+/// Each lead costs the opponent one card, whether they follow, ruff
+/// or discard. They can protect a suit only while they hold other
+/// cards to play, so they must play from suit `s` iff the number of
+/// leads exceeds the cards they hold outside `s`. The count of `s`
+/// cards held is modelled as a sum of independent draws with the
+/// belief's marginal probabilities, ignoring the constraint on the
+/// total, and whether the long suit's leads actually win is ignored.
+///
+/// Returns an empty list if the player has no cards.
+pub fn forced_discards(view: &PlayerView, belief: &HandBelief) -> Vec<(Suit, f32)> {
+    let long_suit = match Suit::iterator().max_by_key(|&&s| (view.suit_count(s), s != view.trump)) {
+        Some(&s) if view.suit_count(s) > 0 => s,
+        _ => return Vec::new(),
+    };
+
+    let opp_size = view.opponent_hand_size();
+    let leads = view.suit_count(long_suit).min(opp_size);
+
+    Suit::iterator()
+        .filter(|&&s| s != long_suit)
+        .map(|&s| {
+            let dist = count_distribution(
+                BasicCard::all()
+                    .iter()
+                    .filter(|c| c.suit == s)
+                    .map(|c| belief.p(c)),
+                usize::MAX,
+            );
+
+            // forced iff held + leads > opponent's hand size
+            let p_forced: f32 = dist
+                .iter()
+                .enumerate()
+                .filter(|&(held, _)| held > 0 && held + leads > opp_size)
+                .map(|(_, p)| *p as f32)
+                .sum();
+            (s, p_forced.min(1.0))
+        })
+        .collect()
+}
+
+/// Return the probability that the opponent can beat `my_lead`.
+///
+/// The opponent beats the lead with a higher card of the suit, or, if
+/// void in the suit, with any trump. Holding each card is treated as
+/// independent, with the belief's marginal probability.
+pub fn trick_win_probability(view: &PlayerView, my_lead: &BasicCard, belief: &HandBelief) -> f32 {
+    let p_none = |pred: &dyn Fn(&BasicCard) -> bool| -> f32 {
+        BasicCard::all()
+            .iter()
+            .filter(|c| pred(c))
+            .map(|c| 1.0 - belief.p(c).clamp(0.0, 1.0))
+            .product()
+    };
+
+    let suit = my_lead.suit;
+    let p_higher = 1.0 - p_none(&|c| c.suit == suit && view.wins_against(my_lead, c));
+    let p_trump = if suit == view.trump {
+        0.0
+    } else {
+        1.0 - p_none(&|c| c.suit == view.trump)
+    };
+    let p_void = p_none(&|c| c.suit == suit);
+
+    (p_higher + p_void * p_trump).clamp(0.0, 1.0)
+}
+
+/// Probability that `rollout_policy` ignores its heuristics and plays
+/// a uniformly random card.
+const ROLLOUT_EXPLORATION: f32 = 0.2;
+
+/// Fast, randomized default policy for Monte Carlo rollouts.
+///
+/// Wins the trick as cheaply as possible when it's worth winning (any
+/// trick in the scoring phase, or a trump or face-card prize in the
+/// hand-building phase), and otherwise ditches the lowest non-trump.
+/// One move in five (`ROLLOUT_EXPLORATION`) plays a uniformly random
+/// card instead. Always returns one of `view.playable_cards()`.
+pub fn rollout_policy<R: Rng>(view: &PlayerView, rng: &mut R) -> BasicCard {
+    let mut cards = view.playable_cards();
+    assert!(
+        !cards.is_empty(),
+        "player {} has no cards mid-trick",
+        view.player + 1
+    );
+
+    if rng.gen::<f32>() < ROLLOUT_EXPLORATION {
+        return *rng.choose(&cards).expect("cards is non-empty");
+    }
+
+    // lowest cards first, trumps after non-trumps of the same rank
+    cards.sort_by_key(|c| (c.rank.ord_ace_high(), view.ord_suit(c.suit)));
+    let lowest_non_trump = *cards
+        .iter()
+        .find(|c| c.suit != view.trump)
+        .unwrap_or(&cards[0]);
+
+    let worth_winning = view.should_contest_revealed().unwrap_or(true);
+
+    match view.leading_card {
+        Some(ref lc) => {
+            let cheapest_winner = cards
+                .iter()
+                .filter(|c| view.wins_against(lc, c))
+                .min_by_key(|c| (c.suit == view.trump, c.rank.ord_ace_high()));
+            match cheapest_winner {
+                Some(c) if worth_winning => *c,
+                _ => lowest_non_trump,
+            }
+        }
+        None => {
+            if worth_winning {
+                // lead the strongest non-trump, falling back to a trump
+                *cards
+                    .iter()
+                    .rev()
+                    .find(|c| c.suit != view.trump)
+                    .unwrap_or(&cards[cards.len() - 1])
+            } else {
+                lowest_non_trump
+            }
+        }
+    }
+}
+
+/// Sort candidate actions so that the likely-best moves come first,
+/// for better pruning in game-tree search.
+///
+/// When following, cheap winning cards come first and losing cards
+/// last, unless the trick isn't worth winning, in which case the cheap
+/// ditches come first. When leading, trumps come first, then the
+/// remaining cards from high to low. Only the order of `actions` is
+/// changed.
+pub fn order_moves_for_search(view: &PlayerView, actions: &mut [Action]) {
+    let worth_winning = view.should_contest_revealed().unwrap_or(true);
+
+    match view.leading_card {
+        Some(ref lc) => actions.sort_by_key(|a| {
+            let wins = view.wins_against(lc, &a.card);
+            let cost = (a.card.suit == view.trump, a.card.rank.ord_ace_high());
+            (wins != worth_winning, cost)
+        }),
+        None => actions.sort_by_key(|a| {
+            (
+                a.card.suit != view.trump,
+                Reverse(a.card.rank.ord_ace_high()),
+            )
+        }),
+    }
+}
+
+/// Why one card of a trick beats the other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrickReason {
+    /// Exactly one of the cards is a trump, and it wins.
+    TrumpBeatsNonTrump,
+
+    /// Both cards are of the same suit, so the higher rank wins.
+    HigherOfLedSuit,
+
+    /// Neither card is a trump and the follower didn't follow suit, so
+    /// the leading card wins whatever its rank.
+    OffSuitCannotWin,
+}
+
+impl TrickReason {
+    /// Return the rationale in words, e.g. "trump beats non-trump".
+    pub fn description(&self) -> &'static str {
+        match *self {
+            TrickReason::TrumpBeatsNonTrump => "trump beats non-trump",
+            TrickReason::HigherOfLedSuit => "higher card of led suit",
+            TrickReason::OffSuitCannotWin => "off-suit cannot win",
+        }
+    }
+}
+
+impl fmt::Display for TrickReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.description())
+    }
+}
+
+/// Outcome of a two-card trick, as returned by `explain_trick`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrickExplanation {
+    /// whether the leading card wins
+    pub leader_wins: bool,
+    pub winner: BasicCard,
+    pub loser: BasicCard,
+    pub reason: TrickReason,
+}
+
+impl fmt::Display for TrickExplanation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} beats {}: {}", self.winner, self.loser, self.reason)
+    }
+}
+
+/// Return the card that wins a trick of two cards with the given
+/// trump: the higher card of the led suit, unless only the following
+/// card is a trump. A card can't beat itself, so identical cards
+/// return the leading card.
+///
+/// Every other rule for deciding a trick goes through this.
+pub fn trick_winner(lead: &BasicCard, follow: &BasicCard, trump: Suit) -> BasicCard {
+    let follow_wins = if follow.suit == lead.suit {
+        follow.rank.ord_ace_high() > lead.rank.ord_ace_high()
+    } else {
+        follow.suit == trump
+    };
+    if follow_wins {
+        *follow
+    } else {
+        *lead
+    }
+}
+
+/// Decide who wins a trick of two cards with the given trump, and why.
+///
+/// Agrees with `trick_winner`. Panics if the cards are the same.
+pub fn explain_trick(leading: &BasicCard, following: &BasicCard, trump: Suit) -> TrickExplanation {
+    assert!(leading != following, "a trick needs two different cards");
+
+    let leader_wins = trick_winner(leading, following, trump) == *leading;
+    let reason = if leading.suit == following.suit {
+        TrickReason::HigherOfLedSuit
+    } else if leading.suit == trump || following.suit == trump {
+        TrickReason::TrumpBeatsNonTrump
+    } else {
+        TrickReason::OffSuitCannotWin
+    };
+
+    let (winner, loser) = if leader_wins {
+        (*leading, *following)
+    } else {
+        (*following, *leading)
+    };
+    TrickExplanation {
+        leader_wins,
+        winner,
+        loser,
+        reason,
+    }
+}
+
+/// Distribution of a hand's cards across the suits, as returned by
+/// `hand_shape`. Hands of the same shape hash equal, for bucketing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HandShape {
+    /// number of cards in each suit, longest first
+    pub lengths: [usize; 4],
+
+    /// number of trumps
+    pub trumps: usize,
+}
+
+/// Shown as e.g. "5-4-3-1 (5 trumps)".
+impl fmt::Display for HandShape {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let [a, b, c, d] = self.lengths;
+        write!(f, "{}-{}-{}-{} ({} trumps)", a, b, c, d, self.trumps)
+    }
+}
+
+/// Return the shape of the hand: its sorted suit lengths, and how many
+/// of its cards are trumps.
+pub fn hand_shape(hand: &[BasicCard], trump: Suit) -> HandShape {
+    let mut lengths = [0; 4];
+    for (len, s) in lengths.iter_mut().zip(Suit::iterator()) {
+        *len = hand.iter().filter(|c| c.suit == *s).count();
+    }
+    lengths.sort_by_key(|&n| Reverse(n));
+
+    HandShape {
+        lengths,
+        trumps: hand.iter().filter(|c| c.suit == trump).count(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cards::{parse_hand, Suit};
+    use rand::thread_rng;
+
+    #[test]
+    fn test_trick_winner() {
+        let card = |s: &str| s.parse::<BasicCard>().unwrap();
+        let cases = [
+            ("K♠", "A♠", Suit::Hearts, "A♠"),
+            ("A♠", "2♥", Suit::Hearts, "2♥"),
+            ("2♥", "A♠", Suit::Hearts, "2♥"),
+            ("2♣", "A♠", Suit::Hearts, "2♣"),
+            ("Q♦", "Q♦", Suit::Hearts, "Q♦"),
+        ];
+        for &(lead, follow, trump, winner) in &cases {
+            assert_eq!(
+                trick_winner(&card(lead), &card(follow), trump),
+                card(winner)
+            );
+        }
+    }
+
+    #[test]
+    fn test_explain_trick() {
+        let card = |s: &str| s.parse::<BasicCard>().unwrap();
+
+        let e = explain_trick(&card("K♠"), &card("A♠"), Suit::Hearts);
+        assert!(!e.leader_wins);
+        assert_eq!(e.reason, TrickReason::HigherOfLedSuit);
+        assert_eq!(
+            e.to_string(),
+            format!(
+                "{} beats {}: higher card of led suit",
+                card("A♠"),
+                card("K♠")
+            )
+        );
+
+        let e = explain_trick(&card("A♠"), &card("2♥"), Suit::Hearts);
+        assert_eq!(
+            (e.leader_wins, e.reason),
+            (false, TrickReason::TrumpBeatsNonTrump)
+        );
+        let e = explain_trick(&card("2♥"), &card("A♠"), Suit::Hearts);
+        assert_eq!(
+            (e.leader_wins, e.reason),
+            (true, TrickReason::TrumpBeatsNonTrump)
+        );
+        let e = explain_trick(&card("2♣"), &card("A♠"), Suit::Hearts);
+        assert_eq!(
+            (e.leader_wins, e.reason),
+            (true, TrickReason::OffSuitCannotWin)
+        );
+        assert_eq!(e.winner, card("2♣"));
+
+        // every pair agrees with the engine
+        let mut gs = GameState::new(0);
+        for &trump in Suit::iterator() {
+            gs.trump = trump;
+            for lead in BasicCard::all() {
+                for follow in BasicCard::all() {
+                    if lead != follow {
+                        let e = explain_trick(&lead, &follow, trump);
+                        assert_eq!(Some(e.leader_wins), gs.score_hand(&lead, &follow));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_hand_shape() {
+        let balanced = parse_hand("A♠ K♠ Q♠ J♠ A♥ K♥ Q♥ A♦ K♦ Q♦ A♣ K♣ Q♣").unwrap();
+        let shape = hand_shape(&balanced, Suit::Hearts);
+        assert_eq!(shape.lengths, [4, 3, 3, 3]);
+        assert_eq!(shape.trumps, 3);
+        assert_eq!(hand_shape(&balanced, Suit::Spades).trumps, 4);
+
+        let hand = parse_hand("A♠ K♠ Q♠ J♠ T♠ 2♥ 3♥ 4♥ 5♥ 2♦ 3♦ 4♦ 2♣").unwrap();
+        let shape = hand_shape(&hand, Suit::Spades);
+        assert_eq!(shape.to_string(), "5-4-3-1 (5 trumps)");
+
+        // the same shape, whichever suits hold the cards
+        let permuted = parse_hand("A♣ K♣ Q♣ J♣ T♣ 2♦ 3♦ 4♦ 5♦ 2♥ 3♥ 4♥ 2♠").unwrap();
+        assert_eq!(hand_shape(&permuted, Suit::Clubs), shape);
+        assert_ne!(hand_shape(&permuted, Suit::Spades), shape);
+
+        assert_eq!(hand_shape(&[], Suit::Spades).lengths, [0; 4]);
+    }
+
+    #[test]
+    fn test_certain_winners() {
+        let mut gs = GameState::new(0);
+        gs.hands[0] = parse_hand("A♠ K♠ Q♥ 2♣ 4♦").unwrap();
+        gs.trump = Suit::Hearts;
+
+        // the opponent is known to hold exactly these cards
+        let mut belief = HandBelief::new();
+        for c in parse_hand("J♥ 3♠ 5♦").unwrap() {
+            belief.card_drawn(&c);
+        }
+
+        let winners = certain_winners(&gs.player_view(0), &belief);
+        assert_eq!(winners, parse_hand("A♠ K♠ Q♥").unwrap());
+    }
+
+    #[test]
+    fn test_trick_win_probability() {
+        let mut gs = GameState::new(0);
+        gs.hands[0] = parse_hand("A♠ 9♠ Q♥ 2♣").unwrap();
+        gs.trump = Suit::Hearts;
+        let view = gs.player_view(0);
+        let card = |s: &str| s.parse::<BasicCard>().unwrap();
+
+        // the opponent is known to hold exactly these cards
+        let mut belief = HandBelief::new();
+        for c in parse_hand("K♠ 3♥ 5♦").unwrap() {
+            belief.card_drawn(&c);
+        }
+        assert_eq!(trick_win_probability(&view, &card("A♠"), &belief), 0.0);
+        assert_eq!(trick_win_probability(&view, &card("9♠"), &belief), 1.0);
+        assert_eq!(trick_win_probability(&view, &card("Q♥"), &belief), 0.0);
+        // void in clubs, so the trump wins
+        assert_eq!(trick_win_probability(&view, &card("2♣"), &belief), 1.0);
+
+        let mut belief = HandBelief::new();
+        belief.random_cards_drawn(4);
+        let p_low = trick_win_probability(&view, &card("2♣"), &belief);
+        let p_high = trick_win_probability(&view, &card("A♠"), &belief);
+        assert!(p_high > 0.0 && p_high < p_low && p_low < 1.0);
+    }
+
+    #[test]
+    fn test_forced_discards() {
+        let mut gs = GameState::new(0);
+        gs.hands[0] = parse_hand("A♠ K♠ Q♠ J♠ 2♥").unwrap();
+        gs.trump = Suit::Hearts;
+
+        // the opponent is known to hold exactly these cards
+        let mut belief = HandBelief::new();
+        for c in parse_hand("3♠ 7♥ 8♥ 2♦ 3♦").unwrap() {
+            belief.card_drawn(&c);
+        }
+
+        // four spade leads leave only one card outside hearts or diamonds
+        let forced = forced_discards(&gs.player_view(0), &belief);
+        assert_eq!(
+            forced,
+            vec![
+                (Suit::Clubs, 0.0),
+                (Suit::Diamonds, 1.0),
+                (Suit::Hearts, 1.0)
+            ]
+        );
+
+        // with an unknown hand, the estimates are probabilities, and
+        // fewer leads force fewer discards
+        let mut belief = HandBelief::new();
+        belief.random_cards_drawn(5);
+        let long = forced_discards(&gs.player_view(0), &belief);
+        gs.hands[0] = parse_hand("A♠ K♠ 2♥ 3♦ 4♣").unwrap();
+        let short = forced_discards(&gs.player_view(0), &belief);
+        for (&(_, pl), &(_, ps)) in long.iter().zip(&short) {
+            assert!(pl > 0.0 && pl < 1.0);
+            assert!(ps < pl);
+        }
+
+        gs.hands[0].clear();
+        assert!(forced_discards(&gs.player_view(0), &belief).is_empty());
+    }
+
+    #[test]
+    fn test_order_moves_for_search() {
+        let mut rng = thread_rng();
+        let mut round = Round::new((0, 1));
+        round.start_round(None);
+        while !round.is_game_over() {
+            let view = round.active_player_view();
+            let mut actions = round.possible_actions();
+            order_moves_for_search(&view, &mut actions);
+
+            let sorted_cards = |actions: &[Action]| {
+                let mut cards: Vec<_> = actions.iter().map(|a| (a.player, a.card)).collect();
+                cards.sort_by_key(|&(_, c)| u8::from(c));
+                cards
+            };
+            assert_eq!(
+                sorted_cards(&actions),
+                sorted_cards(&round.possible_actions())
+            );
+
+            // a cheap winning card comes before any losing card
+            if let Some(ref lc) = view.leading_card {
+                if view.should_contest_revealed().unwrap_or(true) {
+                    let first_loser = actions.iter().position(|a| !view.wins_against(lc, &a.card));
+                    let last_winner = actions.iter().rposition(|a| view.wins_against(lc, &a.card));
+                    if let (Some(l), Some(w)) = (first_loser, last_winner) {
+                        assert!(w < l);
+                    }
+                }
+            }
+
+            let card = rollout_policy(&view, &mut rng);
+            let player = view.player;
+            round.play_action(Action { player, card }).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_rollout_policy_is_legal() {
+        let mut rng = thread_rng();
+        for _ in 0..20 {
+            let mut round = Round::new((0, 1));
+            round.start_round(None);
+            while !round.is_game_over() {
+                let view = round.active_player_view();
+                let card = rollout_policy(&view, &mut rng);
+                assert!(view.playable_cards().contains(&card));
+
+                let player = view.player;
+                round.play_action(Action { player, card }).unwrap();
+            }
+        }
+    }
 }
@@ -5,11 +5,13 @@ use ndarray::DataMut;
 use std::cmp;
 /// modules for storing player and opponent model
 use std::collections::HashMap;
+use std::error;
 use std::fmt;
 
 /// A `CardState` is an internal tracker for the likelihood of a
 /// specific card.
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 enum CardState {
     /// The card has been played, or the player otherwise definitely
     /// doesn't have the card.
@@ -67,6 +69,49 @@ impl fmt::Display for CardState {
     }
 }
 
+/// Tolerance when comparing the expected number of cards held.
+const CARD_COUNT_EPS: f32 = 1e-3;
+
+/// Reason two `HandBelief`s can't describe the same hand.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BeliefInconsistency {
+    /// One belief has the player holding the card, while the other
+    /// rules it out.
+    Card(BasicCard),
+
+    /// The beliefs expect the player to hold different numbers of
+    /// cards.
+    CardCount(f32, f32),
+
+    /// No uncertain card is possible in both beliefs, but the player
+    /// holds more cards than are known.
+    NoCandidates,
+}
+
+impl fmt::Display for BeliefInconsistency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BeliefInconsistency::Card(c) => write!(f, "beliefs disagree about {}", c),
+            BeliefInconsistency::CardCount(a, b) => {
+                write!(f, "beliefs expect {:.1} and {:.1} cards", a, b)
+            }
+            BeliefInconsistency::NoCandidates => {
+                write!(f, "no card is possible in both beliefs")
+            }
+        }
+    }
+}
+
+impl error::Error for BeliefInconsistency {}
+
+/// Game-specific model of how a player chooses between cards.
+///
+/// Given the card played and one the player could have played instead,
+/// returns the factor by which to scale the probability that the
+/// player holds the alternative. A factor below 1 means a player
+/// holding the alternative would likely have played it.
+pub type PlayChoiceModel = fn(played: &BasicCard, alternative: &BasicCard) -> f32;
+
 /// A `HandBelief` object represents the best estimate of the
 /// probabilities of holding a specific card.
 ///
@@ -78,8 +123,38 @@ impl fmt::Display for CardState {
 /// `HandBelief` is game-agnostic, and does not generally try to
 /// model any game-specific knowledge. Estimates are generally
 /// max-entropy in that sense.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct HandBelief {
+    #[cfg_attr(feature = "serde", serde(with = "crate::cards::serde_card_map"))]
     probs: HashMap<BasicCard, CardState>,
+
+    #[cfg_attr(feature = "serde", serde(skip))]
+    choice_model: Option<PlayChoiceModel>,
+
+    /// previous states of changed cards, oldest first, while a
+    /// checkpoint is outstanding
+    #[cfg_attr(feature = "serde", serde(skip))]
+    journal: Vec<(BasicCard, CardState)>,
+
+    #[cfg_attr(feature = "serde", serde(skip))]
+    recording: bool,
+
+    #[cfg_attr(feature = "serde", serde(skip))]
+    recency: Option<Recency>,
+}
+
+/// When each card's probability was last set, by a counter that
+/// advances once per observation.
+#[derive(Default)]
+struct Recency {
+    clock: u32,
+    stamps: HashMap<BasicCard, u32>,
+}
+
+/// Point to roll a `HandBelief` back to, from `HandBelief::checkpoint`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BeliefCheckpoint {
+    journal_len: usize,
 }
 
 impl Default for HandBelief {
@@ -89,7 +164,13 @@ impl Default for HandBelief {
             probs.insert(card, CardState::Void);
         }
 
-        HandBelief { probs }
+        HandBelief {
+            probs,
+            choice_model: None,
+            journal: Vec::new(),
+            recording: false,
+            recency: None,
+        }
     }
 }
 
@@ -100,8 +181,112 @@ impl HandBelief {
 
     /// Reset the entire hand to void.
     pub fn clear(&mut self) {
-        for v in self.probs.values_mut() {
-            *v = CardState::Void;
+        self.tick();
+        self.modify(|_, _| CardState::Void);
+    }
+
+    /// Start recording changes, so that the belief can be rolled back
+    /// to its current state with `restore`.
+    ///
+    /// Checkpoints may be nested, and restoring one invalidates any
+    /// taken after it.
+    pub fn checkpoint(&mut self) -> BeliefCheckpoint {
+        self.recording = true;
+        BeliefCheckpoint {
+            journal_len: self.journal.len(),
+        }
+    }
+
+    /// Undo every change since the checkpoint was taken, in time
+    /// proportional to the number of changes. The checkpoint, and any
+    /// taken before it, stay valid; recording continues until `commit`.
+    pub fn restore(&mut self, cp: BeliefCheckpoint) {
+        assert!(
+            cp.journal_len <= self.journal.len(),
+            "checkpoint was invalidated by an earlier restore or commit"
+        );
+        while self.journal.len() > cp.journal_len {
+            let (card, state) = self.journal.pop().expect("journal is non-empty");
+            self.probs.insert(card, state);
+        }
+    }
+
+    /// Keep every change, stop recording, and invalidate all
+    /// outstanding checkpoints.
+    pub fn commit(&mut self) {
+        self.journal.clear();
+        self.recording = false;
+    }
+
+    /// Start or stop tracking how recently each card's probability was
+    /// set, for `recency`. Off by default; stopping discards the
+    /// history.
+    pub fn set_track_recency(&mut self, enabled: bool) {
+        if enabled != self.recency.is_some() {
+            self.recency = if enabled {
+                Some(Recency::default())
+            } else {
+                None
+            };
+        }
+    }
+
+    /// Return the number of observations since the probability of the
+    /// card was last set, where 0 is the latest observation, however
+    /// many times it set the card. Returns `None` if recency isn't
+    /// tracked, the card isn't uncertain, or its probability hasn't
+    /// been set since tracking started.
+    ///
+    /// Recency is metadata only: it doesn't affect the probabilities,
+    /// and isn't rolled back by `restore`.
+    pub fn recency(&self, card: &BasicCard) -> Option<u32> {
+        let recency = self.recency.as_ref()?;
+        if !self.probs[card].is_prob() {
+            return None;
+        }
+        recency.stamps.get(card).map(|t| recency.clock - t)
+    }
+
+    /// Advance the recency clock. Each public update calls this once,
+    /// before changing any card.
+    fn tick(&mut self) {
+        if let Some(ref mut recency) = self.recency {
+            recency.clock += 1;
+        }
+    }
+
+    /// Set the state of a card, recording the old state if needed.
+    fn set(&mut self, card: BasicCard, state: CardState) {
+        if let Some(ref mut recency) = self.recency {
+            if state.is_prob() {
+                recency.stamps.insert(card, recency.clock);
+            }
+        }
+        let old = self.probs.insert(card, state);
+        if self.recording {
+            self.journal.push((
+                card,
+                old.expect("All basic cards should be in probability map."),
+            ));
+        }
+    }
+
+    /// Replace the state of every card with `f(card, state)`, recording
+    /// the old states that change if needed.
+    fn modify<F: Fn(BasicCard, CardState) -> CardState>(&mut self, f: F) {
+        for (card, v) in self.probs.iter_mut() {
+            let new = f(*card, *v);
+            if new != *v {
+                if self.recording {
+                    self.journal.push((*card, *v));
+                }
+                if let Some(ref mut recency) = self.recency {
+                    if new.is_prob() {
+                        recency.stamps.insert(*card, recency.clock);
+                    }
+                }
+                *v = new;
+            }
         }
     }
 
@@ -118,11 +303,241 @@ impl HandBelief {
             .p()
     }
 
+    /// Return the probability that the player has the card, given
+    /// that they hold exactly `total` cards.
+    ///
+    /// Each uncertain card is treated as an independent draw with its
+    /// marginal probability, and the result is conditioned on the
+    /// number of uncertain cards being `total` less the cards known to
+    /// be held. Falls back to the marginal if `total` is inconsistent
+    /// with the belief.
+    pub fn p_given_count(&self, card: &BasicCard, total: usize) -> f32 {
+        let state = *self
+            .probs
+            .get(card)
+            .expect("All basic cards should be in probability map.");
+        let p_card = match state {
+            CardState::Prob(p) => f64::from(p).clamp(0.0, 1.0),
+            _ => return state.p(),
+        };
+
+        let owned = self
+            .probs
+            .values()
+            .filter(|v| **v == CardState::Owns)
+            .count();
+        let m = match total.checked_sub(owned) {
+            Some(0) => return 0.0,
+            Some(m) => m,
+            None => return state.p(),
+        };
+
+        // distribution of the number held among the other uncertain cards
+        let dist = count_distribution(
+            self.probs
+                .iter()
+                .filter(|&(c, _)| c != card)
+                .filter_map(|(_, v)| match *v {
+                    CardState::Prob(p) => Some(p),
+                    _ => None,
+                }),
+            m,
+        );
+        let p_count = |k: usize| dist.get(k).cloned().unwrap_or(0.0);
+
+        let held = p_card * p_count(m - 1);
+        let p_total = held + (1.0 - p_card) * p_count(m);
+        if p_total > 0.0 {
+            (held / p_total) as f32
+        } else {
+            state.p()
+        }
+    }
+
+    /// Combine two beliefs about the same hand built from independent
+    /// observations.
+    ///
+    /// A card ruled out by either belief is ruled out, and a card
+    /// owned in either is owned. Probabilities of the remaining cards
+    /// are multiplied, then scaled so that the expected number of
+    /// cards is unchanged. A card the scaling would push above 1 is
+    /// capped there, and the others are scaled up to make up the
+    /// difference. Fails if the beliefs contradict each other.
+    /// The play choice model is taken from `self`.
+    pub fn intersect(&self, other: &HandBelief) -> Result<HandBelief, BeliefInconsistency> {
+        use self::CardState::*;
+
+        let (n, m) = (self.num_cards(), other.num_cards());
+        if (n - m).abs() > CARD_COUNT_EPS {
+            return Err(BeliefInconsistency::CardCount(n, m));
+        }
+
+        let mut probs = HashMap::new();
+        for card in BasicCard::all() {
+            let state = match (self.probs[&card], other.probs[&card]) {
+                (Owns, Played) | (Owns, Void) | (Played, Owns) | (Void, Owns) => {
+                    return Err(BeliefInconsistency::Card(card))
+                }
+                (Owns, Prob(p)) | (Prob(p), Owns) if p <= 0.0 => {
+                    return Err(BeliefInconsistency::Card(card))
+                }
+                (Owns, _) | (_, Owns) => Owns,
+                (Played, _) | (_, Played) => Played,
+                (Void, _) | (_, Void) => Void,
+                (Prob(a), Prob(b)) => Prob(a * b),
+            };
+            probs.insert(card, state);
+        }
+
+        // rescale the uncertain cards to account for the rest of the hand
+        let owned = probs.values().filter(|v| **v == Owns).count() as f32;
+        let mut target = n - owned;
+        if target < -CARD_COUNT_EPS {
+            return Err(BeliefInconsistency::CardCount(n, owned));
+        }
+        let mut open: Vec<_> = BasicCard::all()
+            .into_iter()
+            .filter(|c| probs[c].is_prob())
+            .collect();
+        while target > CARD_COUNT_EPS {
+            let total: f32 = open.iter().map(|c| probs[c].p()).sum();
+            if total <= 0.0 {
+                return Err(BeliefInconsistency::NoCandidates);
+            }
+            let scale = target / total;
+            let (full, rest): (Vec<_>, Vec<_>) =
+                open.into_iter().partition(|c| probs[c].p() * scale >= 1.0);
+            if full.is_empty() {
+                for card in &rest {
+                    if let Some(Prob(p)) = probs.get_mut(card) {
+                        *p *= scale;
+                    }
+                }
+                break;
+            }
+
+            // cap these cards, and scale the rest against what's left
+            target -= full.len() as f32;
+            for card in full {
+                probs.insert(card, Prob(1.0));
+            }
+            if target <= CARD_COUNT_EPS {
+                for card in &rest {
+                    probs.insert(*card, Prob(0.0));
+                }
+            }
+            open = rest;
+        }
+
+        Ok(HandBelief {
+            probs,
+            choice_model: self.choice_model,
+            ..HandBelief::default()
+        })
+    }
+
     /// Return the total number of cards held by the player
     pub fn num_cards(&self) -> f32 {
         self.probs.values().map(|v| v.p()).sum()
     }
 
+    /// Return the expected card count, and the number of owned and
+    /// void cards, for each suit.
+    pub fn summary(&self) -> Vec<SuitSummary> {
+        Suit::iterator()
+            .map(|&suit| {
+                let mut summary = SuitSummary {
+                    suit,
+                    expected: 0.0,
+                    owned: 0,
+                    void: 0,
+                };
+                for (_, v) in self.probs.iter().filter(|(c, _)| c.suit == suit) {
+                    summary.expected += v.p();
+                    match *v {
+                        CardState::Owns => summary.owned += 1,
+                        CardState::Void => summary.void += 1,
+                        _ => (),
+                    }
+                }
+                summary
+            })
+            .collect()
+    }
+
+    /// Return true iff the player provably holds no card of the suit:
+    /// every card of the suit is void or already played.
+    pub fn is_void(&self, suit: Suit) -> bool {
+        self.probs
+            .iter()
+            .filter(|(c, _)| c.suit == suit)
+            .all(|(_, v)| matches!(v, CardState::Void | CardState::Played))
+    }
+
+    /// Return the suits the player provably holds no cards of.
+    pub fn void_suits(&self) -> Vec<Suit> {
+        Suit::iterator()
+            .cloned()
+            .filter(|&s| self.is_void(s))
+            .collect()
+    }
+
+    /// Return the probability that the player holds at least one card
+    /// of the suit, and so must follow it, from the marginal of each
+    /// card of the suit, treated as independent. Unlike `is_void`,
+    /// this accounts for cards the player may or may not hold.
+    pub fn p_can_follow(&self, suit: Suit) -> f32 {
+        let p_none: f32 = self
+            .probs
+            .iter()
+            .filter(|(c, _)| c.suit == suit)
+            .map(|(_, v)| 1.0 - v.p().clamp(0.0, 1.0))
+            .product();
+        1.0 - p_none
+    }
+
+    /// Return the cards of `led_suit` the player might have to follow
+    /// with, each with the probability that they hold it, in card
+    /// order. An empty result means the player is void in the suit, and
+    /// may play any card.
+    pub fn follow_candidates(&self, led_suit: Suit) -> Vec<(BasicCard, f32)> {
+        let mut cards = self.cards_where(|v| (v.is_prob() && v.p() > 0.0) || *v == CardState::Owns);
+        cards.retain(|c| c.suit == led_suit);
+        cards.into_iter().map(|c| (c, self.p(&c))).collect()
+    }
+
+    /// Return the highest card of the suit, aces high, that the player
+    /// holds with probability above `threshold`, or `None` if there is
+    /// none, e.g. because they are likely void in the suit.
+    pub fn likely_highest(&self, suit: Suit, threshold: f32) -> Option<BasicCard> {
+        self.probs
+            .iter()
+            .filter(|&(c, v)| c.suit == suit && v.p() > threshold)
+            .map(|(c, _)| *c)
+            .max_by_key(|c| c.rank.ord_ace_high())
+    }
+
+    /// Return the cards the player definitely holds.
+    pub fn owned_cards(&self) -> Vec<BasicCard> {
+        self.cards_where(|v| *v == CardState::Owns)
+    }
+
+    /// Return the cards the player might hold, but isn't known to.
+    pub fn uncertain_cards(&self) -> Vec<BasicCard> {
+        self.cards_where(|v| v.is_prob() && v.p() > 0.0)
+    }
+
+    fn cards_where<F: Fn(&CardState) -> bool>(&self, pred: F) -> Vec<BasicCard> {
+        let mut cards: Vec<_> = self
+            .probs
+            .iter()
+            .filter(|(_, v)| pred(v))
+            .map(|(c, _)| *c)
+            .collect();
+        cards.sort_by_key(|c| u8::from(c));
+        cards
+    }
+
     /// Return false iff any card in the iteration has zero
     /// probaability.
     pub fn matches_hand<'a, T: Iterator<Item = &'a BasicCard>>(&self, mut iter: T) -> bool {
@@ -133,20 +548,21 @@ impl HandBelief {
     /// such that the total probability increase is ec.
     fn distribute_uniformly(&mut self, ec: f32) {
         let nc = self.num_candidates();
-        for v in self.probs.values_mut() {
-            if let CardState::Prob(ref mut p) = v {
-                *p += ec / nc;
-            }
-        }
+        self.modify(|_, v| match v {
+            CardState::Prob(p) => CardState::Prob(p + ec / nc),
+            _ => v,
+        });
     }
 
     /// Transfer the probability from cards satisfying the predicate
     /// to cards that don't.
     fn transfer_probability_to<F: Fn(BasicCard) -> bool>(&mut self, pred: F) {
-        let (p_dist, count) = self
-            .probs
+        // sum in card order, so that the result doesn't depend on the
+        // map's iteration order
+        let (p_dist, count) = BasicCard::all()
             .iter()
-            .filter_map(|(k, v)| {
+            .filter_map(|k| {
+                let v = self.probs[k];
                 if !pred(*k) && v.is_prob() {
                     Some(v.p())
                 } else {
@@ -158,15 +574,11 @@ impl HandBelief {
         let nc = self.num_candidates();
         let p_inc = p_dist / (nc - count);
 
-        for (card, p) in self.probs.iter_mut() {
-            if let CardState::Prob(ref mut pp) = p {
-                if pred(*card) {
-                    *pp += p_inc;
-                } else {
-                    *pp = 0.0;
-                }
-            }
-        }
+        self.modify(|card, v| match v {
+            CardState::Prob(p) if pred(card) => CardState::Prob(p + p_inc),
+            CardState::Prob(_) => CardState::Prob(0.0),
+            _ => v,
+        });
     }
 
     // Return the number of cards that are in a probability state.
@@ -176,57 +588,121 @@ impl HandBelief {
 
     // mark all cards as non-void
     fn remove_voids(&mut self) {
-        for p in self.probs.values_mut() {
-            *p = p.void_to_zero();
-        }
+        self.modify(|_, mut v| v.void_to_zero());
     }
 
     // Draw n random cards, after marking cards as non-void.
     pub fn random_cards_drawn(&mut self, n: usize) {
+        self.tick();
         self.remove_voids();
         self.distribute_uniformly(n as f32);
     }
 
     /// establish that the suit is empty
     pub fn empty_suit(&mut self, suit: Suit) {
+        self.tick();
         self.transfer_probability_to(|c| c.suit != suit);
-        for (k, v) in self.probs.iter_mut() {
-            if k.suit == suit {
-                if let CardState::Prob(_) = v {
-                    *v = CardState::Void;
+        self.modify(|k, v| {
+            if k.suit == suit && v.is_prob() {
+                CardState::Void
+            } else {
+                v
+            }
+        });
+    }
+
+    /// Set the model used by `observe_play_choice`. With no model (the
+    /// default), play choices don't affect the belief.
+    pub fn set_play_choice_model(&mut self, model: Option<PlayChoiceModel>) {
+        self.choice_model = model;
+    }
+
+    pub fn play_choice_model(&self) -> Option<PlayChoiceModel> {
+        self.choice_model
+    }
+
+    /// Update the belief after the player played `played` when they
+    /// might instead have played any of `could_have_played`.
+    ///
+    /// Each uncertain alternative is scaled by the play choice model,
+    /// and the probability removed is spread evenly over the other
+    /// uncertain cards, so the expected number of cards is unchanged.
+    /// No card is raised above 1; what a card can't take is passed on
+    /// to the others. Does nothing unless a model has been set.
+    pub fn observe_play_choice(&mut self, played: &BasicCard, could_have_played: &[BasicCard]) {
+        let model = match self.choice_model {
+            Some(m) => m,
+            None => return,
+        };
+        self.tick();
+
+        let mut removed = 0.0;
+        for alt in could_have_played.iter().filter(|c| *c != played) {
+            if let CardState::Prob(p) = self.probs[alt] {
+                let scaled = p * model(played, alt).clamp(0.0, 1.0);
+                removed += p - scaled;
+                self.set(*alt, CardState::Prob(scaled));
+            }
+        }
+
+        let mut others: Vec<_> = self
+            .probs
+            .iter()
+            .filter(|&(k, v)| v.is_prob() && !could_have_played.contains(k))
+            .map(|(k, _)| *k)
+            .collect();
+
+        // Stop once a pass caps no card, as everything left was placed.
+        while removed > 0.0 && !others.is_empty() {
+            let p_inc = removed / others.len() as f32;
+            let mut uncapped = Vec::with_capacity(others.len());
+            for &card in &others {
+                let p = self.probs[&card].p();
+                let raised = (p + p_inc).min(1.0);
+                removed -= raised - p;
+                self.set(card, CardState::Prob(raised));
+                if raised < 1.0 {
+                    uncapped.push(card);
                 }
             }
+            if uncapped.len() == others.len() {
+                break;
+            }
+            others = uncapped;
         }
     }
 
     /// Show that a particular card has been picked up.
     pub fn card_drawn(&mut self, card: &BasicCard) {
+        self.tick();
         // distribute the probability among the remaining cards
         self.transfer_probability_to(|c| c != *card);
 
         // Set the card to be owned
-        self.probs.insert(*card, CardState::Owns);
+        self.set(*card, CardState::Owns);
     }
 
     /// Mark that the card has been played by this player.
     pub fn card_played(&mut self, card: &BasicCard) {
+        self.tick();
         if self.probs.get(card).unwrap().is_prob() {
             self.transfer_probability_to(|c| c != *card);
 
             // mark that the card has been played
-            self.probs.insert(*card, CardState::Played);
+            self.set(*card, CardState::Played);
 
             // show that the total number of cards has been reduced
             self.distribute_uniformly(-1.0);
         } else {
-            self.probs.insert(*card, CardState::Played);
+            self.set(*card, CardState::Played);
         }
     }
 
     /// Mark that the card was played by another player.
     pub fn card_seen(&mut self, card: &BasicCard) {
+        self.tick();
         self.transfer_probability_to(|c| c != *card);
-        self.probs.insert(*card, CardState::Played);
+        self.set(*card, CardState::Played);
     }
 
     /// Write the probabilities onto a vector in the given suit order.
@@ -247,13 +723,649 @@ impl HandBelief {
     }
 }
 
-impl fmt::Display for HandBelief {
+/// Record of belief probabilities against the actual holdings, to
+/// check that a belief is calibrated: of the cards believed held with
+/// probability p, a fraction p should actually be held.
+#[derive(Debug, Clone, Default)]
+pub struct BeliefCalibration {
+    samples: Vec<(f32, bool)>,
+}
+
+impl BeliefCalibration {
+    pub fn new() -> BeliefCalibration {
+        Self::default()
+    }
+
+    /// Record a single prediction and its outcome.
+    pub fn record(&mut self, prob: f32, actually_held: bool) {
+        self.samples.push((prob, actually_held));
+    }
+
+    /// Record the belief in every uncertain card against the player's
+    /// actual hand. Cards known to be held or not are skipped, as their
+    /// outcome is certain.
+    pub fn record_belief(&mut self, belief: &HandBelief, hand: &[BasicCard]) {
+        for card in belief.uncertain_cards() {
+            self.record(belief.p(&card), hand.contains(&card));
+        }
+    }
+
+    pub fn num_samples(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Split [0, 1] into `buckets` equal intervals of predicted
+    /// probability, and return the mean prediction and the observed
+    /// frequency of the samples in each non-empty interval, in order.
+    pub fn reliability(&self, buckets: usize) -> Vec<(f32, f32)> {
+        assert!(buckets > 0, "must have at least one bucket");
+        let mut sums = vec![(0.0, 0, 0); buckets];
+        for &(p, held) in &self.samples {
+            let i = ((p.clamp(0.0, 1.0) * buckets as f32) as usize).min(buckets - 1);
+            sums[i].0 += p;
+            sums[i].1 += held as usize;
+            sums[i].2 += 1;
+        }
+
+        sums.into_iter()
+            .filter(|&(_, _, n)| n > 0)
+            .map(|(p, held, n)| (p / n as f32, held as f32 / n as f32))
+            .collect()
+    }
+}
+
+/// Return the distribution of the number of cards held, given an
+/// independent probability of holding each card: entry `k` is the
+/// probability of holding exactly `k`. Counts above `max_count` are
+/// left out, saving work when only small counts matter.
+pub fn count_distribution<I: IntoIterator<Item = f32>>(probs: I, max_count: usize) -> Vec<f64> {
+    let mut dist = vec![1.0];
+    for p in probs {
+        let p = f64::from(p.clamp(0.0, 1.0));
+        if dist.len() <= max_count {
+            dist.push(0.0);
+        }
+        for j in (0..dist.len()).rev() {
+            let below = if j > 0 { dist[j - 1] } else { 0.0 };
+            dist[j] = dist[j] * (1.0 - p) + below * p;
+        }
+    }
+    dist
+}
+
+/// Compact view of a `HandBelief` for a single suit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SuitSummary {
+    pub suit: Suit,
+
+    /// expected number of cards held in the suit
+    pub expected: f32,
+
+    /// number of cards definitely held
+    pub owned: usize,
+
+    /// number of cards the player was proven not to hold
+    pub void: usize,
+}
+
+impl fmt::Display for SuitSummary {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        format_card_map(&self.probs, fmt)?;
-        writeln!(
+        write!(
             fmt,
-            "Num Cards: {:.1}",
-            self.probs.values().map(|v| v.p()).sum::<f32>()
+            "{}: {:.1} ({} mine, {} void)",
+            self.suit, self.expected, self.owned, self.void
         )
     }
 }
+
+/// Shows the per-suit `summary`. The alternate form (`{:#}`) shows the
+/// full probability grid instead.
+impl fmt::Display for HandBelief {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        if fmt.alternate() {
+            format_card_map(&self.probs, fmt)?;
+        } else {
+            let suits: Vec<_> = self.summary().iter().map(|s| s.to_string()).collect();
+            writeln!(fmt, "{}", suits.join(" | "))?;
+        }
+        writeln!(fmt, "Num Cards: {:.1}", self.num_cards())
+    }
+}
+
+/// Belief about the cards an opponent holds, updated from public
+/// events. `PlayerState` is generic over it, so cheaper models can
+/// stand in for `HandBelief`.
+pub trait OpponentModel {
+    /// Reset to a player holding no cards, with every card unknown.
+    fn clear(&mut self);
+
+    /// The player played the card.
+    fn card_played(&mut self, card: &BasicCard);
+
+    /// The card was seen elsewhere, so the player doesn't hold it.
+    fn card_seen(&mut self, card: &BasicCard);
+
+    /// The player drew the card face up.
+    fn card_drawn(&mut self, card: &BasicCard);
+
+    /// The player holds no cards of the suit.
+    fn empty_suit(&mut self, suit: Suit);
+
+    /// The player drew `n` cards face down.
+    fn random_cards_drawn(&mut self, n: usize);
+
+    /// Return the probability that the player holds the card.
+    fn p(&self, card: &BasicCard) -> f32;
+
+    /// Return true iff the player provably holds no card of the suit.
+    fn is_void(&self, suit: Suit) -> bool;
+
+    /// Return the expected number of cards the player holds.
+    fn num_cards(&self) -> f32;
+
+    /// Return the probability that the player holds at least one card
+    /// of the suit, treating the cards as independent.
+    fn p_can_follow(&self, suit: Suit) -> f32 {
+        let p_none: f32 = BasicCard::all()
+            .iter()
+            .filter(|c| c.suit == suit)
+            .map(|c| 1.0 - self.p(c).clamp(0.0, 1.0))
+            .product();
+        1.0 - p_none
+    }
+
+    /// Return true if the model uses `observe_play_choice`, so callers
+    /// can skip computing the alternatives otherwise.
+    fn observes_play_choices(&self) -> bool {
+        false
+    }
+
+    /// The player played `played` when they might instead have played
+    /// any of `could_have_played`. Ignored by default.
+    fn observe_play_choice(&mut self, _played: &BasicCard, _could_have_played: &[BasicCard]) {}
+}
+
+impl OpponentModel for HandBelief {
+    fn clear(&mut self) {
+        HandBelief::clear(self)
+    }
+
+    fn card_played(&mut self, card: &BasicCard) {
+        HandBelief::card_played(self, card)
+    }
+
+    fn card_seen(&mut self, card: &BasicCard) {
+        HandBelief::card_seen(self, card)
+    }
+
+    fn card_drawn(&mut self, card: &BasicCard) {
+        HandBelief::card_drawn(self, card)
+    }
+
+    fn empty_suit(&mut self, suit: Suit) {
+        HandBelief::empty_suit(self, suit)
+    }
+
+    fn random_cards_drawn(&mut self, n: usize) {
+        HandBelief::random_cards_drawn(self, n)
+    }
+
+    fn p(&self, card: &BasicCard) -> f32 {
+        HandBelief::p(self, card)
+    }
+
+    fn is_void(&self, suit: Suit) -> bool {
+        HandBelief::is_void(self, suit)
+    }
+
+    fn num_cards(&self) -> f32 {
+        HandBelief::num_cards(self)
+    }
+
+    fn p_can_follow(&self, suit: Suit) -> f32 {
+        HandBelief::p_can_follow(self, suit)
+    }
+
+    fn observes_play_choices(&self) -> bool {
+        self.play_choice_model().is_some()
+    }
+
+    fn observe_play_choice(&mut self, played: &BasicCard, could_have_played: &[BasicCard]) {
+        HandBelief::observe_play_choice(self, played, could_have_played)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{count_distribution, BeliefCalibration, BeliefInconsistency, HandBelief};
+    use crate::cards::prelude::*;
+
+    #[test]
+    fn test_count_distribution() {
+        let dist = count_distribution(vec![0.5, 0.5, 1.0], usize::MAX);
+        assert_eq!(dist, vec![0.0, 0.25, 0.5, 0.25]);
+
+        // higher counts are cut off, leaving the lower ones unchanged
+        assert_eq!(count_distribution(vec![0.5, 0.5, 1.0], 1), vec![0.0, 0.25]);
+        assert_eq!(count_distribution(Vec::new(), 3), vec![1.0]);
+    }
+
+    #[test]
+    fn test_p_given_count() {
+        let mut hb = HandBelief::new();
+        hb.random_cards_drawn(4);
+        let ace: BasicCard = "A♠".parse().unwrap();
+        hb.card_drawn(&ace);
+        hb.empty_suit(Suit::Hearts);
+
+        assert_eq!(hb.p_given_count(&ace, 4), 1.0);
+        assert_eq!(hb.p_given_count(&"2♥".parse().unwrap(), 4), 0.0);
+
+        // the conditional probabilities add up to the known hand size
+        for total in 1..6 {
+            let sum: f32 = BasicCard::all()
+                .iter()
+                .map(|c| hb.p_given_count(c, total))
+                .sum();
+            assert!((sum - total as f32).abs() < 1e-3);
+        }
+
+        // holding only the known ace, nothing else is possible
+        assert_eq!(hb.p_given_count(&"K♠".parse().unwrap(), 1), 0.0);
+    }
+
+    #[test]
+    fn test_observe_play_choice() {
+        let mut hb = HandBelief::new();
+        hb.random_cards_drawn(13);
+        let played: BasicCard = "5♠".parse().unwrap();
+        let alts = parse_hand("K♠ A♠").unwrap();
+        let before: Vec<_> = BasicCard::all().iter().map(|c| hb.p(c)).collect();
+
+        // no model: nothing changes
+        hb.observe_play_choice(&played, &alts);
+        let after: Vec<_> = BasicCard::all().iter().map(|c| hb.p(c)).collect();
+        assert_eq!(before, after);
+
+        hb.set_play_choice_model(Some(|_, _| 0.5));
+        hb.observe_play_choice(&played, &alts);
+        assert!((hb.p(&alts[0]) - 0.125).abs() < 1e-6);
+        assert!((hb.p(&alts[1]) - 0.125).abs() < 1e-6);
+        assert!(hb.p(&"2♥".parse().unwrap()) > 0.25);
+        assert!((hb.num_cards() - 13.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_observe_play_choice_bounded() {
+        let mut hb = HandBelief::new();
+        hb.random_cards_drawn(13);
+        hb.set_play_choice_model(Some(|_, _| 0.5));
+        let played: BasicCard = "5♠".parse().unwrap();
+        let ace: BasicCard = "A♠".parse().unwrap();
+        let off_suit: Vec<_> = BasicCard::all()
+            .into_iter()
+            .filter(|c| c.suit != Suit::Spades)
+            .collect();
+
+        // push the belief onto the spades, then keep discounting the ace
+        for i in 0..40 {
+            let alts = if i < 20 { &off_suit[..] } else { &[ace][..] };
+            hb.observe_play_choice(&played, alts);
+            for card in BasicCard::all() {
+                assert!(
+                    (0.0..=1.0).contains(&hb.p(&card)),
+                    "{}: {}",
+                    card,
+                    hb.p(&card)
+                );
+            }
+            assert!((hb.num_cards() - 13.0).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_void_suits() {
+        let mut hb = HandBelief::new();
+        assert_eq!(hb.void_suits().len(), 4);
+
+        hb.random_cards_drawn(13);
+        assert!(hb.void_suits().is_empty());
+
+        hb.empty_suit(Suit::Hearts);
+        assert!(hb.is_void(Suit::Hearts));
+        assert!(!hb.is_void(Suit::Spades));
+
+        // an opponent holding the last spade isn't void in spades
+        let spades = parse_hand("A♠ K♠ Q♠ J♠ T♠ 9♠ 8♠ 7♠ 6♠ 5♠ 4♠ 3♠").unwrap();
+        for c in &spades {
+            hb.card_seen(c);
+        }
+        hb.card_drawn(&"2♠".parse().unwrap());
+        assert_eq!(hb.void_suits(), vec![Suit::Hearts]);
+        hb.card_played(&"2♠".parse().unwrap());
+        assert!(hb.is_void(Suit::Spades));
+        assert_eq!(hb.void_suits().len(), 2);
+    }
+
+    #[test]
+    fn test_follow_candidates() {
+        let mut hb = HandBelief::new();
+        hb.random_cards_drawn(13);
+        for c in &parse_hand("A♥ K♥ Q♥ J♥ T♥ 9♥ 8♥ 7♥ 6♥ 5♥").unwrap() {
+            hb.card_seen(c);
+        }
+        hb.card_drawn(&"4♥".parse().unwrap());
+
+        let hearts = hb.follow_candidates(Suit::Hearts);
+        let cards: Vec<_> = hearts.iter().map(|&(c, _)| c).collect();
+        assert_eq!(cards, parse_hand("2♥ 3♥ 4♥").unwrap());
+        assert_eq!(hearts[2].1, 1.0);
+        assert!(hearts[..2].iter().all(|&(_, p)| p > 0.0 && p < 1.0));
+
+        hb.card_played(&"4♥".parse().unwrap());
+        hb.empty_suit(Suit::Hearts);
+        assert!(hb.follow_candidates(Suit::Hearts).is_empty());
+        assert_eq!(hb.follow_candidates(Suit::Clubs).len(), 13);
+    }
+
+    #[test]
+    fn test_recency() {
+        let mut hb = HandBelief::new();
+        let ace: BasicCard = "A♠".parse().unwrap();
+        hb.random_cards_drawn(13);
+        assert_eq!(hb.recency(&ace), None);
+
+        hb.set_track_recency(true);
+        assert_eq!(hb.recency(&ace), None);
+
+        // every uncertain card shares in the probability of a draw
+        hb.random_cards_drawn(1);
+        assert_eq!(hb.recency(&ace), Some(0));
+
+        // voiding hearts raises the other suits, then clears the hearts,
+        // all within one observation
+        let clubs: BasicCard = "A♣".parse().unwrap();
+        hb.empty_suit(Suit::Hearts);
+        assert_eq!(hb.recency(&ace), Some(0));
+        assert_eq!(hb.recency(&clubs), Some(0));
+        assert_eq!(hb.recency(&"A♥".parse().unwrap()), None);
+
+        // the clock advances once per observation, even where it sets
+        // only some of the cards
+        hb.set_play_choice_model(Some(|_, _| 1.0));
+        for i in 1..4 {
+            hb.observe_play_choice(&"2♠".parse().unwrap(), &[clubs]);
+            assert_eq!(hb.recency(&ace), Some(i));
+            assert_eq!(hb.recency(&clubs), Some(0));
+        }
+
+        hb.card_drawn(&ace);
+        assert_eq!(hb.recency(&ace), None);
+
+        hb.set_track_recency(false);
+        assert_eq!(hb.recency(&clubs), None);
+    }
+
+    #[test]
+    fn test_likely_highest() {
+        let mut hb = HandBelief::new();
+        assert_eq!(hb.likely_highest(Suit::Hearts, 0.0), None);
+
+        hb.random_cards_drawn(13);
+        let ace: BasicCard = "A♥".parse().unwrap();
+        assert_eq!(hb.likely_highest(Suit::Hearts, 0.0), Some(ace));
+        assert_eq!(hb.likely_highest(Suit::Hearts, 0.5), None);
+
+        hb.card_drawn(&"9♥".parse().unwrap());
+        assert_eq!(hb.likely_highest(Suit::Hearts, 0.5), "9♥".parse().ok());
+        hb.card_seen(&ace);
+        assert_eq!(hb.likely_highest(Suit::Hearts, 0.0), "K♥".parse().ok());
+    }
+
+    #[test]
+    fn test_p_can_follow() {
+        let mut hb = HandBelief::new();
+        hb.random_cards_drawn(13);
+        let p = hb.p_can_follow(Suit::Spades);
+        let p_card = hb.p(&"A♠".parse().unwrap());
+        assert!((p - (1.0 - (1.0 - p_card).powi(13))).abs() < 1e-5);
+        assert!(p > 0.0 && p < 1.0);
+
+        hb.empty_suit(Suit::Spades);
+        assert!(hb.is_void(Suit::Spades));
+        assert_eq!(hb.p_can_follow(Suit::Spades), 0.0);
+
+        hb.card_drawn(&"2♦".parse().unwrap());
+        assert_eq!(hb.p_can_follow(Suit::Diamonds), 1.0);
+    }
+
+    #[test]
+    fn test_reliability() {
+        let mut cal = BeliefCalibration::new();
+        for &(p, held) in &[
+            (0.1, false),
+            (0.2, true),
+            (0.3, false),
+            (0.9, true),
+            (1.0, true),
+        ] {
+            cal.record(p, held);
+        }
+        assert_eq!(cal.num_samples(), 5);
+
+        let r = cal.reliability(2);
+        assert_eq!(r.len(), 2);
+        assert!((r[0].0 - 0.2).abs() < 1e-6);
+        assert!((r[0].1 - 1.0 / 3.0).abs() < 1e-6);
+        assert_eq!(r[1], (0.95, 1.0));
+
+        // empty buckets are left out
+        assert_eq!(cal.reliability(10).len(), 4);
+    }
+
+    #[test]
+    fn test_calibration() {
+        use crate::germanwhist::{Action, Player, PlayerState, RandomPlayer, Round};
+        use rand::{SeedableRng, StdRng};
+
+        let mut cal = BeliefCalibration::new();
+        let mut rng = StdRng::from_seed(&[3][..]);
+        let mut random = RandomPlayer::new(StdRng::from_seed(&[4][..]));
+        for _ in 0..200 {
+            let mut round = Round::new((0, 1));
+            let mut ps = PlayerState::new(0);
+            for ev in &round.start_round_with_rng(None, &mut rng)[0] {
+                ps.on_event(ev);
+            }
+            while !round.is_game_over() {
+                cal.record_belief(&ps.oppo, &round.get_state().hands[1]);
+                let view = round.active_player_view();
+                let action = Action {
+                    player: view.player,
+                    card: random.play_card(&view),
+                };
+                for ev in &round.play_action(action).unwrap()[0] {
+                    ps.on_event(ev);
+                }
+            }
+        }
+
+        // Probability is added and removed uniformly rather than in
+        // proportion, so cards predicted below 0.3 turn out to be held
+        // about twice as often. Check that this skew stays one-sided and
+        // bounded, and that the upper range is calibrated.
+        let diagram = cal.reliability(10);
+        assert!(diagram.len() >= 8);
+        for &(predicted, observed) in &diagram {
+            if predicted >= 0.3 {
+                assert!((predicted - observed).abs() < 0.05);
+            } else {
+                assert!(observed > predicted);
+                assert!(observed - predicted < 0.3);
+            }
+        }
+    }
+
+    #[test]
+    fn test_checkpoint() {
+        let snapshot =
+            |hb: &HandBelief| BasicCard::all().iter().map(|c| hb.p(c)).collect::<Vec<_>>();
+        let mut hb = HandBelief::new();
+        hb.random_cards_drawn(13);
+        hb.card_seen(&"A♠".parse().unwrap());
+        let before = snapshot(&hb);
+
+        let outer = hb.checkpoint();
+        hb.card_played(&"K♠".parse().unwrap());
+        let middle = snapshot(&hb);
+
+        let inner = hb.checkpoint();
+        hb.empty_suit(Suit::Hearts);
+        hb.card_drawn(&"2♣".parse().unwrap());
+        assert_ne!(snapshot(&hb), middle);
+
+        hb.restore(inner);
+        assert_eq!(snapshot(&hb), middle);
+        hb.restore(outer);
+        assert_eq!(snapshot(&hb), before);
+
+        // nothing is recorded after a commit
+        hb.commit();
+        hb.card_played(&"Q♠".parse().unwrap());
+        assert!(hb.journal.is_empty());
+
+        let _cp = hb.checkpoint();
+        hb.empty_suit(Suit::Clubs);
+        hb.commit();
+        assert!(hb.is_void(Suit::Clubs));
+        assert!(hb.journal.is_empty());
+        assert!(!hb.recording);
+    }
+
+    #[test]
+    fn test_checkpoint_reuse() {
+        let snapshot =
+            |hb: &HandBelief| BasicCard::all().iter().map(|c| hb.p(c)).collect::<Vec<_>>();
+        let mut hb = HandBelief::new();
+        hb.random_cards_drawn(13);
+        let before = snapshot(&hb);
+
+        // the same checkpoint can be restored repeatedly
+        let cp = hb.checkpoint();
+        for suit in Suit::iterator() {
+            hb.empty_suit(*suit);
+            assert_ne!(snapshot(&hb), before);
+            hb.restore(cp);
+            assert_eq!(snapshot(&hb), before);
+        }
+        hb.commit();
+    }
+
+    #[test]
+    fn test_checkpoint_nested_at_start() {
+        let snapshot =
+            |hb: &HandBelief| BasicCard::all().iter().map(|c| hb.p(c)).collect::<Vec<_>>();
+        let mut hb = HandBelief::new();
+        hb.random_cards_drawn(13);
+        let before = snapshot(&hb);
+
+        // both checkpoints are taken before any change is recorded
+        let outer = hb.checkpoint();
+        let inner = hb.checkpoint();
+        hb.card_seen(&"A♠".parse().unwrap());
+        hb.restore(inner);
+        assert_eq!(snapshot(&hb), before);
+
+        hb.card_drawn(&"K♠".parse().unwrap());
+        let inner = hb.checkpoint();
+        hb.empty_suit(Suit::Hearts);
+        hb.restore(inner);
+        hb.restore(outer);
+        assert_eq!(snapshot(&hb), before);
+        hb.commit();
+    }
+
+    #[test]
+    fn test_intersect() {
+        let ace: BasicCard = "A♠".parse().unwrap();
+        let king: BasicCard = "K♠".parse().unwrap();
+
+        // both hold five cards, one of which `a` saw picked up
+        let mut a = HandBelief::new();
+        a.random_cards_drawn(4);
+        let mut b = HandBelief::new();
+        b.random_cards_drawn(5);
+
+        a.card_drawn(&ace);
+        a.empty_suit(Suit::Hearts);
+        b.card_seen(&king);
+
+        let merged = a.intersect(&b).unwrap();
+        assert_eq!(merged.p(&ace), 1.0);
+        assert_eq!(merged.p(&king), 0.0);
+        assert!(merged.is_void(Suit::Hearts));
+        assert!((merged.num_cards() - 5.0).abs() < 1e-3);
+        assert!(b
+            .intersect(&a)
+            .unwrap()
+            .matches_hand(parse_hand("A♠ 2♣ 3♣ 4♣ 5♣").unwrap().iter()));
+
+        // owning a card the other belief saw played is a contradiction
+        b.card_seen(&ace);
+        assert_eq!(a.intersect(&b).err(), Some(BeliefInconsistency::Card(ace)));
+
+        let mut c = HandBelief::new();
+        c.random_cards_drawn(4);
+        assert!(matches!(
+            a.intersect(&c),
+            Err(BeliefInconsistency::CardCount(_, _))
+        ));
+    }
+
+    #[test]
+    fn test_intersect_capped() {
+        // `a` thinks the low spades unlikely, `b` that the hand is all
+        // spades, so scaling alone would put the high spades above 1
+        let low = parse_hand("2♠ 3♠ 4♠ 5♠ 6♠ 7♠ 8♠").unwrap();
+        let mut a = HandBelief::new();
+        a.random_cards_drawn(12);
+        a.set_play_choice_model(Some(|_, _| 0.1));
+        a.observe_play_choice(&"A♣".parse().unwrap(), &low);
+        let mut b = HandBelief::new();
+        b.random_cards_drawn(12);
+        for &suit in &[Suit::Hearts, Suit::Diamonds, Suit::Clubs] {
+            b.empty_suit(suit);
+        }
+
+        let merged = a.intersect(&b).unwrap();
+        for card in parse_hand("9♠ T♠ J♠ Q♠ K♠ A♠").unwrap() {
+            assert_eq!(merged.p(&card), 1.0);
+        }
+        for card in &low {
+            assert!((merged.p(card) - 6.0 / 7.0).abs() < 1e-4);
+        }
+        assert!((merged.num_cards() - 12.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_summary() {
+        let mut hb = HandBelief::new();
+        hb.random_cards_drawn(4);
+        hb.card_drawn(&"A♠".parse().unwrap());
+        hb.card_drawn(&"K♠".parse().unwrap());
+        hb.empty_suit(Suit::Hearts);
+
+        let summary = hb.summary();
+        assert_eq!(summary.len(), 4);
+        let spades = summary.iter().find(|s| s.suit == Suit::Spades).unwrap();
+        assert_eq!(spades.owned, 2);
+        assert!(spades.expected >= 2.0);
+        let hearts = summary.iter().find(|s| s.suit == Suit::Hearts).unwrap();
+        assert_eq!((hearts.expected, hearts.owned, hearts.void), (0.0, 0, 13));
+
+        let total: f32 = summary.iter().map(|s| s.expected).sum();
+        assert!((total - hb.num_cards()).abs() < 1e-4);
+        assert_eq!(format!("{}", hb).lines().count(), 2);
+        assert!(format!("{:#}", hb).lines().count() > 2);
+    }
+}
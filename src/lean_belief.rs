@@ -0,0 +1,198 @@
+use crate::cards::{suit_mask, BasicCard, Suit};
+use crate::hand_belief::OpponentModel;
+
+/// Cheap belief over the cards an opponent holds, for the inner loop
+/// of Monte Carlo rollouts.
+///
+/// Unlike `HandBelief`, no per-card probabilities are kept: only which
+/// cards are unaccounted for, which the opponent is known to hold, the
+/// suits they are void in, and how many unknown cards they hold. Every
+/// unknown card of a suit they may hold is taken to be equally likely,
+/// so inferences such as those of `HandBelief::observe_play_choice` are
+/// lost. Updates take constant time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LeanBelief {
+    /// cards whose location is unknown, as in `card_mask`
+    unknown: u64,
+
+    /// cards the player is known to hold
+    owned: u64,
+
+    /// bit `suit as u8` is set for each suit the player is void in
+    voids: u8,
+
+    /// number of unknown cards the player holds
+    hidden: usize,
+}
+
+impl Default for LeanBelief {
+    fn default() -> Self {
+        LeanBelief {
+            unknown: suit_mask(Suit::Clubs)
+                | suit_mask(Suit::Diamonds)
+                | suit_mask(Suit::Hearts)
+                | suit_mask(Suit::Spades),
+            owned: 0,
+            voids: 0,
+            hidden: 0,
+        }
+    }
+}
+
+impl LeanBelief {
+    /// Create a belief in which the player holds no cards, and every
+    /// card is unknown.
+    pub fn new() -> LeanBelief {
+        Self::default()
+    }
+
+    /// Return the unknown cards the player may hold: those not in a
+    /// suit they are void in.
+    fn candidates(&self) -> u64 {
+        Suit::iterator()
+            .filter(|s| self.voids & (1 << **s as u8) != 0)
+            .fold(self.unknown, |mask, s| mask & !suit_mask(*s))
+    }
+}
+
+impl OpponentModel for LeanBelief {
+    /// Reset to the state of `new`.
+    fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Return the number of cards the player holds.
+    fn num_cards(&self) -> f32 {
+        (self.owned.count_ones() as usize + self.hidden) as f32
+    }
+
+    /// Return the probability that the player holds the card.
+    fn p(&self, card: &BasicCard) -> f32 {
+        let bit = 1u64 << u8::from(card);
+        if self.owned & bit != 0 {
+            return 1.0;
+        }
+        let candidates = self.candidates();
+        if candidates & bit == 0 {
+            return 0.0;
+        }
+        (self.hidden as f32 / candidates.count_ones() as f32).min(1.0)
+    }
+
+    /// Return true iff the player provably holds no card of the suit.
+    fn is_void(&self, suit: Suit) -> bool {
+        self.p_can_follow(suit) == 0.0
+    }
+
+    /// Return the probability that the player holds at least one card
+    /// of the suit, with the unknown cards they hold drawn uniformly
+    /// from the candidates.
+    fn p_can_follow(&self, suit: Suit) -> f32 {
+        if self.owned & suit_mask(suit) != 0 {
+            return 1.0;
+        }
+        let candidates = self.candidates();
+        let n = candidates.count_ones() as usize;
+        let k = (candidates & suit_mask(suit)).count_ones() as usize;
+        if k == 0 || self.hidden == 0 {
+            return 0.0;
+        }
+        if self.hidden + k > n {
+            return 1.0;
+        }
+
+        // chance that all `hidden` cards come from the other suits
+        let p_none: f32 = (0..self.hidden)
+            .map(|i| (n - k - i) as f32 / (n - i) as f32)
+            .product();
+        1.0 - p_none
+    }
+
+    /// The player drew `n` cards face down. They may now hold cards of
+    /// suits they were void in.
+    fn random_cards_drawn(&mut self, n: usize) {
+        self.voids = 0;
+        self.hidden += n;
+    }
+
+    /// The player drew the card face up.
+    fn card_drawn(&mut self, card: &BasicCard) {
+        let bit = 1u64 << u8::from(card);
+        self.unknown &= !bit;
+        self.owned |= bit;
+    }
+
+    /// The player played the card.
+    fn card_played(&mut self, card: &BasicCard) {
+        let bit = 1u64 << u8::from(card);
+        if self.owned & bit != 0 {
+            self.owned &= !bit;
+        } else if self.unknown & bit != 0 {
+            self.unknown &= !bit;
+            self.hidden = self.hidden.saturating_sub(1);
+        }
+    }
+
+    /// The card was seen elsewhere, so the player doesn't hold it.
+    fn card_seen(&mut self, card: &BasicCard) {
+        let bit = 1u64 << u8::from(card);
+        self.unknown &= !bit;
+        self.owned &= !bit;
+    }
+
+    /// The player holds no cards of the suit.
+    fn empty_suit(&mut self, suit: Suit) {
+        self.voids |= 1 << suit as u8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LeanBelief;
+    use crate::cards::{parse_hand, BasicCard, Suit};
+    use crate::hand_belief::OpponentModel;
+
+    #[test]
+    fn test_lean_belief() {
+        let card = |s: &str| s.parse::<BasicCard>().unwrap();
+        let mut b = LeanBelief::new();
+        assert_eq!(b.num_cards(), 0.0);
+        assert!(b.is_void(Suit::Spades));
+
+        b.random_cards_drawn(13);
+        for c in parse_hand("2♠ 3♠ 4♠ 5♠ 6♠ 7♠ 8♠ 9♠ T♠ J♠ Q♠ K♠ A♠ 2♥").unwrap()
+        {
+            b.card_seen(&c);
+        }
+        assert_eq!(b.num_cards(), 13.0);
+        assert_eq!(b.p(&card("A♠")), 0.0);
+        assert!(b.is_void(Suit::Spades));
+        assert_eq!(b.p(&card("3♥")), 13.0 / 38.0);
+
+        // hypergeometric: no hearts among 13 of 38 cards, 12 of them
+        // hearts
+        let p_none: f32 = (0..13).map(|i| (26 - i) as f32 / (38 - i) as f32).product();
+        assert!((b.p_can_follow(Suit::Hearts) - (1.0 - p_none)).abs() < 1e-6);
+
+        b.empty_suit(Suit::Hearts);
+        assert!(b.is_void(Suit::Hearts));
+        assert_eq!(b.p(&card("3♥")), 0.0);
+        assert_eq!(b.p(&card("3♦")), 0.5);
+
+        b.card_drawn(&card("3♥"));
+        assert_eq!(b.p_can_follow(Suit::Hearts), 1.0);
+        assert_eq!(b.num_cards(), 14.0);
+
+        b.card_played(&card("3♥"));
+        b.card_played(&card("3♦"));
+        assert_eq!(b.num_cards(), 12.0);
+        assert_eq!(b.p(&card("3♦")), 0.0);
+
+        // a face-down draw may refill the void
+        b.random_cards_drawn(1);
+        assert!(!b.is_void(Suit::Hearts));
+
+        b.clear();
+        assert_eq!(b, LeanBelief::new());
+    }
+}
@@ -0,0 +1,170 @@
+use crate::germanwhist::{play_match_with_rng, ActionError, BuiltinPlayer, Player, RandomPlayer};
+use crate::learning::neural_net::NeuralNet;
+use crate::learning::training::SarsaLambda;
+use rand::{SeedableRng, StdRng};
+use std::error;
+use std::fmt;
+use std::io::{self, Write};
+
+/// Number of deals played against each baseline per evaluation, by
+/// default.
+const DEFAULT_EVAL_DEALS: usize = 100;
+
+#[derive(Debug)]
+pub enum HarnessError {
+    /// Writing the metrics failed.
+    Io(io::Error),
+
+    /// The engine rejected an action during training.
+    Action(ActionError),
+}
+
+impl fmt::Display for HarnessError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            HarnessError::Io(ref e) => write!(f, "failed to write metrics: {}", e),
+            HarnessError::Action(ref e) => write!(f, "invalid action in training: {}", e),
+        }
+    }
+}
+
+impl error::Error for HarnessError {}
+
+impl From<io::Error> for HarnessError {
+    fn from(e: io::Error) -> Self {
+        HarnessError::Io(e)
+    }
+}
+
+impl From<ActionError> for HarnessError {
+    fn from(e: ActionError) -> Self {
+        HarnessError::Action(e)
+    }
+}
+
+/// Reproducible training loop, writing evaluation metrics as CSV.
+///
+/// Every `eval_interval` episodes (and once training finishes), the
+/// greedy policy of the current model plays a number of deals against
+/// a random and a basic player, and a row is written with the episode,
+/// the exploration strategy's name and parameter, the win rate against
+/// each baseline, and the L1 norm of the model's weights.
+///
+/// All randomness in training and evaluation is derived from the seed,
+/// so two runs from the same seed and the same initial model produce
+/// identical metrics.
+pub struct TrainingHarness {
+    seed: usize,
+    num_episodes: usize,
+    eval_interval: usize,
+    eval_deals: usize,
+}
+
+impl TrainingHarness {
+    pub fn new(seed: usize, num_episodes: usize, eval_interval: usize) -> TrainingHarness {
+        assert!(eval_interval > 0, "evaluation interval must be positive");
+        TrainingHarness {
+            seed,
+            num_episodes,
+            eval_interval,
+            eval_deals: DEFAULT_EVAL_DEALS,
+        }
+    }
+
+    /// Set the number of deals played against each baseline per
+    /// evaluation.
+    pub fn set_eval_deals(&mut self, eval_deals: usize) {
+        self.eval_deals = eval_deals;
+    }
+
+    /// Train `learner` for the configured number of episodes, writing
+    /// the metrics to `out`.
+    pub fn run<W: Write>(
+        &self,
+        learner: &mut SarsaLambda<NeuralNet>,
+        out: &mut W,
+    ) -> Result<(), HarnessError> {
+        learner.set_seed(&[self.seed, 0]);
+        let mut eval_rng = StdRng::from_seed(&[self.seed, 1][..]);
+        let mut random = RandomPlayer::new(StdRng::from_seed(&[self.seed, 2][..]));
+        let mut basic = BuiltinPlayer::Basic { min_rank: None };
+
+        writeln!(
+            out,
+            "episode,exploration,exploration_param,win_rate_random,win_rate_basic,l1"
+        )?;
+        for episode in 0..=self.num_episodes {
+            if episode % self.eval_interval == 0 || episode == self.num_episodes {
+                let net = learner.current_model();
+                let mut win_rate = |oppo: &mut dyn Player| {
+                    let mut greedy = BuiltinPlayer::greedy(net.clone());
+                    let won =
+                        play_match_with_rng(oppo, &mut greedy, self.eval_deals, &mut eval_rng);
+                    won[1] as f32 / self.eval_deals as f32
+                };
+                let vs_random = win_rate(&mut random);
+                let vs_basic = win_rate(&mut basic);
+
+                let exploration = learner.exploration();
+                writeln!(
+                    out,
+                    "{},{},{},{},{},{}",
+                    episode,
+                    exploration.name(),
+                    exploration.param(),
+                    vs_random,
+                    vs_basic,
+                    net.l1()
+                )?;
+            }
+
+            if episode < self.num_episodes {
+                learner.train_on_episode(false)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TrainingHarness;
+    use crate::germanwhist::PlayerState;
+    use crate::learning::neural_net::{ActivationFunction, LayerDesc, NeuralNet};
+    use crate::learning::training::{SarsaLambda, SarsaLambdaParameters};
+
+    #[test]
+    fn test_reproducible() {
+        let sa = PlayerState::state_action_size();
+        let nn = NeuralNet::new(
+            &[
+                LayerDesc::new(sa, 10, ActivationFunction::SymmetricSigmoid),
+                LayerDesc::new(10, 1, ActivationFunction::Sigmoid),
+            ],
+            0.05,
+        )
+        .unwrap();
+
+        let mut harness = TrainingHarness::new(7, 5, 2);
+        harness.set_eval_deals(4);
+        let run = |nn: NeuralNet| {
+            let mut sl = SarsaLambda::new((0, 1), nn, SarsaLambdaParameters::default()).unwrap();
+            let mut out = Vec::new();
+            harness.run(&mut sl, &mut out).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        let metrics = run(nn.clone());
+        assert_eq!(metrics, run(nn));
+
+        // header, then episodes 0, 2, 4 and the final one
+        let lines: Vec<_> = metrics.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(
+            lines[0],
+            "episode,exploration,exploration_param,win_rate_random,win_rate_basic,l1"
+        );
+        assert!(lines[4].starts_with("5,epsilon_greedy,0.01,"));
+    }
+}
@@ -0,0 +1,112 @@
+use crate::learning::model::LearningModel;
+use ndarray::prelude::*;
+use ndarray::{s, Data};
+
+/// Linear value function: the value of an input is its dot product
+/// with a single weight vector, so the gradient is the input itself.
+///
+/// Fast to evaluate and train, for a baseline against `NeuralNet`.
+/// Unlike `NeuralNet`, the learning rate is constant.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LinearModel {
+    weights: Array1<f32>,
+    learning_rate: f32,
+}
+
+impl LinearModel {
+    /// Create a model of the given input size, with all weights zero.
+    pub fn new(input_size: usize, learning_rate: f32) -> LinearModel {
+        LinearModel {
+            weights: Array::zeros(input_size),
+            learning_rate,
+        }
+    }
+
+    pub fn weights(&self) -> &Array1<f32> {
+        &self.weights
+    }
+
+    /// Replace the weights, which must keep the input size.
+    pub fn set_weights(&mut self, w: ArrayView<f32, Ix1>) {
+        assert_eq!(w.dim(), self.weights.dim());
+        self.weights.assign(&w);
+    }
+}
+
+impl LearningModel for LinearModel {
+    /// The prefix's share of the dot product.
+    type PrefixCache = f32;
+
+    fn evaluate_q(&self, p: &ArrayView<f32, Ix1>) -> f32 {
+        self.weights.dot(p)
+    }
+
+    fn evaluate_q_grad(&self, p: &ArrayView<f32, Ix1>, mut grad: ArrayViewMut<f32, Ix1>) -> f32 {
+        grad.assign(p);
+        self.weights.dot(p)
+    }
+
+    fn input_size(&self) -> usize {
+        self.weights.dim()
+    }
+
+    fn num_parameters(&self) -> usize {
+        self.weights.dim()
+    }
+
+    fn update_weights<T: Data<Elem = f32>>(&mut self, error: f32, dir: &ArrayBase<T, Ix1>) {
+        self.weights.scaled_add(self.learning_rate * error, dir);
+    }
+
+    fn prepare_prefix(&self, prefix: &ArrayView<f32, Ix1>) -> f32 {
+        self.weights.slice(s![..prefix.dim()]).dot(prefix)
+    }
+
+    fn evaluate_q_with_prefix(&self, cache: &f32, suffix: &ArrayView<f32, Ix1>) -> f32 {
+        let offset = self.weights.dim() - suffix.dim();
+        cache + self.weights.slice(s![offset..]).dot(suffix)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LinearModel;
+    use crate::germanwhist::PlayerState;
+    use crate::learning::model::LearningModel;
+    use crate::learning::training::{SarsaLambda, SarsaLambdaParameters};
+    use ndarray::prelude::*;
+    use ndarray::s;
+
+    #[test]
+    fn test_linear_model() {
+        let mut model = LinearModel::new(3, 0.5);
+        model.set_weights(arr1(&[1.0, -2.0, 0.5]).view());
+        let x = arr1(&[2.0, 1.0, 4.0]);
+        assert_eq!(model.evaluate_q(&x.view()), 2.0);
+
+        let mut grad = Array::zeros(3);
+        assert_eq!(model.evaluate_q_grad(&x.view(), grad.view_mut()), 2.0);
+        assert_eq!(grad, x);
+
+        let cache = model.prepare_prefix(&x.slice(s![..2]));
+        assert_eq!(model.evaluate_q_with_prefix(&cache, &x.slice(s![2..])), 2.0);
+
+        // a step of size 0.5 * 0.1 along the input
+        model.update_weights(0.1, &grad);
+        assert_eq!(model.weights(), &arr1(&[1.1, -1.95, 0.7]));
+    }
+
+    #[test]
+    fn test_sarsa_lambda() {
+        let model = LinearModel::new(PlayerState::state_action_size(), 0.001);
+        let mut sl = SarsaLambda::new((0, 1), model, SarsaLambdaParameters::default()).unwrap();
+        sl.set_seed(&[1]);
+        for _ in 0..3 {
+            sl.train_on_episode(true).unwrap();
+        }
+        let w = sl.current_model().weights();
+        assert!(w.iter().all(|x| x.is_finite()));
+        assert!(w.iter().any(|&x| x != 0.0));
+    }
+}
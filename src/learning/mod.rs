@@ -1,5 +1,5 @@
+pub mod harness;
+pub mod linear;
 pub mod model;
 pub mod neural_net;
 pub mod training;
-
-// pub mod linear;
@@ -1,11 +1,35 @@
-use crate::learning::neural_net::NeuralNet;
+use crate::learning::neural_net::{NeuralNet, PrefixCache};
+use crate::learning::training::ParameterError;
 use ndarray::prelude::*;
 use ndarray::Data;
+use std::error;
+use std::fmt;
 
+/// Reason a model can't be used for training.
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum LearningModelError {
-    MismatchedSize,
+    /// The model's input size isn't the size of a state-action vector.
+    MismatchedSize { expected: usize, got: usize },
+
+    /// A training parameter is out of range.
+    InvalidParameter(ParameterError),
+}
+
+impl fmt::Display for LearningModelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LearningModelError::MismatchedSize { expected, got } => write!(
+                f,
+                "model takes {} inputs, but a state-action vector has {}",
+                got, expected
+            ),
+            LearningModelError::InvalidParameter(e) => e.fmt(f),
+        }
+    }
 }
 
+impl error::Error for LearningModelError {}
+
 /// Reinforcement Learning traits and implementations
 pub trait LearningModel {
     // Evaluate the gradient
@@ -22,9 +46,28 @@ pub trait LearningModel {
 
     // Update the weights of the model
     fn update_weights<T: Data<Elem = f32>>(&mut self, error: f32, dir: &ArrayBase<T, Ix1>);
+
+    /// Cached representation of the leading part of an input, from
+    /// `prepare_prefix`.
+    type PrefixCache;
+
+    /// Prepare a cached representation of the leading part of the
+    /// input, to evaluate many inputs that share it with
+    /// `evaluate_q_with_prefix`.
+    fn prepare_prefix(&self, prefix: &ArrayView<f32, Ix1>) -> Self::PrefixCache;
+
+    /// Evaluate the input formed by the prefix passed to
+    /// `prepare_prefix`, followed by `suffix`.
+    fn evaluate_q_with_prefix(
+        &self,
+        cache: &Self::PrefixCache,
+        suffix: &ArrayView<f32, Ix1>,
+    ) -> f32;
 }
 
 impl LearningModel for NeuralNet {
+    type PrefixCache = PrefixCache;
+
     fn evaluate_q(&self, view: &ArrayView<f32, Ix1>) -> f32 {
         self.evaluate(view)[0]
     }
@@ -44,4 +87,73 @@ impl LearningModel for NeuralNet {
     fn update_weights<T: Data<Elem = f32>>(&mut self, error: f32, dir: &ArrayBase<T, Ix1>) {
         self.update_weights(error, dir.view())
     }
+
+    fn prepare_prefix(&self, prefix: &ArrayView<f32, Ix1>) -> PrefixCache {
+        self.prefix_preactivation(prefix)
+    }
+
+    fn evaluate_q_with_prefix(&self, cache: &PrefixCache, suffix: &ArrayView<f32, Ix1>) -> f32 {
+        self.evaluate_with_prefix(cache, suffix)[0]
+    }
+}
+
+/// A fixed set of inputs with known values, to track a model's
+/// accuracy over training independently of win-rate noise.
+#[derive(Debug, Clone, Default)]
+pub struct BenchmarkSuite {
+    pub positions: Vec<(Array1<f32>, f32)>,
+}
+
+impl BenchmarkSuite {
+    pub fn new(positions: Vec<(Array1<f32>, f32)>) -> BenchmarkSuite {
+        BenchmarkSuite { positions }
+    }
+
+    /// Return the root-mean-square error of the model's value for each
+    /// position against its known value. An empty suite has no error.
+    pub fn evaluate_rmse(&self, model: &impl LearningModel) -> f32 {
+        if self.positions.is_empty() {
+            return 0.0;
+        }
+        let sse: f32 = self
+            .positions
+            .iter()
+            .map(|(input, value)| {
+                assert_eq!(
+                    input.dim(),
+                    model.input_size(),
+                    "benchmark input size must match the model"
+                );
+                (model.evaluate_q(&input.view()) - value).powi(2)
+            })
+            .sum();
+        (sse / self.positions.len() as f32).sqrt()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BenchmarkSuite, LearningModel};
+    use crate::learning::neural_net::{ActivationFunction, LayerDesc, NeuralNet};
+    use ndarray::prelude::*;
+
+    #[test]
+    fn test_benchmark_rmse() {
+        let nn = NeuralNet::new(&[LayerDesc::new(3, 1, ActivationFunction::Sigmoid)], 0.1).unwrap();
+        assert_eq!(BenchmarkSuite::default().evaluate_rmse(&nn), 0.0);
+
+        let inputs = [arr1(&[1.0, 0.0, 0.0]), arr1(&[0.0, 1.0, 1.0])];
+        let exact: Vec<_> = inputs
+            .iter()
+            .map(|x| (x.clone(), nn.evaluate_q(&x.view())))
+            .collect();
+        assert!(BenchmarkSuite::new(exact.clone()).evaluate_rmse(&nn) < 1e-6);
+
+        // off by 0.3 and 0.4
+        let mut off = exact;
+        off[0].1 += 0.3;
+        off[1].1 -= 0.4;
+        let rmse = BenchmarkSuite::new(off).evaluate_rmse(&nn);
+        assert!((rmse - (0.125f32).sqrt()).abs() < 1e-5);
+    }
 }
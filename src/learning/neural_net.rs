@@ -1,10 +1,15 @@
 use itertools::{izip, Itertools};
+#[cfg(feature = "blas")]
+use ndarray::linalg::general_mat_vec_mul;
 use ndarray::prelude::*;
-use ndarray::{Data, DataMut, Zip};
+use ndarray::{s, Data, DataMut, Zip};
 use ndarray_rand::{RandomExt, F32};
 use rand::distributions;
+use std::error;
+use std::fmt;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ActivationFunction {
     Linear,
     Sigmoid,
@@ -36,6 +41,10 @@ fn activate_relu(x: f32) -> f32 {
     }
 }
 
+/// Distance from a sigmoid's asymptote, relative to its range, within
+/// which a unit counts as saturated.
+const SATURATION_MARGIN: f32 = 0.01;
+
 fn grad_linear(_x: f32, _f: f32) -> f32 {
     1.0
 }
@@ -69,6 +78,19 @@ impl ActivationFunction {
         }
     }
 
+    /// Return true if the activated value `f` is in a region where the
+    /// gradient (almost) vanishes: zero for ReLU, or near either
+    /// asymptote for the sigmoids.
+    pub fn is_saturated(&self, f: f32) -> bool {
+        use self::ActivationFunction::*;
+        match *self {
+            Linear | Exp => false,
+            Sigmoid => !(SATURATION_MARGIN..=1.0 - SATURATION_MARGIN).contains(&f),
+            SymmetricSigmoid => f.abs() >= SS_FSCALE * (1.0 - SATURATION_MARGIN),
+            ReLU => f <= 0.0,
+        }
+    }
+
     pub fn agf(&self) -> fn(f32, f32) -> f32 {
         use self::ActivationFunction::*;
         match *self {
@@ -81,12 +103,57 @@ impl ActivationFunction {
     }
 }
 
+/// Distribution of a layer's initial weights. Biases always start at
+/// zero.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum InitScheme {
+    /// Normal(0, sqrt(num_outputs)), the original initialization.
+    #[default]
+    SqrtOutputs,
+
+    /// Normal with a fixed standard deviation.
+    Normal { std: f32 },
+
+    /// Glorot normal, Normal(0, sqrt(2 / (num_inputs + num_outputs))).
+    /// Suited to sigmoid and tanh activations.
+    Xavier,
+
+    /// Normal(0, sqrt(2 / num_inputs)). Suited to ReLU activations.
+    He,
+
+    /// Uniform on [-range, range).
+    Uniform { range: f32 },
+}
+
+impl InitScheme {
+    /// Draw a `(num_outputs, num_inputs)` weight matrix.
+    pub fn weights(&self, num_outputs: usize, num_inputs: usize) -> Array2<f32> {
+        let shape = (num_outputs, num_inputs);
+        let normal = |std: f64| Array::random(shape, F32(distributions::Normal::new(0.0, std)));
+        match *self {
+            InitScheme::SqrtOutputs => normal((num_outputs as f64).sqrt()),
+            InitScheme::Normal { std } => normal(f64::from(std)),
+            InitScheme::Xavier => normal((2.0 / (num_inputs + num_outputs) as f64).sqrt()),
+            InitScheme::He => normal((2.0 / num_inputs as f64).sqrt()),
+            InitScheme::Uniform { range } => {
+                let r = f64::from(range);
+                Array::random(shape, F32(distributions::Range::new(-r, r)))
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct LayerDesc {
     /// number of inputs, not includes bias
     pub num_inputs: usize,
     pub num_outputs: usize,
     pub activation: ActivationFunction,
+    pub init: InitScheme,
+
+    /// whether the layer has a trainable bias per output
+    pub bias: bool,
 }
 
 impl LayerDesc {
@@ -95,19 +162,37 @@ impl LayerDesc {
             num_inputs: n_in,
             num_outputs: n_out,
             activation: f,
+            init: InitScheme::default(),
+            bias: true,
         }
     }
+
+    /// Use the given weight initialization scheme.
+    pub fn with_init(mut self, init: InitScheme) -> LayerDesc {
+        self.init = init;
+        self
+    }
+
+    /// Give the layer a bias per output, or none at all.
+    pub fn with_bias(mut self, bias: bool) -> LayerDesc {
+        self.bias = bias;
+        self
+    }
 }
 
 /// Single perceptron layer in a neural network.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct Layer {
     m: Array2<f32>,
-    bias: Array1<f32>,
+    bias: Option<Array1<f32>>,
     act: ActivationFunction,
+    init: InitScheme,
 }
 
 /// take the outer-product of a and b, applying it to c
+///
+/// Dimensions are only checked in debug builds.
 pub fn outer_product<Ta, Tb, Tc>(
     c: &mut ArrayBase<Tc, Ix2>,
     a: &ArrayBase<Ta, Ix1>,
@@ -118,13 +203,17 @@ pub fn outer_product<Ta, Tb, Tc>(
     Tc: DataMut<Elem = f32>,
 {
     for (mut row, ai) in izip!(c.outer_iter_mut(), a) {
-        assert_eq!(row.dim(), b.dim());
+        debug_assert_eq!(row.dim(), b.dim());
         Zip::from(&mut row).and(b).apply(|r, bi| *r = *ai * *bi);
     }
 }
 
 // Quick matrix-vector multiplication
 // c = A * b
+//
+// With the `blas` feature, this goes through ndarray's gemv, which is
+// BLAS-backed. Otherwise, it's a row-wise dot product loop. Dimensions
+// are only checked in debug builds.
 pub fn mat_vec_mul<Ta, Tb, Tc>(
     c: &mut ArrayBase<Tc, Ix1>,
     a: &ArrayBase<Ta, Ix2>,
@@ -134,16 +223,22 @@ pub fn mat_vec_mul<Ta, Tb, Tc>(
     Tb: Data<Elem = f32>,
     Tc: DataMut<Elem = f32>,
 {
-    assert_eq!(a.dim().0, c.dim());
-    assert_eq!(a.dim().1, b.dim());
+    debug_assert_eq!(a.dim().0, c.dim());
+    debug_assert_eq!(a.dim().1, b.dim());
+
+    #[cfg(feature = "blas")]
+    general_mat_vec_mul(1.0, a, b, 0.0, c);
 
+    #[cfg(not(feature = "blas"))]
     for (ci, ar) in izip!(c, a.outer_iter()) {
         *ci = ar.dot(b);
     }
 }
 
 // c = A^t * b
-fn mat_t_vec_mul<Ta, Tb, Tc>(
+//
+// Dimensions are only checked in debug builds.
+pub fn mat_t_vec_mul<Ta, Tb, Tc>(
     c: &mut ArrayBase<Tc, Ix1>,
     a: &ArrayBase<Ta, Ix2>,
     b: &ArrayBase<Tb, Ix1>,
@@ -152,9 +247,13 @@ fn mat_t_vec_mul<Ta, Tb, Tc>(
     Tb: Data<Elem = f32>,
     Tc: DataMut<Elem = f32>,
 {
-    assert_eq!(a.dim().0, b.dim());
-    assert_eq!(a.dim().1, c.dim());
+    debug_assert_eq!(a.dim().0, b.dim());
+    debug_assert_eq!(a.dim().1, c.dim());
 
+    #[cfg(feature = "blas")]
+    general_mat_vec_mul(1.0, &a.t(), b, 0.0, c);
+
+    #[cfg(not(feature = "blas"))]
     for (ci, ar) in izip!(c, a.axis_iter(Axis(1))) {
         *ci = ar.dot(b);
     }
@@ -162,19 +261,29 @@ fn mat_t_vec_mul<Ta, Tb, Tc>(
 
 impl Layer {
     pub fn from_desc(desc: &LayerDesc) -> Layer {
-        let std = (desc.num_outputs as f64).sqrt();
-        let m = Array::random(
-            (desc.num_outputs, desc.num_inputs),
-            F32(distributions::Normal::new(0.0, std)),
-        );
+        let m = desc.init.weights(desc.num_outputs, desc.num_inputs);
         // let m = Array::linspace(0.0, 1.0, desc.num_inputs * desc.num_outputs)
         //     .into_shape((desc.num_outputs, desc.num_inputs)).unwrap();
-        let bias = Array::zeros(desc.num_outputs);
+        let bias = if desc.bias {
+            Some(Array::zeros(desc.num_outputs))
+        } else {
+            None
+        };
 
         Layer {
             m,
             bias,
             act: desc.activation,
+            init: desc.init,
+        }
+    }
+
+    /// Draw fresh weights, as in `from_desc`, and zero the bias.
+    fn reinitialize(&mut self) {
+        let (n_out, n_in) = self.m.dim();
+        self.m = self.init.weights(n_out, n_in);
+        if let Some(ref mut bias) = self.bias {
+            bias.fill(0.0);
         }
     }
 
@@ -186,12 +295,24 @@ impl Layer {
         self.m.dim().0
     }
 
+    /// Number of trainable parameters: the weight matrix, followed by
+    /// the bias, if any.
     pub fn num_parameters(&self) -> usize {
-        self.m.dim().0 * (self.m.dim().1 + 1)
+        self.m.len() + self.num_bias_parameters()
+    }
+
+    fn num_bias_parameters(&self) -> usize {
+        self.bias.as_ref().map_or(0, |b| b.len())
+    }
+
+    /// Return the bias of output `i`, which is zero without a bias.
+    fn bias_at(&self, i: usize) -> f32 {
+        self.bias.as_ref().map_or(0.0, |b| b[i])
     }
 
     pub fn l1(&self) -> f32 {
-        self.m.iter().map(|x| x.abs()).sum::<f32>() + self.bias.iter().map(|x| x.abs()).sum::<f32>()
+        self.m.iter().map(|x| x.abs()).sum::<f32>()
+            + self.bias.iter().flatten().map(|x| x.abs()).sum::<f32>()
     }
 
     /// Evaulate input, placing the result into output.
@@ -200,10 +321,11 @@ impl Layer {
         T1: Data<Elem = f32>,
         T2: DataMut<Elem = f32>,
     {
-        assert!(input.dim() == self.num_inputs());
+        debug_assert_eq!(input.dim(), self.num_inputs());
+        mat_vec_mul(output, &self.m, input);
         let f = self.act.af();
-        for (a, r, b) in izip!(output.iter_mut(), self.m.outer_iter(), &self.bias) {
-            *a = f(r.dot(input) + b);
+        for (i, a) in output.iter_mut().enumerate() {
+            *a = f(*a + self.bias_at(i));
         }
     }
 
@@ -211,62 +333,115 @@ impl Layer {
     where
         T1: Data<Elem = f32>,
     {
-        let mut arr = Array::zeros(self.bias.dim());
+        let mut arr = Array::zeros(self.num_outputs());
         self.evaluate_onto(input, &mut arr);
         arr
     }
 
+    /// Return the pre-activation contribution of the leading inputs
+    /// `prefix`, including the bias, for `evaluate_suffix`.
+    pub fn prefix_preactivation<T1>(&self, prefix: &ArrayBase<T1, Ix1>) -> PrefixCache
+    where
+        T1: Data<Elem = f32>,
+    {
+        assert!(prefix.dim() <= self.num_inputs());
+        let mut preact = match self.bias {
+            Some(ref b) => b.clone(),
+            None => Array::zeros(self.num_outputs()),
+        };
+        for (a, r) in izip!(preact.iter_mut(), self.m.outer_iter()) {
+            *a += r.slice(s![..prefix.dim()]).dot(prefix);
+        }
+        PrefixCache {
+            preact,
+            prefix_len: prefix.dim(),
+        }
+    }
+
+    /// Finish evaluating an input whose leading elements were already
+    /// applied by `prefix_preactivation`.
+    pub fn evaluate_suffix<T1>(
+        &self,
+        prefix_pa: &PrefixCache,
+        suffix: &ArrayBase<T1, Ix1>,
+    ) -> Array1<f32>
+    where
+        T1: Data<Elem = f32>,
+    {
+        assert_eq!(prefix_pa.preact.dim(), self.num_outputs());
+        let offset = prefix_pa.prefix_len;
+        assert_eq!(
+            offset + suffix.dim(),
+            self.num_inputs(),
+            "prefix and suffix must make up the whole input"
+        );
+        let f = self.act.af();
+        let mut arr = Array::zeros(self.num_outputs());
+        for (a, r, pa) in izip!(arr.iter_mut(), self.m.outer_iter(), &prefix_pa.preact) {
+            *a = f(r.slice(s![offset..]).dot(suffix) + pa);
+        }
+        arr
+    }
+
+    /// Evaluate input, placing the result into output and the
+    /// gradient of each output w/rt its inputs into `partial_g`.
+    /// Returns theta'(pa), the derivative of each activation, for
+    /// `complete_g`.
     pub fn evaluate_onto_partial_g<T1, T2>(
         &self,
         input: &ArrayBase<T1, Ix1>,
         output: &mut ArrayBase<T2, Ix1>,
         partial_g: ArrayViewMut<f32, Ix1>,
-    ) where
+    ) -> Array1<f32>
+    where
         T1: Data<Elem = f32>,
         T2: DataMut<Elem = f32>,
     {
         let f = self.act.af();
         let g = self.act.agf();
 
-        let (dml, mut dbias) = partial_g.split_at(Axis(0), self.m.len());
+        let (dml, _) = partial_g.split_at(Axis(0), self.m.len());
         let mut dm = dml.into_shape(self.m.dim()).expect("must match.");
-        for (a, r, b, x) in izip!(
-            output.iter_mut(),
-            self.m.outer_iter(),
-            &self.bias,
-            &mut dbias
-        ) {
-            assert_eq!(r.dim(), input.dim());
-            let pa = r.dot(input) + b;
+        let mut dpa = Array::zeros(self.num_outputs());
+        debug_assert_eq!(input.dim(), self.num_inputs());
+        mat_vec_mul(output, &self.m, input);
+        for (i, (a, x)) in izip!(output.iter_mut(), &mut dpa).enumerate() {
+            let pa = *a + self.bias_at(i);
             *a = f(pa);
             *x = g(pa, *a);
         }
 
         // compute the gradient of the weights, with respect to the outputs
-        outer_product(&mut dm, &dbias, input); // theta'(pa) * input
+        outer_product(&mut dm, &dpa, input); // theta'(pa) * input
+        dpa
     }
 
+    /// Like `evaluate_onto_partial_g`, returning the output and
+    /// theta'(pa).
     pub fn evaluate_partial_g<T1>(
         &self,
         input: &ArrayBase<T1, Ix1>,
         partial_g: ArrayViewMut<f32, Ix1>,
-    ) -> Array1<f32>
+    ) -> (Array1<f32>, Array1<f32>)
     where
         T1: Data<Elem = f32>,
     {
-        let mut arr = Array::zeros(self.bias.dim());
-        self.evaluate_onto_partial_g(input, &mut arr, partial_g);
-        arr
+        let mut arr = Array::zeros(self.num_outputs());
+        let dpa = self.evaluate_onto_partial_g(input, &mut arr, partial_g);
+        (arr, dpa)
     }
 
     /// Complete the evaluation of the gradient, taking in the
-    /// gradient with respect to the outputs.
+    /// gradient with respect to the outputs, and theta'(pa) from
+    /// evaluate*_partial_g. Returns the gradient with respect to the
+    /// inputs.
     fn complete_g<T: Data<Elem = f32>>(
         &self,
         de_dout: &ArrayBase<T, Ix1>,
+        dpa: &Array1<f32>,
         g: ArrayViewMut<f32, Ix1>,
     ) -> Array1<f32> {
-        assert_eq!(de_dout.dim(), self.num_outputs());
+        debug_assert_eq!(de_dout.dim(), self.num_outputs());
 
         let (dml, mut dout_dbias) = g.split_at(Axis(0), self.m.len());
         let mut dout_dm = dml.into_shape(self.m.dim()).expect("must match.");
@@ -279,11 +454,14 @@ impl Layer {
             }
         }
 
-        // Finish gradient w/rt bias
-        dout_dbias *= de_dout;
+        // gradient w/rt the pre-activation, which is also w/rt the bias
+        let de_dpa = dpa * de_dout;
+        if self.bias.is_some() {
+            dout_dbias.assign(&de_dpa);
+        }
 
         let mut de_din = Array::zeros(self.num_inputs());
-        mat_t_vec_mul(&mut de_din, &self.m, de_dout);
+        mat_t_vec_mul(&mut de_din, &self.m, &de_dpa);
 
         de_din
     }
@@ -297,14 +475,17 @@ impl Layer {
         Zip::from(&mut self.m)
             .and(&dm)
             .apply(|a, da| *a += da * rate);
-        Zip::from(&mut self.bias)
-            .and(dbias)
-            .apply(|a, da| *a += da * rate);
+        if let Some(ref mut bias) = self.bias {
+            Zip::from(bias).and(dbias).apply(|a, da| *a += da * rate);
+        }
     }
 
     #[allow(unused)]
     pub fn dump(&self) {
-        println!("W:\n{}\nb\n{}\n", self.m, self.bias);
+        match self.bias {
+            Some(ref bias) => println!("W:\n{}\nb\n{}\n", self.m, bias),
+            None => println!("W:\n{}\n", self.m),
+        }
     }
 
     fn weights(&self, v: ArrayViewMut<f32, Ix1>) {
@@ -314,43 +495,158 @@ impl Layer {
         ml.assign(
             &ArrayView::from_shape(self.m.len(), self.m.as_slice().unwrap()).expect("must match"),
         );
-        bias.assign(&self.bias);
+        if let Some(ref b) = self.bias {
+            bias.assign(b);
+        }
+    }
+
+    fn set_weights(&mut self, v: ArrayView<f32, Ix1>) {
+        assert!(v.dim() == self.num_parameters());
+        let (ml, bias) = v.split_at(Axis(0), self.m.len());
+        let m = ml.into_shape(self.m.dim()).expect("must match");
+
+        self.m.assign(&m);
+        if let Some(ref mut b) = self.bias {
+            b.assign(&bias);
+        }
+    }
+}
+
+/// Reason a `NeuralNet` couldn't be built from its layer descriptions.
+/// Layers are indexed from 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetConstructionError {
+    /// No layers were given.
+    EmptyLayers,
+
+    /// The layer's inputs don't match the previous layer's outputs.
+    DimensionMismatch {
+        layer: usize,
+        expected: usize,
+        got: usize,
+    },
+
+    /// The layer has no inputs or no outputs.
+    ZeroWidth { layer: usize },
+}
+
+impl fmt::Display for NetConstructionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            NetConstructionError::EmptyLayers => write!(f, "network has no layers"),
+            NetConstructionError::DimensionMismatch {
+                layer,
+                expected,
+                got,
+            } => write!(
+                f,
+                "layer {} has {} inputs, but the previous layer has {} outputs",
+                layer, got, expected
+            ),
+            NetConstructionError::ZeroWidth { layer } => {
+                write!(f, "layer {} has no inputs or no outputs", layer)
+            }
+        }
+    }
+}
+
+impl error::Error for NetConstructionError {}
+
+/// Contribution of the leading part of an input to the first layer,
+/// from `NeuralNet::prefix_preactivation`.
+#[derive(Debug, Clone)]
+pub struct PrefixCache {
+    /// pre-activation of each output, including the bias
+    preact: Array1<f32>,
+
+    /// number of inputs covered
+    prefix_len: usize,
+}
+
+/// Summary of one layer's activations, for diagnosing dead or
+/// saturated units.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LayerStats {
+    /// Fraction of units whose gradient (almost) vanishes, as judged by
+    /// `ActivationFunction::is_saturated`.
+    pub frac_saturated: f32,
+    pub mean: f32,
+    pub std: f32,
+}
+
+impl LayerStats {
+    pub fn new(activations: &Array1<f32>, act: ActivationFunction) -> LayerStats {
+        let n = activations.len() as f32;
+        let saturated = activations.iter().filter(|&&f| act.is_saturated(f)).count();
+        let mean = activations.sum() / n;
+        let var = activations.iter().map(|f| (f - mean).powi(2)).sum::<f32>() / n;
+        LayerStats {
+            frac_saturated: saturated as f32 / n,
+            mean,
+            std: var.sqrt(),
+        }
     }
 }
 
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct NeuralNetworkParameters {
     pub learning_rate: f32,
 }
 
 /// Neural network
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct NeuralNet {
     layers: Vec<Layer>,
     param: NeuralNetworkParameters,
     current_rate: f32,
     ni: usize,
+
+    /// error-weighted gradients summed by `accumulate_gradient`, not
+    /// yet applied
+    #[cfg_attr(feature = "serde", serde(skip))]
+    accumulated: Option<Array1<f32>>,
 }
 
 impl NeuralNet {
-    pub fn new(layers: &[LayerDesc], lr: f32) -> Option<NeuralNet> {
+    /// Create a network from its layers, first to last, with the given
+    /// learning rate, or report why the layers don't fit together.
+    pub fn new(layers: &[LayerDesc], lr: f32) -> Result<NeuralNet, NetConstructionError> {
         // make sure the layers are valid
-        if layers
+        if layers.is_empty() {
+            return Err(NetConstructionError::EmptyLayers);
+        }
+        if let Some(layer) = layers
+            .iter()
+            .position(|d| d.num_inputs == 0 || d.num_outputs == 0)
+        {
+            return Err(NetConstructionError::ZeroWidth { layer });
+        }
+        if let Some((layer, (d1, d2))) = layers
             .iter()
             .tuple_windows::<(_, _)>()
-            .any(|(d1, d2)| d1.num_outputs != d2.num_inputs)
+            .enumerate()
+            .find(|(_, (d1, d2))| d1.num_outputs != d2.num_inputs)
         {
-            return None;
+            return Err(NetConstructionError::DimensionMismatch {
+                layer: layer + 1,
+                expected: d1.num_outputs,
+                got: d2.num_inputs,
+            });
         }
 
-        Some(NeuralNet {
+        Ok(NeuralNet {
             layers: layers.iter().map(Layer::from_desc).collect(),
             current_rate: lr,
             ni: 0,
+            accumulated: None,
             param: NeuralNetworkParameters { learning_rate: lr },
         })
     }
 
+    /// Split the network into the first `n` layers and the rest. Any
+    /// accumulated gradient is dropped.
     pub fn split_at(mut self, n: usize) -> (NeuralNet, NeuralNet) {
         let b = self.layers.split_off(n);
         (
@@ -359,12 +655,14 @@ impl NeuralNet {
                 param: self.param.clone(),
                 current_rate: self.current_rate,
                 ni: self.ni,
+                accumulated: None,
             },
             NeuralNet {
                 layers: b,
                 param: self.param,
                 current_rate: self.current_rate,
                 ni: self.ni,
+                accumulated: None,
             },
         )
     }
@@ -383,6 +681,22 @@ impl NeuralNet {
         self.layers.iter().map(|layer| layer.num_parameters()).sum()
     }
 
+    pub fn num_layers(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Return layer `i`'s `(num_outputs, num_inputs)` weight matrix
+    /// and its bias, if it has one, or `None` if there is no such
+    /// layer. Layers are numbered from 0, first to last.
+    pub fn layer_weights(
+        &self,
+        i: usize,
+    ) -> Option<(ArrayView2<'_, f32>, Option<ArrayView1<'_, f32>>)> {
+        self.layers
+            .get(i)
+            .map(|layer| (layer.m.view(), layer.bias.as_ref().map(|b| b.view())))
+    }
+
     /// Flattened version of all weights, by layer
     pub fn weights(&self) -> Array<f32, Ix1> {
         let mut arr = Array::zeros(self.num_parameters());
@@ -394,6 +708,26 @@ impl NeuralNet {
         arr
     }
 
+    /// Replace all weights, in the same layout as `weights`.
+    pub fn set_weights(&mut self, w: ArrayView<f32, Ix1>) {
+        assert_eq!(w.dim(), self.num_parameters());
+        self.layers.iter_mut().fold(w, |view, layer| {
+            let (a, b) = view.split_at(Axis(0), layer.num_parameters());
+            layer.set_weights(a);
+            b
+        });
+    }
+
+    /// Re-randomize every layer's weights, keeping the architecture,
+    /// and restart the learning-rate schedule.
+    pub fn reinitialize(&mut self) {
+        for layer in &mut self.layers {
+            layer.reinitialize();
+        }
+        self.ni = 0;
+        self.current_rate = self.param.learning_rate;
+    }
+
     // pub fn dump(&self)  {
     //     for l in &self.layers {
     //         l.dump();
@@ -405,6 +739,9 @@ impl NeuralNet {
     }
 
     /// Feed the input forward through the neural networks.
+    ///
+    /// Evaluation is side-effect free: neither the weights nor the
+    /// learning-rate schedule are affected.
     pub fn evaluate<T1>(&self, input: &ArrayBase<T1, Ix1>) -> Array1<f32>
     where
         T1: Data<Elem = f32>,
@@ -415,7 +752,73 @@ impl NeuralNet {
             .fold(input.to_owned(), |x, layer| layer.evaluate(&x))
     }
 
-    /// Evaluate, and internally store the gradient.
+    /// Like `evaluate`, also returning each layer's post-activation
+    /// output, in order. The last entry is the final output.
+    pub fn evaluate_with_activations<T1>(
+        &self,
+        input: &ArrayBase<T1, Ix1>,
+    ) -> (Array1<f32>, Vec<Array1<f32>>)
+    where
+        T1: Data<Elem = f32>,
+    {
+        assert_eq!(input.dim(), self.layers[0].num_inputs());
+        let mut activations: Vec<Array1<f32>> = Vec::with_capacity(self.layers.len());
+        for layer in &self.layers {
+            let a = match activations.last() {
+                Some(x) => layer.evaluate(x),
+                None => layer.evaluate(input),
+            };
+            activations.push(a);
+        }
+        let output = activations.last().expect("at least one layer").clone();
+        (output, activations)
+    }
+
+    /// Per-layer activation statistics for the input.
+    pub fn layer_stats<T1>(&self, input: &ArrayBase<T1, Ix1>) -> Vec<LayerStats>
+    where
+        T1: Data<Elem = f32>,
+    {
+        let (_, activations) = self.evaluate_with_activations(input);
+        izip!(&self.layers, &activations)
+            .map(|(layer, a)| LayerStats::new(a, layer.act))
+            .collect()
+    }
+
+    /// Precompute the contribution of the leading `prefix` inputs, so
+    /// that inputs sharing the prefix can be evaluated with
+    /// `evaluate_with_prefix` without repeating the work.
+    pub fn prefix_preactivation<T1>(&self, prefix: &ArrayBase<T1, Ix1>) -> PrefixCache
+    where
+        T1: Data<Elem = f32>,
+    {
+        self.layers[0].prefix_preactivation(prefix)
+    }
+
+    /// Evaluate the input formed by the prefix given to
+    /// `prefix_preactivation` followed by `suffix`.
+    ///
+    /// Matches `evaluate` on the full input, up to floating-point
+    /// rounding.
+    pub fn evaluate_with_prefix<T1>(
+        &self,
+        prefix_pa: &PrefixCache,
+        suffix: &ArrayBase<T1, Ix1>,
+    ) -> Array1<f32>
+    where
+        T1: Data<Elem = f32>,
+    {
+        let first = self.layers[0].evaluate_suffix(prefix_pa, suffix);
+        self.layers[1..]
+            .iter()
+            .fold(first, |x, layer| layer.evaluate(&x))
+    }
+
+    /// Evaluate, writing the gradient of the output with respect to
+    /// the weights into `gradient`.
+    ///
+    /// Like `evaluate`, this does not modify the network. Only
+    /// `update_weights` advances the learning-rate schedule.
     pub fn evaluate_with_gradient<T1>(
         &self,
         input: &ArrayBase<T1, Ix1>,
@@ -427,12 +830,15 @@ impl NeuralNet {
         assert_eq!(input.dim(), self.layers[0].num_inputs());
         gradient.fill(0.0);
 
+        let mut dpas = Vec::with_capacity(self.layers.len());
         let output = self
             .layers
             .iter()
             .fold((input.to_owned(), gradient.view_mut()), |(x, gv), layer| {
                 let (g, ogv) = gv.split_at(Axis(0), layer.num_parameters());
-                (layer.evaluate_partial_g(&x, g), ogv)
+                let (out, dpa) = layer.evaluate_partial_g(&x, g);
+                dpas.push(dpa);
+                (out, ogv)
             })
             .0;
 
@@ -441,11 +847,12 @@ impl NeuralNet {
         let de_dout = Array::from_elem(self.num_outputs(), 1.0);
         self.layers
             .iter()
+            .zip(&dpas)
             .rev()
-            .fold((de_dout, gradient), |(x, gv), layer| {
+            .fold((de_dout, gradient), |(x, gv), (layer, dpa)| {
                 let split_loc = gv.len() - layer.num_parameters();
                 let (ogv, g) = gv.split_at(Axis(0), split_loc);
-                (layer.complete_g(&x, g), ogv)
+                (layer.complete_g(&x, dpa, g), ogv)
             });
 
         output
@@ -453,20 +860,380 @@ impl NeuralNet {
 
     /// Move all weights by a factor of alpha * e * grad(x)
     pub fn update_weights(&mut self, err: f32, w: ArrayView<f32, Ix1>) {
+        let lre = self.next_step_rate() * err;
         self.ni += 1;
-        let lre = self.param.learning_rate * err * 1.0 / (1.0 + 0.001 * (self.ni as f32));
         self.layers.iter_mut().fold(w, |weights, layer| {
             let (g, mw) = weights.split_at(Axis(0), layer.num_parameters());
             layer.weight_step(lre, g);
             mw
         });
     }
+
+    /// Learning rate the next `update_weights` call will use, decayed
+    /// by the number of updates so far.
+    fn next_step_rate(&self) -> f32 {
+        self.param.learning_rate / (1.0 + 0.001 * (self.ni + 1) as f32)
+    }
+
+    /// Add `err * grad` to the gradient accumulated for a minibatch,
+    /// without changing the weights. `grad` has the layout of
+    /// `evaluate_with_gradient`.
+    pub fn accumulate_gradient(&mut self, err: f32, grad: ArrayView<f32, Ix1>) {
+        assert_eq!(grad.dim(), self.num_parameters());
+        let n = self.num_parameters();
+        self.accumulated
+            .get_or_insert_with(|| Array::zeros(n))
+            .scaled_add(err, &grad);
+    }
+
+    /// Apply the gradient accumulated since the last call, averaged
+    /// over `batch_size` samples, as a single `update_weights` step,
+    /// and clear it. Does nothing if no gradient was accumulated.
+    pub fn apply_accumulated(&mut self, batch_size: usize) {
+        assert!(batch_size > 0, "batch must not be empty");
+        if let Some(acc) = self.accumulated.take() {
+            self.update_weights(1.0 / batch_size as f32, acc.view());
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::ActivationFunction;
-    use ndarray::Array;
+    use super::{
+        mat_t_vec_mul, mat_vec_mul, ActivationFunction, InitScheme, Layer, LayerDesc, LayerStats,
+        NetConstructionError, NeuralNet,
+    };
+    use ndarray::{s, Array, Array2};
+
+    #[test]
+    fn test_evaluate_with_activations() {
+        let net = NeuralNet::new(
+            &[
+                LayerDesc::new(6, 5, ActivationFunction::ReLU),
+                LayerDesc::new(5, 4, ActivationFunction::Sigmoid),
+                LayerDesc::new(4, 1, ActivationFunction::Linear),
+            ],
+            0.1,
+        )
+        .unwrap();
+        let input = Array::linspace(-1.0, 1.0, 6);
+
+        let (output, activations) = net.evaluate_with_activations(&input);
+        assert_eq!(output, net.evaluate(&input));
+        let dims: Vec<_> = activations.iter().map(|a| a.dim()).collect();
+        assert_eq!(dims, vec![5, 4, 1]);
+        assert_eq!(activations[2], output);
+        assert!(activations[0].iter().all(|&x| x >= 0.0));
+
+        let stats = net.layer_stats(&input);
+        assert_eq!(stats.len(), 3);
+        assert_eq!(stats[2].frac_saturated, 0.0);
+
+        let a = Array::from_vec(vec![0.0, 0.0, 1.0, 3.0]);
+        let st = LayerStats::new(&a, ActivationFunction::ReLU);
+        assert_eq!(st.frac_saturated, 0.5);
+        assert_eq!(st.mean, 1.0);
+        assert!((st.std - 1.5f32.sqrt()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_init_schemes() {
+        let (n_out, n_in) = (64, 128);
+        let std = |w: &Array2<f32>| (w.iter().map(|x| x * x).sum::<f32>() / w.len() as f32).sqrt();
+
+        let w = InitScheme::SqrtOutputs.weights(n_out, n_in);
+        assert!((std(&w) - 8.0).abs() < 0.5);
+        let w = InitScheme::Normal { std: 0.5 }.weights(n_out, n_in);
+        assert!((std(&w) - 0.5).abs() < 0.05);
+        let w = InitScheme::Xavier.weights(n_out, n_in);
+        assert!((std(&w) - (2.0f32 / 192.0).sqrt()).abs() < 0.01);
+        let w = InitScheme::He.weights(n_out, n_in);
+        assert!((std(&w) - (2.0f32 / 128.0).sqrt()).abs() < 0.01);
+
+        let w = InitScheme::Uniform { range: 0.1 }.weights(n_out, n_in);
+        assert_eq!(w.dim(), (n_out, n_in));
+        assert!(w.iter().all(|x| x.abs() <= 0.1));
+
+        let desc = LayerDesc::new(3, 2, ActivationFunction::ReLU);
+        assert_eq!(desc.init, InitScheme::SqrtOutputs);
+        assert_eq!(desc.with_init(InitScheme::He).init, InitScheme::He);
+    }
+
+    #[test]
+    fn test_reinitialize() {
+        let layers = [
+            LayerDesc::new(3, 2, ActivationFunction::SymmetricSigmoid),
+            LayerDesc::new(2, 1, ActivationFunction::Linear),
+        ];
+        let mut nn = NeuralNet::new(&layers, 0.1).unwrap();
+        let input = Array::from_vec(vec![0.5, -1.0, 0.25]);
+        let mut grad = Array::zeros(nn.num_parameters());
+        for _ in 0..5 {
+            nn.evaluate_with_gradient(&input, grad.view_mut());
+            nn.update_weights(1.0, grad.view());
+        }
+        let before = nn.weights();
+        assert_eq!(nn.ni, 5);
+
+        nn.reinitialize();
+        assert_eq!(nn.ni, 0);
+        assert_eq!(nn.current_rate, 0.1);
+        assert_eq!(nn.num_parameters(), before.len());
+        assert_ne!(nn.weights(), before);
+
+        // biases are stored after each layer's weight matrix
+        let w = nn.weights();
+        assert!(w.slice(s![6..8]).iter().all(|&b| b == 0.0));
+        assert_eq!(w[10], 0.0);
+    }
+
+    #[test]
+    fn test_gradient() {
+        let nn = NeuralNet::new(
+            &[
+                LayerDesc::new(4, 3, ActivationFunction::SymmetricSigmoid).with_bias(false),
+                LayerDesc::new(3, 2, ActivationFunction::Sigmoid),
+                LayerDesc::new(2, 1, ActivationFunction::Linear).with_bias(false),
+            ],
+            0.1,
+        )
+        .unwrap();
+        assert_eq!(nn.num_parameters(), 12 + (6 + 2) + 2);
+
+        let input = Array::linspace(-1.0, 1.0, 4);
+        let mut grad = Array::zeros(nn.num_parameters());
+        let out = nn.evaluate_with_gradient(&input, grad.view_mut());
+        assert_eq!(out, nn.evaluate(&input));
+
+        // compare against central differences in each parameter
+        let w = nn.weights();
+        let mut perturbed = nn.clone();
+        for i in 0..w.len() {
+            let mut wp = w.clone();
+            wp[i] += 1e-2;
+            perturbed.set_weights(wp.view());
+            let hi = perturbed.evaluate(&input)[0];
+            wp[i] -= 2e-2;
+            perturbed.set_weights(wp.view());
+            let lo = perturbed.evaluate(&input)[0];
+
+            let g_est = (hi - lo) / 2e-2;
+            assert!((g_est - grad[i]).abs() < 1e-3, "parameter {}", i);
+        }
+
+        // a step along the gradient moves the bias-free layout consistently
+        let mut stepped = nn.clone();
+        let rate = stepped.next_step_rate();
+        stepped.update_weights(1.0, grad.view());
+        let moved = stepped.weights() - &w;
+        assert!(moved
+            .iter()
+            .zip(grad.iter())
+            .all(|(m, g)| (m - rate * g).abs() < 1e-5));
+    }
+
+    #[test]
+    fn test_accumulate_gradient() {
+        let net = NeuralNet::new(
+            &[
+                LayerDesc::new(3, 4, ActivationFunction::SymmetricSigmoid),
+                LayerDesc::new(4, 1, ActivationFunction::Linear),
+            ],
+            0.1,
+        )
+        .unwrap();
+        let inputs = [
+            Array::from_vec(vec![1.0, 0.5, -1.0]),
+            Array::from_vec(vec![-0.5, 0.0, 2.0]),
+        ];
+        let errs = [0.3, -0.7];
+        let grads: Vec<_> = inputs
+            .iter()
+            .map(|x| {
+                let mut g = Array::zeros(net.num_parameters());
+                net.evaluate_with_gradient(x, g.view_mut());
+                g
+            })
+            .collect();
+
+        let mut batched = net.clone();
+        for (e, g) in errs.iter().zip(&grads) {
+            batched.accumulate_gradient(*e, g.view());
+        }
+        assert_eq!(batched.weights(), net.weights());
+        batched.apply_accumulated(2);
+
+        // a single step along the mean of the error-weighted gradients
+        let mut expected = net.clone();
+        let mean = (&grads[0] * errs[0] + &grads[1] * errs[1]) / 2.0;
+        expected.update_weights(1.0, mean.view());
+        for (a, b) in batched.weights().iter().zip(expected.weights().iter()) {
+            assert!((a - b).abs() < 1e-6);
+        }
+
+        // the buffer is cleared
+        let w = batched.weights();
+        batched.apply_accumulated(2);
+        assert_eq!(batched.weights(), w);
+    }
+
+    #[test]
+    fn test_layer_weights() {
+        let net = NeuralNet::new(
+            &[
+                LayerDesc::new(3, 4, ActivationFunction::ReLU),
+                LayerDesc::new(4, 1, ActivationFunction::Linear).with_bias(false),
+            ],
+            0.1,
+        )
+        .unwrap();
+        assert_eq!(net.num_layers(), 2);
+        assert!(net.layer_weights(2).is_none());
+
+        let (m0, b0) = net.layer_weights(0).unwrap();
+        let (m1, b1) = net.layer_weights(1).unwrap();
+        assert_eq!(m0.dim(), (4, 3));
+        assert_eq!(b0.map(|b| b.dim()), Some(4));
+        assert_eq!(m1.dim(), (1, 4));
+        assert!(b1.is_none());
+
+        // the views follow the layout of `weights`
+        let w = net.weights();
+        assert_eq!(w.slice(s![..12]), Array::from_iter(m0.iter().cloned()));
+        assert_eq!(w.slice(s![12..16]), b0.unwrap());
+        assert_eq!(w.slice(s![16..]), Array::from_iter(m1.iter().cloned()));
+    }
+
+    #[test]
+    fn test_construction_errors() {
+        let f = ActivationFunction::Linear;
+        assert_eq!(
+            NeuralNet::new(&[], 0.1).err(),
+            Some(NetConstructionError::EmptyLayers)
+        );
+        assert_eq!(
+            NeuralNet::new(&[LayerDesc::new(3, 2, f), LayerDesc::new(3, 1, f)], 0.1).err(),
+            Some(NetConstructionError::DimensionMismatch {
+                layer: 1,
+                expected: 2,
+                got: 3
+            })
+        );
+        let err = NeuralNet::new(&[LayerDesc::new(3, 0, f), LayerDesc::new(0, 1, f)], 0.1)
+            .err()
+            .unwrap();
+        assert_eq!(err, NetConstructionError::ZeroWidth { layer: 0 });
+        assert_eq!(err.to_string(), "layer 0 has no inputs or no outputs");
+    }
+
+    #[test]
+    fn test_layer_evaluate() {
+        // goes through `mat_vec_mul`, so this also covers the `blas` path
+        let desc = LayerDesc::new(5, 7, ActivationFunction::SymmetricSigmoid);
+        let layer = Layer::from_desc(&desc);
+        let x = Array::from_shape_fn(5, |j| (j as f32).cos());
+        let f = ActivationFunction::SymmetricSigmoid.af();
+
+        let out = layer.evaluate(&x);
+        let mut partial_g = Array::zeros(layer.num_parameters());
+        let (out_g, _) = layer.evaluate_partial_g(&x, partial_g.view_mut());
+        for (i, r) in layer.m.outer_iter().enumerate() {
+            let expected = f((0..5).map(|j| r[j] * x[j]).sum::<f32>() + layer.bias_at(i));
+            assert!((out[i] - expected).abs() < 1e-5);
+            assert!((out_g[i] - expected).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_mat_vec_mul() {
+        let a = Array::from_shape_fn((7, 5), |(i, j)| ((i * 5 + j) as f32).sin());
+        let x = Array::from_shape_fn(5, |j| (j as f32).cos());
+        let y = Array::from_shape_fn(7, |i| (i as f32 * 0.5).cos());
+
+        let mut ax = Array::zeros(7);
+        mat_vec_mul(&mut ax, &a, &x);
+        for i in 0..7 {
+            let expected: f32 = (0..5).map(|j| a[[i, j]] * x[j]).sum();
+            assert!((ax[i] - expected).abs() < 1e-5);
+        }
+
+        let mut aty = Array::zeros(5);
+        mat_t_vec_mul(&mut aty, &a, &y);
+        for j in 0..5 {
+            let expected: f32 = (0..7).map(|i| a[[i, j]] * y[i]).sum();
+            assert!((aty[j] - expected).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_set_weights() {
+        let layers = [
+            LayerDesc::new(3, 2, ActivationFunction::Linear),
+            LayerDesc::new(2, 1, ActivationFunction::Linear),
+        ];
+        let a = NeuralNet::new(&layers, 0.1).unwrap();
+        let mut b = NeuralNet::new(&layers, 0.1).unwrap();
+
+        b.set_weights(a.weights().view());
+        assert_eq!(a.weights(), b.weights());
+
+        let input = Array::linspace(-1.0, 1.0, 3);
+        assert_eq!(a.evaluate(&input), b.evaluate(&input));
+    }
+
+    #[test]
+    fn test_evaluate_with_prefix() {
+        let nn = NeuralNet::new(
+            &[
+                LayerDesc::new(7, 4, ActivationFunction::SymmetricSigmoid),
+                LayerDesc::new(4, 1, ActivationFunction::Sigmoid),
+            ],
+            0.1,
+        )
+        .unwrap();
+
+        let input = Array::linspace(-1.0, 1.0, 7);
+        let full = nn.evaluate(&input);
+        for k in 0..8 {
+            let pa = nn.prefix_preactivation(&input.slice(s![..k]));
+            let out = nn.evaluate_with_prefix(&pa, &input.slice(s![k..]));
+            assert!((out[0] - full[0]).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "whole input")]
+    fn test_evaluate_with_prefix_mismatch() {
+        let nn = NeuralNet::new(&[LayerDesc::new(7, 1, ActivationFunction::Sigmoid)], 0.1).unwrap();
+        let input = Array::linspace(-1.0, 1.0, 7);
+        let pa = nn.prefix_preactivation(&input.slice(s![..3]));
+        nn.evaluate_with_prefix(&pa, &input.slice(s![2..]));
+    }
+
+    #[test]
+    fn test_evaluate_is_pure() {
+        let nn = NeuralNet::new(
+            &[
+                LayerDesc::new(5, 3, ActivationFunction::SymmetricSigmoid),
+                LayerDesc::new(3, 1, ActivationFunction::Sigmoid),
+            ],
+            0.1,
+        )
+        .unwrap();
+
+        let w = nn.weights();
+        let input = Array::linspace(-1.0, 1.0, 5);
+        let mut grad = Array::zeros(nn.num_parameters());
+        let out = nn.evaluate(&input);
+        for _ in 0..1000 {
+            assert_eq!(nn.evaluate(&input), out);
+            assert_eq!(nn.evaluate_with_gradient(&input, grad.view_mut()), out);
+        }
+
+        assert_eq!(nn.weights(), w);
+        assert_eq!(nn.ni, 0);
+        assert_eq!(nn.current_rate, 0.1);
+    }
 
     #[test]
     fn test_linear() {
@@ -1,13 +1,301 @@
-use crate::germanwhist::{self, Action, ActionError, PlayerState, Round, ScoringRules};
+use crate::germanwhist::{self, Action, ActionError, GameEvent, PlayerState, Round, ScoringRules};
 
 use crate::learning::model::{LearningModel, LearningModelError};
 use ndarray::prelude::*;
-use rand::{thread_rng, Rng};
+use ndarray::Zip;
+use rand::{thread_rng, Rng, SeedableRng, StdRng};
+use std::error;
+use std::fmt;
+use std::mem;
 
+/// How game results are turned into rewards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RewardEncoding {
+    /// A win is worth 1, a draw 0.5 and a loss 0. Values lie in 0.0 ->
+    /// 1.0, so the model should end in a `Sigmoid` output.
+    #[default]
+    ZeroOne,
+
+    /// A win is worth 1, a draw 0 and a loss -1. Values lie in -1.0 ->
+    /// 1.0, so the model should end in a symmetric output such as
+    /// `SymmetricSigmoid`.
+    Symmetric,
+}
+
+impl RewardEncoding {
+    /// Reward for the player in `seat` at the end of a game won by
+    /// `winner`, or drawn if `None`.
+    pub fn terminal(&self, seat: usize, winner: Option<usize>) -> f32 {
+        let zero_one = match winner {
+            Some(w) if w == seat => 1.0,
+            Some(_) => 0.0,
+            None => 0.5,
+        };
+        match *self {
+            RewardEncoding::ZeroOne => zero_one,
+            RewardEncoding::Symmetric => 2.0 * zero_one - 1.0,
+        }
+    }
+
+    /// Scale an intermediate reward, given on the `ZeroOne` scale, to
+    /// this encoding.
+    pub fn intermediate(&self, reward: f32) -> f32 {
+        match *self {
+            RewardEncoding::ZeroOne => reward,
+            RewardEncoding::Symmetric => 2.0 * reward,
+        }
+    }
+}
+
+/// How the eligibility trace takes in each new gradient, after
+/// decaying by `lambda * gamma`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TraceMode {
+    /// Add the gradient to the trace.
+    #[default]
+    Accumulating,
+
+    /// Replace each element of the trace by the gradient's, where the
+    /// gradient is non-zero.
+    Replacing,
+}
+
+/// Limit applied to the eligibility trace after each update.
+///
+/// Limits are built with `max_abs` and `l2_norm`, which check that
+/// the limit is positive and finite.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TraceClip {
+    /// The trace is unbounded.
+    #[default]
+    None,
+
+    /// Clamp each element to at most the given absolute value.
+    #[non_exhaustive]
+    MaxAbs(f32),
+
+    /// Scale the trace down so its L2 norm is at most the given value.
+    #[non_exhaustive]
+    L2Norm(f32),
+}
+
+impl TraceClip {
+    /// Clamp each element of the trace to at most `limit` in absolute
+    /// value.
+    pub fn max_abs(limit: f32) -> Result<TraceClip, ParameterError> {
+        Self::check_limit(limit).map(TraceClip::MaxAbs)
+    }
+
+    /// Scale the trace down so its L2 norm is at most `limit`.
+    pub fn l2_norm(limit: f32) -> Result<TraceClip, ParameterError> {
+        Self::check_limit(limit).map(TraceClip::L2Norm)
+    }
+
+    /// Check the limit, e.g. of a clip read from a checkpoint.
+    fn validate(&self) -> Result<(), ParameterError> {
+        match *self {
+            TraceClip::None => Ok(()),
+            TraceClip::MaxAbs(limit) | TraceClip::L2Norm(limit) => {
+                Self::check_limit(limit).map(|_| ())
+            }
+        }
+    }
+
+    fn check_limit(limit: f32) -> Result<f32, ParameterError> {
+        if limit > 0.0 && limit.is_finite() {
+            Ok(limit)
+        } else {
+            Err(ParameterError::TraceClip(limit))
+        }
+    }
+}
+
+/// Reason a training parameter can't be used.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParameterError {
+    /// A trace clip limit must be positive and finite.
+    TraceClip(f32),
+
+    /// A Boltzmann temperature must be positive and finite.
+    Temperature(f32),
+}
+
+impl fmt::Display for ParameterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParameterError::TraceClip(limit) => {
+                write!(f, "trace clip limit {} is not positive and finite", limit)
+            }
+            ParameterError::Temperature(t) => {
+                write!(f, "temperature {} is not positive and finite", t)
+            }
+        }
+    }
+}
+
+impl error::Error for ParameterError {}
+
+/// How the training policy picks actions other than the greedy one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ExplorationStrategy {
+    /// With probability `eps`, a uniformly random action; otherwise
+    /// the greedy action.
+    EpsilonGreedy { eps: f32 },
+
+    /// Sample actions with probability proportional to
+    /// `exp(Q / temperature)`, so better actions are explored more
+    /// often. Low temperatures approach the greedy policy, and high
+    /// ones a uniformly random one. Built with `boltzmann`, which
+    /// checks that `temperature` is positive and finite.
+    #[non_exhaustive]
+    Boltzmann { temperature: f32 },
+}
+
+impl Default for ExplorationStrategy {
+    fn default() -> Self {
+        ExplorationStrategy::EpsilonGreedy { eps: 0.01 }
+    }
+}
+
+impl ExplorationStrategy {
+    /// Sample actions with probability proportional to
+    /// `exp(Q / temperature)`.
+    pub fn boltzmann(temperature: f32) -> Result<ExplorationStrategy, ParameterError> {
+        if temperature > 0.0 && temperature.is_finite() {
+            Ok(ExplorationStrategy::Boltzmann { temperature })
+        } else {
+            Err(ParameterError::Temperature(temperature))
+        }
+    }
+
+    /// Check the parameter, e.g. of a strategy read from a checkpoint.
+    fn validate(&self) -> Result<(), ParameterError> {
+        match *self {
+            ExplorationStrategy::EpsilonGreedy { .. } => Ok(()),
+            ExplorationStrategy::Boltzmann { temperature } => {
+                Self::boltzmann(temperature).map(|_| ())
+            }
+        }
+    }
+
+    /// Return a short name for the strategy, without its parameter.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            ExplorationStrategy::EpsilonGreedy { .. } => "epsilon_greedy",
+            ExplorationStrategy::Boltzmann { .. } => "boltzmann",
+        }
+    }
+
+    /// Return the strategy's parameter: `eps` or `temperature`.
+    pub fn param(&self) -> f32 {
+        match *self {
+            ExplorationStrategy::EpsilonGreedy { eps } => eps,
+            ExplorationStrategy::Boltzmann { temperature } => temperature,
+        }
+    }
+}
+
+impl fmt::Display for ExplorationStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ExplorationStrategy::EpsilonGreedy { eps } => write!(f, "eps={}", eps),
+            ExplorationStrategy::Boltzmann { temperature } => {
+                write!(f, "temperature={}", temperature)
+            }
+        }
+    }
+}
+
+/// Sample one of the actions with probability proportional to
+/// `exp(Q / temperature)`.
+fn boltzmann_choice<R: Rng>(values: &[(Action, f32)], temperature: f32, rng: &mut R) -> Action {
+    debug_assert!(temperature > 0.0);
+    // shift by the highest value, so the exponentials can't overflow
+    let max = values.iter().map(|&(_, q)| q).fold(f32::MIN, f32::max);
+    let weights: Vec<f32> = values
+        .iter()
+        .map(|&(_, q)| ((q - max) / temperature).exp())
+        .collect();
+
+    let mut r = rng.next_f32() * weights.iter().sum::<f32>();
+    for (&(a, _), w) in values.iter().zip(&weights) {
+        if r < *w {
+            return a;
+        }
+        r -= w;
+    }
+    // only reached through rounding
+    values
+        .last()
+        .expect("must have positive number of actions")
+        .0
+}
+
+/// Decay the trace, take in the gradient, and clip the result.
+fn update_trace(
+    e_trace: &mut Array1<f32>,
+    grad: &Array1<f32>,
+    decay: f32,
+    mode: TraceMode,
+    clip: TraceClip,
+) {
+    *e_trace *= decay;
+    match mode {
+        TraceMode::Accumulating => *e_trace += grad,
+        TraceMode::Replacing => Zip::from(e_trace.view_mut()).and(grad).apply(|e, &g| {
+            if g != 0.0 {
+                *e = g;
+            }
+        }),
+    }
+
+    match clip {
+        TraceClip::None => (),
+        TraceClip::MaxAbs(m) => e_trace.mapv_inplace(|e| e.clamp(-m, m)),
+        TraceClip::L2Norm(m) => {
+            let norm = e_trace.dot(e_trace).sqrt();
+            if norm > m {
+                *e_trace *= m / norm;
+            }
+        }
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SarsaLambdaParameters {
     lambda: f32,
     gamma: f32,
-    eps: f32,
+    #[cfg_attr(
+        feature = "serde",
+        serde(alias = "eps", deserialize_with = "deserialize_exploration")
+    )]
+    exploration: ExplorationStrategy,
+    reward_encoding: RewardEncoding,
+    trace_mode: TraceMode,
+    trace_clip: TraceClip,
+}
+
+/// Read an `ExplorationStrategy`, or a bare `eps` as saved before
+/// other strategies existed, which means epsilon-greedy.
+#[cfg(feature = "serde")]
+fn deserialize_exploration<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<ExplorationStrategy, D::Error> {
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum Saved {
+        Eps(f32),
+        Strategy(ExplorationStrategy),
+    }
+
+    Ok(match serde::Deserialize::deserialize(deserializer)? {
+        Saved::Eps(eps) => ExplorationStrategy::EpsilonGreedy { eps },
+        Saved::Strategy(strategy) => strategy,
+    })
 }
 
 impl Default for SarsaLambdaParameters {
@@ -15,14 +303,74 @@ impl Default for SarsaLambdaParameters {
         SarsaLambdaParameters {
             gamma: 1.0,
             lambda: 0.8,
-            eps: 0.01,
+            exploration: ExplorationStrategy::default(),
+            reward_encoding: RewardEncoding::default(),
+            trace_mode: TraceMode::default(),
+            trace_clip: TraceClip::default(),
         }
     }
 }
+
+impl SarsaLambdaParameters {
+    /// Check the parameters that can't be checked when they're set,
+    /// e.g. when read from a checkpoint.
+    fn validate(&self) -> Result<(), ParameterError> {
+        self.exploration.validate()?;
+        self.trace_clip.validate()
+    }
+
+    /// Use the given strategy for exploring during training.
+    pub fn with_exploration(mut self, exploration: ExplorationStrategy) -> Self {
+        self.exploration = exploration;
+        self
+    }
+
+    /// Use the given encoding for game results and intermediate
+    /// rewards.
+    pub fn with_reward_encoding(mut self, reward_encoding: RewardEncoding) -> Self {
+        self.reward_encoding = reward_encoding;
+        self
+    }
+
+    /// Use the given rule for adding gradients to the eligibility
+    /// trace.
+    pub fn with_trace_mode(mut self, trace_mode: TraceMode) -> Self {
+        self.trace_mode = trace_mode;
+        self
+    }
+
+    /// Limit the eligibility trace after each update.
+    pub fn with_trace_clip(mut self, trace_clip: TraceClip) -> Self {
+        self.trace_clip = trace_clip;
+        self
+    }
+}
+/// Intermediate reward for a player, given an event and the player's
+/// state before the event is applied.
+pub type RewardFn = Box<dyn Fn(&GameEvent, &PlayerState) -> f32>;
+
+/// A single decision by one player, recorded for offline training.
+#[derive(Debug, Clone)]
+pub struct Transition {
+    /// state-action vector of the action taken
+    pub state_action: Array1<f32>,
+
+    /// reward received before the player's next decision, including
+    /// the game result if the game ended first
+    pub reward: f32,
+
+    /// state-action vectors for each legal action at the player's next
+    /// decision; empty if the game ended first
+    pub next_state_actions: Vec<Array1<f32>>,
+}
+
 pub struct SarsaPlayer {
     pub state: PlayerState,
     e_trace: Array<f32, Ix1>,
     last_q: f32,
+
+    /// reward accumulated since the player's last update
+    pending_reward: f32,
 }
 
 impl SarsaPlayer {
@@ -33,21 +381,22 @@ impl SarsaPlayer {
             state,
             e_trace,
             last_q: 0.0,
+            pending_reward: 0.0,
         }
     }
 
     /// Choose an epsilon-greedy action.
-    fn epsilon_greedy_action<M: LearningModel>(
+    fn epsilon_greedy_action<M: LearningModel, R: Rng>(
         &self,
         model: &M,
         eps: f32,
         actions: &[Action],
         mut sa: ArrayViewMut<f32, Ix1>,
+        rng: &mut R,
     ) -> Action {
         assert_eq!(sa.dim(), PlayerState::state_action_size());
 
         // epsilon-greedy state-choosing
-        let mut rng = thread_rng();
         let r = rng.next_f32();
 
         // choose a random action with probability epsilon
@@ -64,26 +413,100 @@ impl SarsaPlayer {
         }
     }
 
-    /// Fill in the state action input `sa` most beneficial action of those provided in the
-    /// current state, returning the Q-value at that state.
-    pub fn greedy_action<M: LearningModel>(
+    /// Choose an action by the exploration strategy, writing its
+    /// state-action input to `sa`. Under epsilon-greedy exploration,
+    /// the state portion is only written for greedy choices.
+    fn exploring_action<M: LearningModel, R: Rng>(
         &self,
         model: &M,
+        exploration: ExplorationStrategy,
         actions: &[Action],
         mut sa: ArrayViewMut<f32, Ix1>,
+        rng: &mut R,
     ) -> Action {
-        let (max_action, _) = actions
-            .iter()
-            .zip(actions.iter().map(|a| {
+        match exploration {
+            ExplorationStrategy::EpsilonGreedy { eps } => {
+                self.epsilon_greedy_action(model, eps, actions, sa, rng)
+            }
+            ExplorationStrategy::Boltzmann { temperature } => {
+                assert_eq!(sa.dim(), PlayerState::state_action_size());
+                let values = self.action_values(model, actions);
+                let action = boltzmann_choice(&values, temperature, rng);
                 self.state
-                    .state_action_vector(sa.view_mut(), false, Some(a));
-                model.evaluate_q(&sa.view())
-            }))
-            .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
-            .expect("action list should not be empty");
+                    .state_action_vector(sa.view_mut(), false, Some(&action));
+                action
+            }
+        }
+    }
+
+    /// Return each of the actions paired with its Q-value in the
+    /// current state, in the order given.
+    pub fn action_values<M: LearningModel>(
+        &self,
+        model: &M,
+        actions: &[Action],
+    ) -> Vec<(Action, f32)> {
+        // The state portion of the input is shared by every action, so
+        // only evaluate it once.
+        let prefix = model.prepare_prefix(&self.state.state_vector_cached().view());
+        let mut action_v = Array::zeros(PlayerState::action_size());
+
+        actions
+            .iter()
+            .map(|a| {
+                self.state.action_vector(a, &mut action_v.view_mut());
+                (*a, model.evaluate_q_with_prefix(&prefix, &action_v.view()))
+            })
+            .collect()
+    }
+
+    /// Choose the most beneficial of the actions provided in the
+    /// current state, returning it with its Q-value.
+    ///
+    /// `sa` must have length `PlayerState::state_action_size()`. It is
+    /// used as scratch space while the actions are evaluated, so its
+    /// previous contents are ignored, and on return it holds the
+    /// state-action input of the returned action, ready for e.g.
+    /// `evaluate_q_grad`. The same buffer can be reused across calls.
+    /// Ties go to the last of the best actions.
+    pub fn greedy_action_into<M: LearningModel>(
+        &self,
+        model: &M,
+        actions: &[Action],
+        mut sa: ArrayViewMut<f32, Ix1>,
+    ) -> (Action, f32) {
+        assert_eq!(sa.dim(), PlayerState::state_action_size());
+        assert!(!actions.is_empty(), "action list should not be empty");
+
+        self.state.state_action_vector(sa.view_mut(), false, None);
+        let (state_view, mut action_view) = sa
+            .view_mut()
+            .split_at(Axis(0), self.state.state_vector_size());
+
+        // The state portion of the input is shared by every action, so
+        // only evaluate it once.
+        let prefix = model.prepare_prefix(&state_view.view());
+        let mut best = (actions[0], f32::MIN);
+        for a in actions {
+            self.state.action_vector(a, &mut action_view);
+            let q = model.evaluate_q_with_prefix(&prefix, &action_view.view());
+            if q >= best.1 {
+                best = (*a, q);
+            }
+        }
 
-        self.state.state_action_vector(sa, false, Some(max_action));
-        *max_action
+        self.state.action_vector(&best.0, &mut action_view);
+        best
+    }
+
+    /// Like `greedy_action_into`, without the Q-value.
+    pub fn greedy_action<M: LearningModel>(
+        &self,
+        model: &M,
+        actions: &[Action],
+        sa: ArrayViewMut<f32, Ix1>,
+    ) -> Action {
+        self.greedy_action_into(model, actions, sa).0
     }
 }
 
@@ -92,6 +515,13 @@ pub struct SarsaLambda<M: LearningModel> {
     model: M,
     engine: germanwhist::Round,
     param: SarsaLambdaParameters,
+    reward_fn: RewardFn,
+
+    /// source of randomness for dealing and exploration
+    rng: StdRng,
+
+    /// event lists reused across actions
+    events: Vec<Vec<GameEvent>>,
 }
 
 impl<M: LearningModel> SarsaLambda<M> {
@@ -99,21 +529,42 @@ impl<M: LearningModel> SarsaLambda<M> {
         rules: ScoringRules,
         model: M,
         param: SarsaLambdaParameters,
+    ) -> Result<SarsaLambda<M>, LearningModelError> {
+        let rng = StdRng::from_seed(&[thread_rng().gen::<usize>()][..]);
+        Self::with_rng(rules, model, param, rng)
+    }
+
+    /// Like `new`, using `rng` for dealing and exploration.
+    pub fn with_rng(
+        rules: ScoringRules,
+        model: M,
+        param: SarsaLambdaParameters,
+        rng: StdRng,
     ) -> Result<SarsaLambda<M>, LearningModelError> {
         let players = [
             SarsaPlayer::new(PlayerState::new(0), model.num_parameters()),
             SarsaPlayer::new(PlayerState::new(1), model.num_parameters()),
         ];
 
-        if PlayerState::state_size() + PlayerState::action_size() != model.input_size() {
-            return Err(LearningModelError::MismatchedSize);
+        let expected = PlayerState::state_action_size();
+        if model.input_size() != expected {
+            return Err(LearningModelError::MismatchedSize {
+                expected,
+                got: model.input_size(),
+            });
         }
+        param
+            .validate()
+            .map_err(LearningModelError::InvalidParameter)?;
 
         Ok(SarsaLambda {
             players,
             engine: Round::new(rules),
             model,
             param,
+            reward_fn: Box::new(|_, _| 0.0),
+            rng,
+            events: Vec::new(),
         })
     }
 
@@ -121,17 +572,143 @@ impl<M: LearningModel> SarsaLambda<M> {
         &self.model
     }
 
-    pub fn train_on_episode(&mut self, dual_train: bool) -> Result<(), ActionError> {
-        // start a new round
-        let ev = self.engine.start_round(None);
+    /// Strategy the training policy explores with.
+    pub fn exploration(&self) -> ExplorationStrategy {
+        self.param.exploration
+    }
+
+    /// Reseed the random number generator used for dealing and
+    /// exploration, so that training from the same model is
+    /// reproducible.
+    pub fn set_seed(&mut self, seed: &[usize]) {
+        self.rng = StdRng::from_seed(seed);
+    }
 
-        for (i, evs) in ev.iter().enumerate() {
-            self.players[i].e_trace.fill(0.0);
+    /// Set a shaping function providing intermediate rewards.
+    ///
+    /// The function is called for each event a player receives, with
+    /// that player's state before the event is applied, and the
+    /// rewards are added to the player's next update. By default, no
+    /// intermediate reward is given, and only the terminal reward is
+    /// used.
+    ///
+    /// To avoid changing the optimal policy, the shaping should be
+    /// potential-based: the total reward between two consecutive
+    /// decisions `s` and `s'` should be `gamma * phi(s') - phi(s)` for
+    /// some potential `phi` over states, with `phi` zero at the end of
+    /// the game. An arbitrary shaping function (e.g. a bonus per
+    /// trick won) can make the learned policy chase the shaping
+    /// reward rather than the game result.
+    pub fn set_reward_fn(&mut self, reward_fn: RewardFn) {
+        self.reward_fn = reward_fn;
+    }
+
+    /// Deliver the events to each player, accumulating any shaped
+    /// reward.
+    fn deliver_events(&mut self, events: &[Vec<GameEvent>]) {
+        let encoding = self.param.reward_encoding;
+        for (player, evs) in self.players.iter_mut().zip(events.iter()) {
             for e in evs {
-                self.players[i].state.on_event(e)
+                player.pending_reward += encoding.intermediate((self.reward_fn)(e, &player.state));
+                player.state.on_event(e);
+            }
+        }
+    }
+
+    /// Play the action, delivering the resulting events to each player.
+    fn play_and_deliver(&mut self, action: Action) -> Result<(), ActionError> {
+        let mut events = mem::take(&mut self.events);
+        let result = self.engine.play_action_into(action, &mut events);
+        self.deliver_events(&events);
+        self.events = events;
+        result
+    }
+
+    /// Play a full game with the current exploring policy for both
+    /// seats, without training, and return the transitions seen by the
+    /// player in `seat`.
+    pub fn record_episode(&mut self, seat: usize) -> Result<Vec<Transition>, ActionError> {
+        let ev = self.engine.start_round_with_rng(None, &mut self.rng);
+        for player in self.players.iter_mut() {
+            player.pending_reward = 0.0;
+        }
+        self.deliver_events(&ev);
+
+        let mut transitions = Vec::new();
+        let mut last: Option<Array1<f32>> = None;
+        let mut player_action = Array::zeros(PlayerState::state_action_size());
+
+        while !self.engine.is_game_over() {
+            let active = self.engine.active_player();
+            let possible_actions = self.engine.possible_actions();
+
+            if active == seat {
+                let player = &mut self.players[seat];
+                if let Some(state_action) = last.take() {
+                    let next_state_actions = possible_actions
+                        .iter()
+                        .map(|a| {
+                            let mut sa = Array::zeros(PlayerState::state_action_size());
+                            player
+                                .state
+                                .state_action_vector(sa.view_mut(), false, Some(a));
+                            sa
+                        })
+                        .collect();
+                    transitions.push(Transition {
+                        state_action,
+                        reward: player.pending_reward,
+                        next_state_actions,
+                    });
+                }
+                player.pending_reward = 0.0;
+            }
+
+            let chosen_action = self.players[active].exploring_action(
+                &self.model,
+                self.param.exploration,
+                &possible_actions,
+                player_action.view_mut(),
+                &mut self.rng,
+            );
+            if active == seat {
+                // the state portion is only filled in for greedy choices
+                self.players[seat].state.state_action_vector(
+                    player_action.view_mut(),
+                    false,
+                    Some(&chosen_action),
+                );
+                last = Some(player_action.clone());
             }
+
+            self.play_and_deliver(chosen_action)?;
+        }
+
+        if let Some(state_action) = last {
+            let result = self
+                .param
+                .reward_encoding
+                .terminal(seat, self.engine.winner());
+            transitions.push(Transition {
+                state_action,
+                reward: self.players[seat].pending_reward + result,
+                next_state_actions: Vec::new(),
+            });
         }
 
+        Ok(transitions)
+    }
+
+    pub fn train_on_episode(&mut self, dual_train: bool) -> Result<(), ActionError> {
+        // start a new round
+        let ev = self.engine.start_round_with_rng(None, &mut self.rng);
+
+        for player in self.players.iter_mut() {
+            player.e_trace.fill(0.0);
+            player.pending_reward = 0.0;
+        }
+        self.deliver_events(&ev);
+
         let mut player_action = Array::zeros(PlayerState::state_action_size());
         let mut grad = Array::zeros(self.model.num_parameters());
 
@@ -140,13 +717,14 @@ impl<M: LearningModel> SarsaLambda<M> {
             // Get the next action based on whose turn it is.
             let active = self.engine.active_player();
 
-            // choose the epsilon-greedy action for that player.
+            // choose the exploring action for that player.
             let possible_actions = self.engine.possible_actions();
-            let chosen_action = self.players[active].epsilon_greedy_action(
+            let chosen_action = self.players[active].exploring_action(
                 &self.model,
-                self.param.eps,
+                self.param.exploration,
                 &possible_actions,
                 player_action.view_mut(),
+                &mut self.rng,
             );
 
             // evaluate the gradient for the state-action pair
@@ -159,7 +737,7 @@ impl<M: LearningModel> SarsaLambda<M> {
                 {
                     let player = &self.players[active];
                     self.model.update_weights(
-                        self.param.gamma * q_predict - player.last_q,
+                        player.pending_reward + self.param.gamma * q_predict - player.last_q,
                         &player.e_trace,
                     );
                 }
@@ -167,35 +745,390 @@ impl<M: LearningModel> SarsaLambda<M> {
                 // update the eligibility trace
                 {
                     let player = &mut self.players[active];
-                    player.e_trace *= self.param.lambda * self.param.gamma;
-                    player.e_trace += &grad;
+                    update_trace(
+                        &mut player.e_trace,
+                        &grad,
+                        self.param.lambda * self.param.gamma,
+                        self.param.trace_mode,
+                        self.param.trace_clip,
+                    );
                     player.last_q = q_predict;
+                    player.pending_reward = 0.0;
                 }
             }
             // play the chosen action
-            let _ = self.engine.play_action(chosen_action)?;
+            self.play_and_deliver(chosen_action)?;
+        }
 
-            for (i, evs) in ev.iter().enumerate() {
-                for ev in evs {
-                    self.players[i].state.on_event(ev);
-                }
+        // Once the game is over, perform the final update based on the
+        // game result.
+        let winner = self.engine.winner();
+        for (seat, player) in self.players.iter().enumerate() {
+            let reward = self.param.reward_encoding.terminal(seat, winner);
+            self.model.update_weights(
+                player.pending_reward + reward - player.last_q,
+                &player.e_trace,
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Training state written by `SarsaLambda::save`.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+struct CheckpointRef<'a, M> {
+    rules: ScoringRules,
+    param: &'a SarsaLambdaParameters,
+    model: &'a M,
+}
+
+/// Training state read by `SarsaLambda::load`.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct Checkpoint<M> {
+    rules: ScoringRules,
+    param: SarsaLambdaParameters,
+    model: M,
+}
+
+#[cfg(feature = "serde")]
+impl<M> SarsaLambda<M>
+where
+    M: LearningModel + serde::Serialize + serde::de::DeserializeOwned,
+{
+    /// Write the training state, so that training can be paused and
+    /// resumed with `load`.
+    ///
+    /// The state holds the scoring rules, the parameters and the
+    /// model, including any learning rate schedule it keeps. The
+    /// random number generator and the reward function are not saved.
+    pub fn save<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serde::Serialize::serialize(
+            &CheckpointRef {
+                rules: self.engine.scoring_rules(),
+                param: &self.param,
+                model: &self.model,
+            },
+            serializer,
+        )
+    }
+
+    /// Resume training from a state written by `save`.
+    ///
+    /// The random number generator is freshly seeded, and the reward
+    /// function is the default; use `set_seed` and `set_reward_fn` to
+    /// restore them. Resuming with the seed used after saving plays
+    /// the same episodes as the original would have.
+    pub fn load<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let checkpoint: Checkpoint<M> = serde::Deserialize::deserialize(deserializer)?;
+        Self::new(checkpoint.rules, checkpoint.model, checkpoint.param)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        boltzmann_choice, update_trace, ExplorationStrategy, ParameterError, RewardEncoding,
+        SarsaLambda, SarsaLambdaParameters, SarsaPlayer, TraceClip, TraceMode,
+    };
+    use crate::germanwhist::{Action, PlayerState, Round};
+    use crate::learning::model::{LearningModel, LearningModelError};
+    use crate::learning::neural_net::{ActivationFunction, LayerDesc, NeuralNet};
+    use ndarray::prelude::*;
+    use rand::{SeedableRng, StdRng};
+
+    #[test]
+    fn test_update_trace() {
+        let grad = Array::from_vec(vec![1.0, 0.0, -2.0]);
+        let run = |mode, clip| {
+            let mut e = Array::zeros(3);
+            for _ in 0..10 {
+                update_trace(&mut e, &grad, 1.0, mode, clip);
             }
+            e
+        };
+
+        let e = run(TraceMode::Accumulating, TraceClip::None);
+        assert_eq!(e, Array::from_vec(vec![10.0, 0.0, -20.0]));
+        let e = run(TraceMode::Replacing, TraceClip::None);
+        assert_eq!(e, grad);
+
+        let e = run(TraceMode::Accumulating, TraceClip::max_abs(3.0).unwrap());
+        assert_eq!(e, Array::from_vec(vec![3.0, 0.0, -3.0]));
+        let e = run(TraceMode::Accumulating, TraceClip::l2_norm(1.0).unwrap());
+        assert!((e.dot(&e).sqrt() - 1.0).abs() < 1e-5);
+        assert!((e[2] / e[0] + 2.0).abs() < 1e-5);
+
+        for &limit in &[0.0, -1.0, f32::NAN, f32::INFINITY] {
+            assert!(TraceClip::max_abs(limit).is_err());
+            assert!(TraceClip::l2_norm(limit).is_err());
         }
 
-        // Once the game is over, perform the final update based on the game result.
-        let winner = self
-            .engine
-            .winner()
-            .expect("must be a winner at game-over phase.");
-        let loser = 1 - winner;
-        self.model.update_weights(
-            1.0 - self.players[winner].last_q,
-            &self.players[winner].e_trace,
+        // decay still applies where the gradient is zero
+        let mut e = Array::from_vec(vec![0.0, 4.0, 0.0]);
+        update_trace(&mut e, &grad, 0.5, TraceMode::Replacing, TraceClip::None);
+        assert_eq!(e, Array::from_vec(vec![1.0, 2.0, -2.0]));
+    }
+
+    #[test]
+    fn test_reward_encoding() {
+        let enc = RewardEncoding::ZeroOne;
+        assert_eq!(enc.terminal(0, Some(0)), 1.0);
+        assert_eq!(enc.terminal(1, Some(0)), 0.0);
+        assert_eq!(enc.terminal(1, None), 0.5);
+        assert_eq!(enc.intermediate(0.25), 0.25);
+
+        let enc = RewardEncoding::Symmetric;
+        assert_eq!(enc.terminal(0, Some(0)), 1.0);
+        assert_eq!(enc.terminal(1, Some(0)), -1.0);
+        assert_eq!(enc.terminal(1, None), 0.0);
+        assert_eq!(enc.intermediate(0.25), 0.5);
+    }
+
+    #[test]
+    fn test_mismatched_size() {
+        let sa = PlayerState::state_action_size();
+        let nn = NeuralNet::new(
+            &[LayerDesc::new(sa - 52, 1, ActivationFunction::Sigmoid)],
+            0.05,
+        )
+        .unwrap();
+        let e = SarsaLambda::new((0, 1), nn, SarsaLambdaParameters::default())
+            .err()
+            .unwrap();
+        assert_eq!(
+            e,
+            LearningModelError::MismatchedSize {
+                expected: sa,
+                got: sa - 52
+            }
         );
-        self.model.update_weights(
-            0.0 - self.players[loser].last_q,
-            &self.players[loser].e_trace,
+        assert_eq!(
+            e.to_string(),
+            format!(
+                "model takes {} inputs, but a state-action vector has {}",
+                sa - 52,
+                sa
+            )
+        );
+    }
+
+    #[test]
+    fn test_invalid_parameters() {
+        let sa = PlayerState::state_action_size();
+        let nn =
+            || NeuralNet::new(&[LayerDesc::new(sa, 1, ActivationFunction::Sigmoid)], 0.05).unwrap();
+
+        // e.g. a checkpoint holding a clip `max_abs` would reject
+        let param = SarsaLambdaParameters::default().with_trace_clip(TraceClip::MaxAbs(-1.0));
+        let e = SarsaLambda::new((0, 1), nn(), param).err().unwrap();
+        assert_eq!(
+            e,
+            LearningModelError::InvalidParameter(ParameterError::TraceClip(-1.0))
+        );
+        assert_eq!(
+            e.to_string(),
+            "trace clip limit -1 is not positive and finite"
+        );
+
+        for &t in &[0.0, -1.0, f32::NAN, f32::INFINITY] {
+            assert!(ExplorationStrategy::boltzmann(t).is_err());
+        }
+        let param = SarsaLambdaParameters::default()
+            .with_exploration(ExplorationStrategy::Boltzmann { temperature: 0.0 });
+        assert_eq!(
+            SarsaLambda::new((0, 1), nn(), param).err(),
+            Some(LearningModelError::InvalidParameter(
+                ParameterError::Temperature(0.0)
+            ))
+        );
+    }
+
+    #[test]
+    fn test_record_episode() {
+        let sa = PlayerState::state_action_size();
+        let nn = NeuralNet::new(
+            &[
+                LayerDesc::new(sa, 10, ActivationFunction::SymmetricSigmoid),
+                LayerDesc::new(10, 1, ActivationFunction::Sigmoid),
+            ],
+            0.05,
+        )
+        .unwrap();
+        let mut sl = SarsaLambda::new((0, 1), nn, SarsaLambdaParameters::default()).unwrap();
+        sl.set_reward_fn(Box::new(|_, _| 0.01));
+
+        let transitions = sl.record_episode(1).unwrap();
+
+        // one decision per trick
+        assert_eq!(transitions.len(), 26);
+        let (last, rest) = transitions.split_last().unwrap();
+        assert!(last.next_state_actions.is_empty());
+        for t in rest {
+            assert_eq!(t.state_action.len(), sa);
+            assert!(!t.next_state_actions.is_empty());
+            assert!(t.reward > 0.0);
+        }
+    }
+
+    #[test]
+    fn test_boltzmann_choice() {
+        let action = |c: &str| Action {
+            player: 0,
+            card: c.parse().unwrap(),
+        };
+        let mut rng = StdRng::from_seed(&[5][..]);
+        let n = 10000;
+        let count_best = |values: &[(Action, f32)], temperature, rng: &mut StdRng| {
+            (0..n)
+                .filter(|_| boltzmann_choice(values, temperature, rng).card == values[0].0.card)
+                .count()
+        };
+
+        // the best action is e^2 times as likely as the other
+        let values = [(action("A♠"), 1.0), (action("2♠"), 0.0)];
+        let p = count_best(&values, 0.5, &mut rng) as f32 / n as f32;
+        let expected = 1.0 / (1.0 + (-2.0f32).exp());
+        assert!((p - expected).abs() < 0.02, "{} vs {}", p, expected);
+
+        // large values don't overflow, and a low temperature is greedy
+        let values = [(action("A♠"), 1000.0), (action("2♠"), 999.0)];
+        assert_eq!(count_best(&values, 0.01, &mut rng), n);
+
+        let param = SarsaLambdaParameters::default()
+            .with_exploration(ExplorationStrategy::boltzmann(0.1).unwrap());
+        let nn = NeuralNet::new(
+            &[LayerDesc::new(
+                PlayerState::state_action_size(),
+                1,
+                ActivationFunction::Sigmoid,
+            )],
+            0.05,
+        )
+        .unwrap();
+        let mut sl = SarsaLambda::new((0, 1), nn, param).unwrap();
+        sl.set_seed(&[1]);
+        sl.train_on_episode(true).unwrap();
+        assert_eq!(sl.record_episode(0).unwrap().len(), 26);
+    }
+
+    #[test]
+    fn test_action_values() {
+        let sa = PlayerState::state_action_size();
+        let nn = NeuralNet::new(
+            &[
+                LayerDesc::new(sa, 10, ActivationFunction::SymmetricSigmoid),
+                LayerDesc::new(10, 1, ActivationFunction::Sigmoid),
+            ],
+            0.05,
+        )
+        .unwrap();
+
+        let mut round = Round::new((0, 1));
+        let mut state = PlayerState::new(0);
+        for ev in &round.start_round(0)[0] {
+            state.on_event(ev);
+        }
+        let player = SarsaPlayer::new(state, nn.num_parameters());
+        let actions = round.possible_actions();
+
+        let values = player.action_values(&nn, &actions);
+        assert_eq!(values.len(), actions.len());
+        let mut v = Array::zeros(sa);
+        for (&(action, q), a) in values.iter().zip(&actions) {
+            assert_eq!(action.card, a.card);
+            player
+                .state
+                .state_action_vector(v.view_mut(), false, Some(&action));
+            assert!((q - nn.evaluate_q(&v.view())).abs() < 1e-5);
+        }
+
+        // the greedy action is the one with the highest value
+        let best = player.greedy_action(&nn, &actions, v.view_mut());
+        let max = values.iter().map(|&(_, q)| q).fold(f32::MIN, f32::max);
+        let best_q = values.iter().find(|(a, _)| a.card == best.card).unwrap().1;
+        assert_eq!(best_q, max);
+
+        // the buffer holds the chosen action's input, whatever it held
+        // before
+        v.fill(7.0);
+        let (best, q) = player.greedy_action_into(&nn, &actions, v.view_mut());
+        assert_eq!(q, max);
+        let mut expected = Array::zeros(sa);
+        player
+            .state
+            .state_action_vector(expected.view_mut(), false, Some(&best));
+        assert_eq!(v, expected);
+        assert!((nn.evaluate_q(&v.view()) - q).abs() < 1e-5);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_save_load() {
+        let sa = PlayerState::state_action_size();
+        let nn = NeuralNet::new(
+            &[
+                LayerDesc::new(sa, 10, ActivationFunction::SymmetricSigmoid),
+                LayerDesc::new(10, 1, ActivationFunction::Sigmoid),
+            ],
+            0.05,
+        )
+        .unwrap();
+        let param = SarsaLambdaParameters::default().with_trace_mode(TraceMode::Replacing);
+        let mut sl = SarsaLambda::new((0, 1), nn, param).unwrap();
+        sl.set_seed(&[3]);
+        sl.train_on_episode(true).unwrap();
+
+        let mut saved = Vec::new();
+        sl.save(&mut serde_json::Serializer::new(&mut saved))
+            .unwrap();
+        let mut resumed: SarsaLambda<NeuralNet> =
+            SarsaLambda::load(&mut serde_json::Deserializer::from_slice(&saved)).unwrap();
+        assert_eq!(resumed.exploration(), sl.exploration());
+
+        sl.set_seed(&[4]);
+        resumed.set_seed(&[4]);
+        for _ in 0..2 {
+            sl.train_on_episode(true).unwrap();
+            resumed.train_on_episode(true).unwrap();
+        }
+        let v = Array::from_elem(sa, 0.5);
+        assert_eq!(
+            sl.current_model().evaluate_q(&v.view()),
+            resumed.current_model().evaluate_q(&v.view())
+        );
+
+        let mut a = Vec::new();
+        let mut b = Vec::new();
+        sl.save(&mut serde_json::Serializer::new(&mut a)).unwrap();
+        resumed
+            .save(&mut serde_json::Serializer::new(&mut b))
+            .unwrap();
+        assert_eq!(a, b);
+
+        // checkpoints from before `ExplorationStrategy` hold a bare eps
+        let old = String::from_utf8(a).unwrap().replace(
+            r#""exploration":{"EpsilonGreedy":{"eps":0.01}}"#,
+            r#""eps":0.05"#,
+        );
+        assert!(old.contains(r#""eps":0.05"#));
+        let old: SarsaLambda<NeuralNet> =
+            SarsaLambda::load(&mut serde_json::Deserializer::from_str(&old)).unwrap();
+        assert_eq!(
+            old.exploration(),
+            ExplorationStrategy::EpsilonGreedy { eps: 0.05 }
+        );
+
+        // a temperature the constructor would reject fails to load
+        let bad = String::from_utf8(b).unwrap().replace(
+            r#"{"EpsilonGreedy":{"eps":0.01}}"#,
+            r#"{"Boltzmann":{"temperature":-1.0}}"#,
+        );
+        assert!(bad.contains("Boltzmann"));
+        assert!(
+            SarsaLambda::<NeuralNet>::load(&mut serde_json::Deserializer::from_str(&bad)).is_err()
         );
-        Ok(())
     }
 }
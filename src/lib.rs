@@ -1,9 +1,23 @@
+// links the BLAS implementation that ndarray's `blas` feature calls
+#[cfg(feature = "blas")]
+extern crate blas_src;
+
 pub mod cards;
 pub mod germanwhist;
 pub mod hand_belief;
+pub mod lean_belief;
 pub mod learning;
 
 pub use germanwhist::engine::{Action, ActionError, GameEvent, Round};
 pub use germanwhist::state::GameState;
-pub use hand_belief::HandBelief;
-pub use learning::neural_net::{ActivationFunction, LayerDesc, NeuralNet};
+pub use hand_belief::{
+    BeliefCalibration, BeliefCheckpoint, BeliefInconsistency, HandBelief, OpponentModel,
+    PlayChoiceModel,
+};
+pub use lean_belief::LeanBelief;
+pub use learning::linear::LinearModel;
+pub use learning::model::BenchmarkSuite;
+pub use learning::neural_net::{
+    ActivationFunction, InitScheme, LayerDesc, LayerStats, NetConstructionError, NeuralNet,
+    PrefixCache,
+};